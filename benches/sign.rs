@@ -0,0 +1,32 @@
+//! 请求签名的基准测试：对比每次签名时重建 SigningKey 与使用预先构建的 `LocalSigner`。
+//! 运行：`cargo bench --bench sign`
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rsa::pkcs1v15::SigningKey;
+use rsa::sha2::Sha256;
+use rsa::signature::{RandomizedSigner, SignatureEncoding};
+use wechatpay::credential::{load_private_key_file, LocalSigner};
+
+const MESSAGE: &[u8] = b"POST\n/v3/pay/transactions/jsapi\n1554208460\n593BEC0C930BF1AFEB40B4A08C8FB242\n{\"appid\":\"wxd678efh567hg6787\",\"mchid\":\"1230000109\"}\n";
+
+fn bench_sign(c: &mut Criterion) {
+    let private_key = load_private_key_file("testdata/apiclient_key.pem", None).unwrap();
+
+    let mut group = c.benchmark_group("sign");
+    group.bench_function("rebuild_signing_key", |b| {
+        b.iter(|| {
+            let signing_key = SigningKey::<Sha256>::new(private_key.clone());
+            signing_key
+                .sign_with_rng(&mut rand::thread_rng(), black_box(MESSAGE))
+                .to_vec()
+        })
+    });
+    let signer = LocalSigner::new(private_key.clone());
+    group.bench_function("local_signer", |b| {
+        b.iter(|| signer.sign_message(black_box(MESSAGE)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_sign);
+criterion_main!(benches);
@@ -0,0 +1,126 @@
+//! actix-web 集成。启用 `actix-web` feature 后可用。
+//!
+//! `WechatPayNotify<T>` 实现了 `FromRequest`，自动完成微信支付通知的验签、解密及反序列化：
+//! ```ignore
+//! async fn notify(WechatPayNotify(event): WechatPayNotify) -> HttpResponse {
+//!     match event {
+//!         NotificationEvent::Trade(trade) => { /* ... */ }
+//!         _ => {}
+//!     }
+//!     HttpResponse::NoContent().finish()
+//! }
+//!
+//! App::new()
+//!     .app_data(web::Data::new(client))
+//!     .route("/notify", web::post().to(notify))
+//! ```
+//! 其中 `WechatPayClient` 须以 `web::Data<WechatPayClient>` 形式注册。
+//! extractor 不使用通知去重存储，重复的通知同样会被解析。需要去重时可使用
+//! `WechatPayClient::process_notification` 或 `NotificationDispatcher`。
+
+use crate::client::WechatPayClient;
+use crate::error::WechatPayError;
+use crate::notify::{FromNotification, NotificationEvent, NotifyResponse, WechatPayNotification};
+use actix_web::dev::Payload;
+use actix_web::error::InternalError;
+use actix_web::web::{Bytes, Data};
+use actix_web::{Error, FromRequest, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use http::HeaderMap;
+
+/// 微信支付通知 extractor。T 默认为 `NotificationEvent`，也可以是 `TradeQueryResponse` 等具体类型。
+/// 验签失败时返回 401，解析或解密失败时返回 400，body 为 `{"code":"FAIL","message":"..."}`。
+#[derive(Debug, Clone)]
+pub struct WechatPayNotify<T = NotificationEvent>(pub T);
+
+impl<T> FromRequest for WechatPayNotify<T>
+where
+    T: FromNotification + 'static,
+{
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let client = req.app_data::<Data<WechatPayClient>>().cloned();
+        let headers: HeaderMap = req
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let body = Bytes::from_request(req, payload);
+
+        Box::pin(async move {
+            let client = client.ok_or_else(|| {
+                actix_web::error::ErrorInternalServerError(
+                    "`web::Data<WechatPayClient>` is not configured",
+                )
+            })?;
+            let body = body.await?;
+
+            client
+                .verify_notification_parts(&headers, &body)
+                .await
+                .map_err(rejection)?;
+            let noti: WechatPayNotification =
+                serde_json::from_slice(&body).map_err(|e| rejection(e.into()))?;
+            let event = T::from_notification(&client, &noti).map_err(rejection)?;
+            Ok(WechatPayNotify(event))
+        })
+    }
+}
+
+fn rejection(e: WechatPayError) -> Error {
+    let (status, body) = NotifyResponse::from_error(&e);
+    let res = HttpResponse::build(status)
+        .content_type("application/json")
+        .body(body.to_json());
+    InternalError::from_response(e, res).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{mock_client, mock_notification};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    #[tokio::test]
+    async fn test_extract_signed_notification() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        let plain = r#"{"stock_creator_mchid":"1900000001","stock_id":"9856000","coupon_id":"98674556","status":"USED"}"#;
+        let body = mock_notification("EV-1", "COUPON.USE", "coupon", plain);
+
+        let mut req = TestRequest::post().app_data(Data::new(client));
+        for (name, value) in mock.signature_headers(&body)?.iter() {
+            req = req.insert_header((name.as_str(), value.to_str()?));
+        }
+        let (req, mut payload) = req.set_payload(body).to_http_parts();
+        let WechatPayNotify(event) =
+            WechatPayNotify::<NotificationEvent>::from_request(&req, &mut payload)
+                .await
+                .map_err(|e| anyhow::anyhow!("rejected: {}", e))?;
+        match event {
+            NotificationEvent::Coupon(coupon) => assert_eq!(coupon.stock_id, "9856000"),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_unsigned_notification() -> anyhow::Result<()> {
+        let (client, _) = mock_client().await?;
+
+        let (req, mut payload) = TestRequest::post()
+            .app_data(Data::new(client))
+            .set_payload("{}")
+            .to_http_parts();
+        let err = WechatPayNotify::<NotificationEvent>::from_request(&req, &mut payload)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::UNAUTHORIZED
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+//! 商户开户意愿确认。
+//! 服务商、收单机构为特约商户提交开户意愿申请单，审核通过后由商户超级管理员扫码确认开户意愿。
+//! 申请单中的联系人、证件等敏感信息声明为 `EncryptedString`，提交时自动加密。
+
+use crate::client::WechatPayClient;
+use crate::error::Result;
+use crate::sensitive::EncryptedString;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+impl WechatPayClient {
+    /// 提交开户意愿申请单。申请单中的敏感信息会被自动加密。
+    pub async fn submit_apply4subject(
+        &self,
+        params: &Apply4SubjectParams,
+    ) -> Result<Apply4SubjectResponse> {
+        let url = format!("{}/apply4subject/applyment", self.base_url);
+        let req = self.json_request(Method::POST, &url, params)?;
+        let res = self.execute_idempotent(req).await?;
+        self.json_response(res).await
+    }
+
+    /// 通过业务申请编号(business_code)查询申请单审核结果。
+    pub async fn query_apply4subject_by_business_code(
+        &self,
+        business_code: &str,
+    ) -> Result<Apply4SubjectQueryResponse> {
+        let url = format!(
+            "{}/apply4subject/applyment?business_code={}",
+            self.base_url, business_code
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 通过申请单编号(applyment_id)查询申请单审核结果。
+    pub async fn query_apply4subject_by_applyment_id(
+        &self,
+        applyment_id: u64,
+    ) -> Result<Apply4SubjectQueryResponse> {
+        let url = format!(
+            "{}/apply4subject/applyment?applyment_id={}",
+            self.base_url, applyment_id
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 通过业务申请编号(business_code)撤销申请单。
+    pub async fn cancel_apply4subject_by_business_code(&self, business_code: &str) -> Result<()> {
+        let url = format!(
+            "{}/apply4subject/applyment/{}/cancel",
+            self.base_url, business_code
+        );
+        let req = self.client.post(url).build()?;
+        self.execute(req).await?;
+        Ok(())
+    }
+
+    /// 通过申请单编号(applyment_id)撤销申请单。
+    pub async fn cancel_apply4subject_by_applyment_id(&self, applyment_id: u64) -> Result<()> {
+        let url = format!(
+            "{}/apply4subject/applyment/{}/cancel",
+            self.base_url, applyment_id
+        );
+        let req = self.client.post(url).build()?;
+        self.execute(req).await?;
+        Ok(())
+    }
+}
+
+/// 开户意愿申请单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Apply4SubjectParams {
+    /// 渠道商户号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub channel_id: Option<String>,
+    /// 业务申请编号，服务商自定义，唯一
+    pub business_code: String,
+    /// 联系人信息
+    pub contact_info: Apply4SubjectContactInfo,
+    /// 主体信息
+    pub subject_info: Apply4SubjectSubjectInfo,
+    /// 法人身份信息
+    pub identification_info: Apply4SubjectIdentificationInfo,
+    /// 补充材料
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub addition_info: Option<Apply4SubjectAdditionInfo>,
+}
+
+/// 联系人信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Apply4SubjectContactInfo {
+    /// 联系人姓名
+    pub name: EncryptedString,
+    /// 联系人手机号
+    pub mobile: EncryptedString,
+    /// 联系人身份证号码
+    pub id_card_number: EncryptedString,
+}
+
+/// 主体信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Apply4SubjectSubjectInfo {
+    /// 主体类型
+    /// * SUBJECT_TYPE_ENTERPRISE：企业
+    /// * SUBJECT_TYPE_INSTITUTIONS_CLONED：事业单位
+    /// * SUBJECT_TYPE_INDIVIDUAL：个体工商户
+    /// * SUBJECT_TYPE_OTHERS：社会组织
+    /// * SUBJECT_TYPE_MICRO：小微商户
+    /// * SUBJECT_TYPE_GOVERNMENT：政府机关
+    pub subject_type: String,
+    /// 营业执照信息，主体为企业、个体工商户时必填
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub business_licence_info: Option<Apply4SubjectBusinessLicenceInfo>,
+    /// 登记证书信息，主体为事业单位、社会组织、政府机关时必填
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub certificate_info: Option<Apply4SubjectCertificateInfo>,
+}
+
+/// 营业执照信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Apply4SubjectBusinessLicenceInfo {
+    /// 注册号/统一社会信用代码
+    pub licence_number: String,
+    /// 营业执照照片的 media_id，通过图片上传接口获取
+    pub licence_copy: String,
+    /// 商户名称，须与营业执照一致
+    pub merchant_name: String,
+    /// 法定代表人/经营者姓名
+    pub legal_person: String,
+    /// 注册地址
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub company_address: Option<String>,
+    /// 营业期限，如 `["2017-10-28","长期"]`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub licence_valid_date: Option<String>,
+}
+
+/// 登记证书信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Apply4SubjectCertificateInfo {
+    /// 登记证书类型，如 CERTIFICATE_TYPE_2388(事业单位法人证书)
+    pub cert_type: String,
+    /// 证书编号
+    pub cert_number: String,
+    /// 登记证书照片的 media_id
+    pub cert_copy: String,
+    /// 商户名称，须与登记证书一致
+    pub merchant_name: String,
+    /// 法定代表人
+    pub legal_person: String,
+    /// 注册地址
+    pub company_address: String,
+    /// 有效期限开始日期，格式为 yyyy-MM-dd
+    pub effect_time: String,
+    /// 有效期限结束日期，格式为 yyyy-MM-dd 或“长期”
+    pub expire_time: String,
+}
+
+/// 法人身份信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Apply4SubjectIdentificationInfo {
+    /// 证件类型，如 IDENTIFICATION_TYPE_IDCARD(中国大陆居民身份证)
+    pub identification_type: String,
+    /// 证件姓名
+    pub identification_name: EncryptedString,
+    /// 证件号码
+    pub identification_number: EncryptedString,
+    /// 证件有效期，如 `["2017-10-28","长期"]`
+    pub identification_valid_date: String,
+    /// 证件正面照片的 media_id
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub identification_front_copy: Option<String>,
+    /// 证件反面照片的 media_id
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub identification_back_copy: Option<String>,
+}
+
+/// 补充材料
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Apply4SubjectAdditionInfo {
+    /// 待确认的商户号列表
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub confirm_mchid_list: Vec<String>,
+}
+
+/// 提交开户意愿申请单的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Apply4SubjectResponse {
+    /// 微信支付申请单编号
+    pub applyment_id: u64,
+}
+
+/// 开户意愿申请单的审核结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Apply4SubjectQueryResponse {
+    /// 申请单状态
+    /// * APPLYMENT_STATE_WAITTING_FOR_AUDIT：审核中
+    /// * APPLYMENT_STATE_EDITTING：编辑中
+    /// * APPLYMENT_STATE_WAITTING_FOR_CONFIRM_CONTACT：待确认联系信息
+    /// * APPLYMENT_STATE_WAITTING_FOR_CONFIRM_LEGALPERSON：待账户验证
+    /// * APPLYMENT_STATE_PASSED：审核通过
+    /// * APPLYMENT_STATE_REJECTED：审核驳回
+    /// * APPLYMENT_STATE_FREEZED：已冻结
+    /// * APPLYMENT_STATE_CANCELED：已作废
+    pub applyment_state: String,
+    /// 小程序码图片，base64 编码。待确认联系信息、待账户验证时返回，须由商户扫码确认。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub qrcode_data: Option<String>,
+    /// 驳回参数，审核驳回时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub reject_param: Option<String>,
+    /// 驳回原因，审核驳回时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub reject_reason: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock_client;
+    use reqwest::StatusCode;
+
+    #[tokio::test]
+    async fn test_apply4subject() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::POST,
+            "/v3/apply4subject/applyment",
+            StatusCode::OK,
+            r#"{"applyment_id":20000011111}"#,
+        );
+        mock.on(
+            Method::POST,
+            "/v3/apply4subject/applyment/1900013511_10000/cancel",
+            StatusCode::NO_CONTENT,
+            "",
+        );
+
+        let params = Apply4SubjectParams {
+            channel_id: None,
+            business_code: "1900013511_10000".to_string(),
+            contact_info: Apply4SubjectContactInfo {
+                name: "张三".into(),
+                mobile: "13900000000".into(),
+                id_card_number: "110101199003071234".into(),
+            },
+            subject_info: Apply4SubjectSubjectInfo {
+                subject_type: "SUBJECT_TYPE_INDIVIDUAL".to_string(),
+                business_licence_info: Some(Apply4SubjectBusinessLicenceInfo {
+                    licence_number: "914201123033363296".to_string(),
+                    licence_copy: "47ZC6GC-vnrbEg05InE4d2I6_H7I4".to_string(),
+                    merchant_name: "张三餐饮店".to_string(),
+                    legal_person: "张三".to_string(),
+                    company_address: None,
+                    licence_valid_date: None,
+                }),
+                certificate_info: None,
+            },
+            identification_info: Apply4SubjectIdentificationInfo {
+                identification_type: "IDENTIFICATION_TYPE_IDCARD".to_string(),
+                identification_name: "张三".into(),
+                identification_number: "110101199003071234".into(),
+                identification_valid_date: r#"["2017-10-28","长期"]"#.to_string(),
+                identification_front_copy: None,
+                identification_back_copy: None,
+            },
+            addition_info: None,
+        };
+        let res = client.submit_apply4subject(&params).await?;
+        assert_eq!(res.applyment_id, 20000011111);
+
+        let req = &mock.requests()[0];
+        let body: serde_json::Value = serde_json::from_slice(&req.body)?;
+        assert_ne!(body["contact_info"]["mobile"], "13900000000");
+        assert_ne!(
+            body["identification_info"]["identification_number"],
+            "110101199003071234"
+        );
+        assert_eq!(
+            body["subject_info"]["subject_type"],
+            "SUBJECT_TYPE_INDIVIDUAL"
+        );
+
+        client
+            .cancel_apply4subject_by_business_code("1900013511_10000")
+            .await?;
+        Ok(())
+    }
+}
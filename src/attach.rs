@@ -0,0 +1,84 @@
+//! 附加数据(attach)的结构化存取。
+//! 下单时传入的 attach 会在查单及支付通知中原样返回，常用于携带订单上下文。
+//! 这里将任意可序列化的结构编码为 JSON 字符串作为 attach，并在查单、通知中解码回来。
+
+use crate::error::{Result, WechatPayError};
+use crate::trade::TradeQueryResponse;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// attach 的长度上限(字符数)
+pub const ATTACH_MAX_LEN: usize = 128;
+
+/// 将 value 编码为 attach。编码结果超过 `ATTACH_MAX_LEN` 个字符时返回 `InvalidParams`。
+pub fn encode_attach<T: Serialize>(value: &T) -> Result<String> {
+    let attach = serde_json::to_string(value)?;
+    let len = attach.chars().count();
+    if len > ATTACH_MAX_LEN {
+        return Err(WechatPayError::InvalidParams(format!(
+            "attach too long: {} chars, max: {}",
+            len, ATTACH_MAX_LEN
+        )));
+    }
+    Ok(attach)
+}
+
+/// 将 attach 解码为 T。
+pub fn decode_attach<T: DeserializeOwned>(attach: &str) -> Result<T> {
+    Ok(serde_json::from_str(attach)?)
+}
+
+impl TradeQueryResponse {
+    /// 将订单的 attach 解码为 T，无 attach 时返回 None。
+    pub fn attach_as<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        self.attach.as_deref().map(decode_attach).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct OrderContext {
+        user_id: u64,
+        coupon: Option<String>,
+    }
+
+    #[test]
+    fn test_attach() -> anyhow::Result<()> {
+        let ctx = OrderContext {
+            user_id: 10086,
+            coupon: Some("新人券".to_string()),
+        };
+        let attach = encode_attach(&ctx)?;
+        assert_eq!(decode_attach::<OrderContext>(&attach)?, ctx);
+
+        let trade: TradeQueryResponse = serde_json::from_value(serde_json::json!({
+            "appid": "wxd678efh567hg6787",
+            "mchid": "1230000109",
+            "out_trade_no": "1217752501201407033233368018",
+            "trade_state": "SUCCESS",
+            "trade_state_desc": "支付成功",
+            "attach": attach,
+        }))?;
+        assert_eq!(trade.attach_as::<OrderContext>()?, Some(ctx));
+
+        // 中文按字符计数
+        let ctx = OrderContext {
+            user_id: 1,
+            coupon: Some("券".repeat(100)),
+        };
+        assert!(encode_attach(&ctx).is_ok());
+        let ctx = OrderContext {
+            user_id: 1,
+            coupon: Some("券".repeat(110)),
+        };
+        assert!(matches!(
+            encode_attach(&ctx),
+            Err(WechatPayError::InvalidParams(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+//! 调用审计。
+//! 实现 `CallRecorder` 并通过 `WechatPayClientBuilder::call_recorder` 注册，即可记录每次 API 调用的
+//! 方法、路径、请求及响应摘要，用于支付系统的审计留痕。
+//! 记录前会自动脱敏：Authorization header、openid 及证书密文不会出现在记录中。
+
+use crate::error::{Result, WechatPayError};
+use crate::transport::BufferedResponse;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::{Method, Request, Response, StatusCode, Url};
+use std::fmt;
+use std::time::Duration;
+
+/// 脱敏后的占位符
+pub const REDACTED: &str = "***";
+
+/// 一次 API 调用的审计记录，均已脱敏。
+#[derive(Debug, Clone)]
+pub struct CallRecord {
+    /// 请求方法
+    pub method: Method,
+    /// 请求 URL
+    pub url: String,
+    /// 请求 header
+    pub request_headers: HeaderMap,
+    /// 请求 body。非 JSON 的 body 仅记录长度。
+    pub request_body: Option<String>,
+    /// 响应状态码。网络错误等未收到响应时为 None。
+    pub status: Option<StatusCode>,
+    /// 响应 body。非 JSON 的 body 仅记录长度。
+    pub response_body: Option<String>,
+    /// 调用失败时的错误信息
+    pub error: Option<String>,
+    /// 耗时
+    pub elapsed: Duration,
+}
+
+/// 调用审计记录器。
+/// 每次实际发送 HTTP 请求(包括重试)时调用一次。文件下载的响应 body 不经过审计。
+pub trait CallRecorder: Send + Sync {
+    fn record(&self, record: &CallRecord);
+}
+
+impl fmt::Debug for dyn CallRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CallRecorder")
+    }
+}
+
+/// 已发出、尚未收到响应的调用
+pub(crate) struct PendingCall {
+    method: Method,
+    url: String,
+    request_headers: HeaderMap,
+    request_body: Option<String>,
+}
+
+impl PendingCall {
+    pub(crate) fn new(req: &Request) -> PendingCall {
+        let mut request_headers = req.headers().clone();
+        if request_headers.contains_key(AUTHORIZATION) {
+            request_headers.insert(AUTHORIZATION, HeaderValue::from_static(REDACTED));
+        }
+        PendingCall {
+            method: req.method().clone(),
+            url: redact_url(req.url()),
+            request_headers,
+            request_body: req.body().map(|body| {
+                body.as_bytes()
+                    .map_or_else(|| "<stream>".to_string(), redact_body)
+            }),
+        }
+    }
+
+    /// 记录调用结果。record_body 为 true 时，成功时需读取响应 body，返回的 Response 的 body 已读入内存；
+    /// 文件下载时为 false，不读取响应 body。
+    pub(crate) async fn finish(
+        self,
+        recorder: &dyn CallRecorder,
+        res: Result<Response>,
+        elapsed: Duration,
+        record_body: bool,
+    ) -> Result<Response> {
+        let (res, status, response_body, error) = match res {
+            Ok(res) if !record_body => {
+                let status = res.status();
+                (Ok(res), Some(status), None, None)
+            }
+            Ok(res) => {
+                let status = res.status();
+                let (res, body) = buffer_response(res).await?;
+                (Ok(res), Some(status), Some(redact_body(&body)), None)
+            }
+            Err(e) => {
+                let status = match &e {
+                    WechatPayError::Api(e) => e.status(),
+                    WechatPayError::Http(e) => e.status(),
+                    _ => None,
+                };
+                let error = e.to_string();
+                (Err(e), status, None, Some(error))
+            }
+        };
+        recorder.record(&CallRecord {
+            method: self.method,
+            url: self.url,
+            request_headers: self.request_headers,
+            request_body: self.request_body,
+            status,
+            response_body,
+            error,
+            elapsed,
+        });
+        res
+    }
+}
+
+/// 读取响应 body，返回 body 已读入内存的 Response 及 body。
+async fn buffer_response(res: Response) -> Result<(Response, bytes::Bytes)> {
+    let res = BufferedResponse::read(res, None).await?;
+    let body = res.body.clone();
+    Ok((res.into(), body))
+}
+
+/// 对 JSON body 脱敏，非 JSON 的 body 仅返回长度。
+pub fn redact_body(body: &[u8]) -> String {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            redact_json(&mut value);
+            value.to_string()
+        }
+        Err(_) => format!("<{} bytes>", body.len()),
+    }
+}
+
+/// 对 JSON 脱敏：openid 及证书密文替换为 `REDACTED`。
+pub fn redact_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (k, v) in object.iter_mut() {
+                if is_sensitive_key(k) {
+                    *v = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(v);
+                }
+            }
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+fn is_sensitive_key(key: &str) -> bool {
+    key.ends_with("openid") || key == "encrypt_certificate" || key == "certificate"
+}
+
+fn redact_url(url: &Url) -> String {
+    if !url.query_pairs().any(|(k, _)| is_sensitive_key(&k)) {
+        return url.to_string();
+    }
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| {
+            let v = if is_sensitive_key(&k) {
+                REDACTED.to_string()
+            } else {
+                v.into_owned()
+            };
+            (k.into_owned(), v)
+        })
+        .collect();
+    let mut url = url.clone();
+    url.query_pairs_mut().clear().extend_pairs(pairs);
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock_client_with;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct MemoryRecorder(Mutex<Vec<CallRecord>>);
+
+    impl CallRecorder for Arc<MemoryRecorder> {
+        fn record(&self, record: &CallRecord) {
+            self.0.lock().unwrap().push(record.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_recorder() -> anyhow::Result<()> {
+        let recorder = Arc::new(MemoryRecorder::default());
+        let (client, mock) = mock_client_with(|builder| {
+            builder.call_recorder(recorder.clone());
+        })
+        .await?;
+        mock.on(
+            Method::POST,
+            "/v3/custom/json",
+            StatusCode::OK,
+            r#"{"payer":{"openid":"oUpF8uMuAJO_M2pxb1Q9zNjWeS6o"},"trade_state":"SUCCESS"}"#,
+        );
+
+        let url = format!("{}/custom/json?sub_openid=o-abc&limit=10", client.base_url);
+        let req = client
+            .client
+            .post(url)
+            .json(&serde_json::json!({"openid": "o-xyz", "amount": {"total": 1}}))
+            .build()?;
+        let res: serde_json::Value = client.execute_json(req).await?;
+        assert_eq!(res["trade_state"], "SUCCESS");
+
+        let records = recorder.0.lock().unwrap();
+        let record = &records[0];
+        assert_eq!(record.status, Some(StatusCode::OK));
+        assert_eq!(record.request_headers[AUTHORIZATION], REDACTED);
+        assert!(!record.url.contains("o-abc"));
+        assert!(record.url.contains("limit=10"));
+        let request_body = record.request_body.as_deref().unwrap();
+        assert!(!request_body.contains("o-xyz"));
+        assert!(request_body.contains(r#""total":1"#));
+        let response_body = record.response_body.as_deref().unwrap();
+        assert!(!response_body.contains("oUpF8uMuAJO_M2pxb1Q9zNjWeS6o"));
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! axum 集成。启用 `axum` feature 后可用。
+//!
+//! `WechatPayNotify<T>` 是一个 extractor，自动完成微信支付通知的验签、解密及反序列化：
+//! ```ignore
+//! async fn notify(WechatPayNotify(event): WechatPayNotify) -> StatusCode {
+//!     match event {
+//!         NotificationEvent::Trade(trade) => { /* ... */ }
+//!         _ => {}
+//!     }
+//!     StatusCode::NO_CONTENT
+//! }
+//!
+//! let app = Router::new().route("/notify", post(notify)).with_state(client);
+//! ```
+//! 其中 state 须能通过 `FromRef` 取得 `WechatPayClient`。
+//! extractor 不使用通知去重存储，重复的通知同样会被解析。需要去重时可使用
+//! `WechatPayClient::process_notification` 或 `NotificationDispatcher`。
+
+use crate::client::WechatPayClient;
+use crate::error::WechatPayError;
+use crate::notify::{FromNotification, NotificationEvent, NotifyResponse, WechatPayNotification};
+use axum::async_trait;
+use axum::body::{Bytes, HttpBody};
+use axum::extract::{FromRef, FromRequest};
+use axum::response::{IntoResponse, Response};
+use axum::BoxError;
+use http::{header, Request};
+
+/// 微信支付通知 extractor。T 默认为 `NotificationEvent`，也可以是 `TradeQueryResponse` 等具体类型。
+/// 验签失败时返回 401，解析或解密失败时返回 400，body 为 `{"code":"FAIL","message":"..."}`。
+#[derive(Debug, Clone)]
+pub struct WechatPayNotify<T = NotificationEvent>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for WechatPayNotify<T>
+where
+    T: FromNotification,
+    S: Send + Sync,
+    WechatPayClient: FromRef<S>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let client = WechatPayClient::from_ref(state);
+        let headers = req.headers().clone();
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        client
+            .verify_notification_parts(&headers, &body)
+            .await
+            .map_err(rejection)?;
+        let noti: WechatPayNotification =
+            serde_json::from_slice(&body).map_err(|e| rejection(e.into()))?;
+        let event = T::from_notification(&client, &noti).map_err(rejection)?;
+        Ok(WechatPayNotify(event))
+    }
+}
+
+fn rejection(e: WechatPayError) -> Response {
+    let (status, body) = NotifyResponse::from_error(&e);
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        body.to_json(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{mock_client, mock_notification};
+    use http::StatusCode;
+
+    #[tokio::test]
+    async fn test_extract_signed_notification() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        let plain = r#"{"stock_creator_mchid":"1900000001","stock_id":"9856000","coupon_id":"98674556","status":"USED"}"#;
+        let body = mock_notification("EV-1", "COUPON.USE", "coupon", plain);
+
+        let mut req = Request::post("/notify").body(axum::body::Body::from(body.clone()))?;
+        *req.headers_mut() = mock.signature_headers(&body)?;
+        let WechatPayNotify(event) =
+            WechatPayNotify::<NotificationEvent>::from_request(req, &client)
+                .await
+                .map_err(|res| anyhow::anyhow!("rejected: {}", res.status()))?;
+        match event {
+            NotificationEvent::Coupon(coupon) => assert_eq!(coupon.stock_id, "9856000"),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_unsigned_notification() -> anyhow::Result<()> {
+        let (client, _) = mock_client().await?;
+
+        let req = Request::post("/notify").body(axum::body::Body::from("{}"))?;
+        let res = WechatPayNotify::<NotificationEvent>::from_request(req, &client)
+            .await
+            .unwrap_err();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        Ok(())
+    }
+}
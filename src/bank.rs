@@ -0,0 +1,179 @@
+//! 银行组件。
+//! 特约商户进件、商家转账等场景填写银行信息时，需使用微信支付的银行编码及省市编码。
+
+use crate::client::WechatPayClient;
+use crate::error::Result;
+use crate::paginate::{Page, Paginated};
+use serde::{Deserialize, Serialize};
+
+impl WechatPayClient {
+    /// 根据银行卡号查询开户银行。卡号会被自动加密。
+    pub async fn search_banks_by_account_number(&self, account_number: &str) -> Result<Vec<Bank>> {
+        let encryptor = self.sensitive_encryptor()?;
+        let url = format!(
+            "{}/capital/capitallhh/banks/search-banks-by-bank-account",
+            self.base_url
+        );
+        let mut req = self
+            .client
+            .get(url)
+            .query(&[("account_number", encryptor.encrypt(account_number)?)])
+            .build()?;
+        encryptor.apply(&mut req)?;
+        let res: BankList = self.execute_json(req).await?;
+        Ok(res.data)
+    }
+
+    /// 查询支持个人业务的银行列表。
+    pub async fn query_personal_banks(&self, offset: u32, limit: u32) -> Result<BankList> {
+        let url = format!(
+            "{}/capital/capitallhh/banks/personal-banking?offset={}&limit={}",
+            self.base_url, offset, limit
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 支持个人业务的银行列表，自动翻页。
+    pub fn query_personal_banks_paginated(&self, limit: u32) -> Paginated<Bank> {
+        let client = self.clone();
+        Paginated::new(limit, move |offset, limit| {
+            let client = client.clone();
+            async move {
+                client
+                    .query_personal_banks(offset, limit)
+                    .await
+                    .map(Page::from)
+            }
+        })
+    }
+
+    /// 查询省份列表。
+    pub async fn query_provinces(&self) -> Result<Vec<Province>> {
+        #[derive(Debug, Deserialize)]
+        struct ProvinceList {
+            #[serde(default)]
+            data: Vec<Province>,
+        }
+
+        let url = format!("{}/capital/capitallhh/areas/provinces", self.base_url);
+        let req = self.client.get(url).build()?;
+        let res: ProvinceList = self.execute_json(req).await?;
+        Ok(res.data)
+    }
+
+    /// 查询省份下的城市列表。
+    pub async fn query_cities(&self, province_code: i32) -> Result<Vec<City>> {
+        #[derive(Debug, Deserialize)]
+        struct CityList {
+            #[serde(default)]
+            data: Vec<City>,
+        }
+
+        let url = format!(
+            "{}/capital/capitallhh/areas/provinces/{}/cities",
+            self.base_url, province_code
+        );
+        let req = self.client.get(url).build()?;
+        let res: CityList = self.execute_json(req).await?;
+        Ok(res.data)
+    }
+}
+
+/// 银行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Bank {
+    /// 银行别名，如“招商银行”
+    pub bank_alias: String,
+    /// 银行别名编码，查询支行时使用
+    pub bank_alias_code: String,
+    /// 开户银行，进件时填写此值
+    pub account_bank: String,
+    /// 开户银行编码
+    pub account_bank_code: i32,
+    /// 是否需要填写支行
+    pub need_bank_branch: bool,
+}
+
+/// 银行列表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BankList {
+    /// 银行总数
+    #[serde(default)]
+    pub total_count: u32,
+    #[serde(default)]
+    pub data: Vec<Bank>,
+}
+
+impl From<BankList> for Page<Bank> {
+    fn from(list: BankList) -> Self {
+        Page {
+            items: list.data,
+            total_count: Some(list.total_count),
+        }
+    }
+}
+
+/// 省份
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Province {
+    /// 省份名称
+    pub province_name: String,
+    /// 省份编码
+    pub province_code: i32,
+}
+
+/// 城市
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct City {
+    /// 城市名称
+    pub city_name: String,
+    /// 城市编码，进件时填写此值
+    pub city_code: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock_client;
+    use futures::TryStreamExt;
+    use reqwest::{Method, StatusCode};
+
+    #[tokio::test]
+    async fn test_bank_component() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+
+        let bank = r#"{"bank_alias":"招商银行","bank_alias_code":"1000009561","account_bank":"招商银行","account_bank_code":1001,"need_bank_branch":true}"#;
+        mock.on(
+            Method::GET,
+            "/v3/capital/capitallhh/banks/search-banks-by-bank-account",
+            StatusCode::OK,
+            &format!(r#"{{"total_count":1,"data":[{}]}}"#, bank),
+        );
+        let banks = client
+            .search_banks_by_account_number("6214830000000000")
+            .await?;
+        assert_eq!(banks[0].account_bank_code, 1001);
+        let path = &mock.requests()[0].path;
+        assert!(path.contains("account_number="));
+        assert!(!path.contains("6214830000000000"));
+
+        mock.on(
+            Method::GET,
+            "/v3/capital/capitallhh/banks/personal-banking",
+            StatusCode::OK,
+            &format!(r#"{{"total_count":2,"data":[{},{}]}}"#, bank, bank),
+        );
+        let banks: Vec<Bank> = client
+            .query_personal_banks_paginated(20)
+            .into_stream()
+            .try_collect()
+            .await?;
+        assert_eq!(banks.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+//! 微信支付命令行工具，用于运维排障。
+//! 需启用 `cli` feature：`cargo run --features cli --bin wechatpay-cli -- --config wechatpay.toml <子命令>`
+//!
+//! 配置文件为 toml 格式：
+//! ```toml
+//! mch_id = "1900000001"
+//! mch_cert_path = "apiclient_cert.pem"
+//! mch_key_path = "apiclient_key.pem"
+//! mch_api_v3_key = "..."
+//! # 以下可选
+//! # 使用微信支付公钥验签；不设置时则下载平台证书验签
+//! wechatpay_public_key_id = "PUB_KEY_ID_..."
+//! wechatpay_public_key_path = "pub_key.pem"
+//! app_id = "wx..."
+//! notify_url = "https://example.com/notify"
+//! base_url = "https://api2.mch.weixin.qq.com/v3"
+//! ```
+
+use clap::{Args, Parser, Subcommand};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::RsaPublicKey;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::PathBuf;
+use tokio::io::AsyncWrite;
+use wechatpay::platform_certificate::PersistedPlatformCertificate;
+use wechatpay::refund::RefundParams;
+use wechatpay::{MchCredential, WechatPayClient, WechatPayPublicKey};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Debug, Parser)]
+#[command(name = "wechatpay-cli", about = "微信支付命令行工具")]
+struct Cli {
+    /// 配置文件路径
+    #[arg(short, long, default_value = "wechatpay.toml")]
+    config: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 下载平台证书
+    Certificates,
+    /// Native 下单，输出商户订单号及二维码 url
+    NativePay {
+        /// 金额，单位为分
+        #[arg(long)]
+        amount: i64,
+        /// 商品描述
+        #[arg(long)]
+        description: String,
+    },
+    /// JSAPI 下单，输出商户订单号、prepay_id 及调起支付所需的签名
+    JsapiPay {
+        /// 用户的 openid
+        #[arg(long)]
+        openid: String,
+        /// 金额，单位为分
+        #[arg(long)]
+        amount: i64,
+        /// 商品描述
+        #[arg(long)]
+        description: String,
+    },
+    /// 查询订单
+    QueryTrade(TradeIdArgs),
+    /// 关闭订单
+    CloseTrade {
+        /// 商户订单号
+        #[arg(long)]
+        out_trade_no: String,
+    },
+    /// 申请退款
+    Refund {
+        #[command(flatten)]
+        trade_id: TradeIdArgs,
+        /// 商户退款单号
+        #[arg(long)]
+        out_refund_no: String,
+        /// 原订单金额，单位为分
+        #[arg(long)]
+        total: i64,
+        /// 退款金额，单位为分
+        #[arg(long)]
+        refund: i64,
+        /// 退款原因
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// 查询退款
+    QueryRefund {
+        /// 商户退款单号
+        #[arg(long)]
+        out_refund_no: String,
+    },
+    /// 下载交易账单
+    TradeBill {
+        /// 账单日期，格式为 yyyy-MM-DD
+        #[arg(long)]
+        date: String,
+        /// 账单类型：ALL、SUCCESS、REFUND
+        #[arg(long = "type")]
+        bill_type: Option<String>,
+        /// 输出文件，默认输出到标准输出
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 下载资金账单
+    FundFlowBill {
+        /// 账单日期，格式为 yyyy-MM-DD
+        #[arg(long)]
+        date: String,
+        /// 资金账户类型：BASIC、OPERATION、FEES
+        #[arg(long)]
+        account_type: Option<String>,
+        /// 输出文件，默认输出到标准输出
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+struct TradeIdArgs {
+    /// 微信支付订单号
+    #[arg(long)]
+    transaction_id: Option<String>,
+    /// 商户订单号
+    #[arg(long)]
+    out_trade_no: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    mch_id: String,
+    mch_cert_path: PathBuf,
+    mch_key_path: PathBuf,
+    mch_api_v3_key: String,
+    wechatpay_public_key_id: Option<String>,
+    wechatpay_public_key_path: Option<PathBuf>,
+    app_id: Option<String>,
+    notify_url: Option<String>,
+    base_url: Option<String>,
+}
+
+impl Config {
+    fn load(path: &PathBuf) -> Result<Config> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config {}: {}", path.display(), e))?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    async fn build_client(&self) -> Result<WechatPayClient> {
+        let credential = MchCredential::from_pem_files(
+            self.mch_id.clone(),
+            &self.mch_cert_path,
+            &self.mch_key_path,
+            self.mch_api_v3_key.clone(),
+        )?;
+
+        let mut builder = WechatPayClient::builder();
+        builder.mch_credential(credential);
+        match (
+            &self.wechatpay_public_key_id,
+            &self.wechatpay_public_key_path,
+        ) {
+            (Some(id), Some(path)) => {
+                let pem = std::fs::read_to_string(path)?;
+                let public_key = RsaPublicKey::from_public_key_pem(&pem)?;
+                builder.wechatpay_public_key(WechatPayPublicKey::new(id.clone(), public_key));
+            }
+            (None, None) => {
+                builder.fetch_platform_certificates();
+            }
+            _ => {
+                return Err(
+                    "wechatpay_public_key_id and wechatpay_public_key_path must be set together"
+                        .into(),
+                )
+            }
+        }
+        if let Some(app_id) = &self.app_id {
+            builder.default_app_id(app_id.clone());
+        }
+        if let Some(notify_url) = &self.notify_url {
+            builder.default_notify_url(notify_url.clone());
+        }
+        if let Some(base_url) = &self.base_url {
+            builder.base_url(base_url);
+        }
+        Ok(builder.build().await?)
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+async fn output_writer(output: &Option<PathBuf>) -> Result<Box<dyn AsyncWrite + Unpin>> {
+    Ok(match output {
+        Some(path) => Box::new(tokio::fs::File::create(path).await?),
+        None => Box::new(tokio::io::stdout()),
+    })
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let config = Config::load(&cli.config)?;
+    let client = config.build_client().await?;
+
+    match cli.command {
+        Command::Certificates => {
+            let certificates = client
+                .get_platform_certificates()
+                .await?
+                .iter()
+                .map(PersistedPlatformCertificate::try_from)
+                .collect::<wechatpay::error::Result<Vec<_>>>()?;
+            print_json(&certificates)?;
+        }
+        Command::NativePay {
+            amount,
+            description,
+        } => {
+            print_json(&client.native_pay(amount, &description).await?)?;
+        }
+        Command::JsapiPay {
+            openid,
+            amount,
+            description,
+        } => {
+            print_json(&client.jsapi_pay(&openid, amount, &description).await?)?;
+        }
+        Command::QueryTrade(TradeIdArgs {
+            transaction_id,
+            out_trade_no,
+        }) => {
+            let res = match (transaction_id, out_trade_no) {
+                (Some(transaction_id), _) => {
+                    client
+                        .query_trade_by_transaction_id(&transaction_id)
+                        .await?
+                }
+                (None, Some(out_trade_no)) => {
+                    client.query_trade_by_out_trade_no(&out_trade_no).await?
+                }
+                (None, None) => unreachable!("clap requires one of the trade ids"),
+            };
+            print_json(&res)?;
+        }
+        Command::CloseTrade { out_trade_no } => {
+            client.close_trade(&out_trade_no).await?;
+        }
+        Command::Refund {
+            trade_id,
+            out_refund_no,
+            total,
+            refund,
+            reason,
+        } => {
+            let mut builder = RefundParams::builder();
+            builder
+                .out_refund_no(out_refund_no.parse::<wechatpay::ids::OutRefundNo>()?)
+                .amount(total, refund);
+            if let Some(transaction_id) = trade_id.transaction_id {
+                builder.transaction_id(transaction_id);
+            }
+            if let Some(out_trade_no) = trade_id.out_trade_no {
+                builder.out_trade_no(out_trade_no.parse::<wechatpay::ids::OutTradeNo>()?);
+            }
+            if let Some(reason) = reason {
+                builder.reason(reason);
+            }
+            print_json(&client.apply_refund(&builder.build()?).await?)?;
+        }
+        Command::QueryRefund { out_refund_no } => {
+            print_json(&client.query_refund(&out_refund_no).await?)?;
+        }
+        Command::TradeBill {
+            date,
+            bill_type,
+            output,
+        } => {
+            let bill = client.get_trade_bill(&date, bill_type.as_deref()).await?;
+            let mut writer = output_writer(&output).await?;
+            client.download_bill(&bill, &mut writer).await?;
+        }
+        Command::FundFlowBill {
+            date,
+            account_type,
+            output,
+        } => {
+            let bill = client
+                .get_fund_flow_bill(&date, account_type.as_deref())
+                .await?;
+            let mut writer = output_writer(&output).await?;
+            client.download_bill(&bill, &mut writer).await?;
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run(Cli::parse()).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
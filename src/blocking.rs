@@ -0,0 +1,171 @@
+//! 阻塞式客户端，启用 `blocking` feature 时可用。
+//! 内部持有一个 tokio runtime，在其上执行 `WechatPayClient` 的异步方法，适合在非 async 的脚本和桌面程序中使用。
+//! 请勿在 async 上下文中使用：此时各接口方法返回错误，`block_on` 则会 panic。
+
+use crate::client::{WechatPayClient, WechatPayClientBuilder};
+use crate::error::{Result, WechatPayError};
+use crate::refund::{RefundParams, RefundQueryResponse};
+use crate::trade::{
+    AppCreateTradeParams, H5CreateTradeParams, JsApiCreateTradeParams, JsApiTradeSignature,
+    NativeCreateTradeParams, TradeQueryResponse,
+};
+use std::future::Future;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+/// 阻塞式微信支付客户端。
+/// 仅封装了下单、查询、退款等主要接口，其他接口可通过 `block_on` 调用 `inner()` 的异步方法。
+#[derive(Debug)]
+pub struct WechatPayBlockingClient {
+    inner: WechatPayClient,
+    runtime: Runtime,
+}
+
+impl WechatPayBlockingClient {
+    /// 使用 builder 构建客户端。
+    /// 后台任务(如平台证书自动刷新)运行在内部 runtime 的工作线程上。
+    /// 在 async 上下文中调用时返回错误。
+    pub fn new(builder: &mut WechatPayClientBuilder) -> Result<WechatPayBlockingClient> {
+        check_not_in_runtime()?;
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("wechatpay-blocking")
+            .enable_all()
+            .build()
+            .map_err(WechatPayError::Io)?;
+        let inner = runtime.block_on(builder.build())?;
+        Ok(WechatPayBlockingClient { inner, runtime })
+    }
+
+    /// 内部的异步客户端
+    pub fn inner(&self) -> &WechatPayClient {
+        &self.inner
+    }
+
+    /// 在内部 runtime 上执行 future，直到完成。
+    /// 如 `client.block_on(client.inner().query_complaints(&params))`。
+    /// 在 async 上下文中调用会 panic。
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// 同 `block_on`，但在 async 上下文中调用时返回错误，而非 panic。
+    fn run<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        check_not_in_runtime()?;
+        self.runtime.block_on(future)
+    }
+
+    /// JSAPI 下单，返回 prepay_id。参见 `WechatPayClient::jsapi_create_trade`。
+    pub fn jsapi_create_trade(&self, params: &JsApiCreateTradeParams) -> Result<String> {
+        self.run(self.inner.jsapi_create_trade(params))
+    }
+
+    /// APP 下单，返回 prepay_id。参见 `WechatPayClient::app_create_trade`。
+    pub fn app_create_trade(&self, params: &AppCreateTradeParams) -> Result<String> {
+        self.run(self.inner.app_create_trade(params))
+    }
+
+    /// H5 下单，返回 h5_url。参见 `WechatPayClient::h5_create_trade`。
+    pub fn h5_create_trade(&self, params: &H5CreateTradeParams) -> Result<String> {
+        self.run(self.inner.h5_create_trade(params))
+    }
+
+    /// Native 下单，返回 code_url。参见 `WechatPayClient::native_create_trade`。
+    pub fn native_create_trade(&self, params: &NativeCreateTradeParams) -> Result<String> {
+        self.run(self.inner.native_create_trade(params))
+    }
+
+    /// 通过微信支付订单号查询订单。参见 `WechatPayClient::query_trade_by_transaction_id`。
+    pub fn query_trade_by_transaction_id(
+        &self,
+        transaction_id: &str,
+    ) -> Result<TradeQueryResponse> {
+        self.run(self.inner.query_trade_by_transaction_id(transaction_id))
+    }
+
+    /// 通过商户订单号查询订单。参见 `WechatPayClient::query_trade_by_out_trade_no`。
+    pub fn query_trade_by_out_trade_no(&self, out_trade_no: &str) -> Result<TradeQueryResponse> {
+        self.run(self.inner.query_trade_by_out_trade_no(out_trade_no))
+    }
+
+    /// 关闭订单。参见 `WechatPayClient::close_trade`。
+    pub fn close_trade(&self, out_trade_no: &str) -> Result<()> {
+        self.run(self.inner.close_trade(out_trade_no))
+    }
+
+    /// JSAPI 调起支付的签名。参见 `WechatPayClient::sign_jsapi_trade`。
+    pub fn sign_jsapi_trade(&self, prepay_id: &str, app_id: &str) -> Result<JsApiTradeSignature> {
+        self.run(self.inner.sign_jsapi_trade(prepay_id, app_id))
+    }
+
+    /// 申请退款。参见 `WechatPayClient::apply_refund`。
+    pub fn apply_refund(&self, params: &RefundParams) -> Result<RefundQueryResponse> {
+        self.run(self.inner.apply_refund(params))
+    }
+
+    /// 查询退款。参见 `WechatPayClient::query_refund`。
+    pub fn query_refund(&self, out_refund_no: &str) -> Result<RefundQueryResponse> {
+        self.run(self.inner.query_refund(out_refund_no))
+    }
+}
+
+/// 在 async 上下文(已有 tokio runtime)中无法阻塞等待，返回错误。
+fn check_not_in_runtime() -> Result<()> {
+    if Handle::try_current().is_ok() {
+        return Err(WechatPayError::Other(
+            "WechatPayBlockingClient cannot be used within an async runtime".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+    use crate::MchCredential;
+    use reqwest::{Method, StatusCode};
+    use rsa::RsaPrivateKey;
+
+    fn builder(mock: &MockTransport, private_key: RsaPrivateKey) -> WechatPayClientBuilder {
+        let mut builder = WechatPayClient::builder();
+        builder
+            .mch_credential(MchCredential::new(
+                "1900000001".to_string(),
+                "serial".to_string(),
+                private_key,
+                "0".repeat(32),
+            ))
+            .wechatpay_public_key(mock.wechatpay_public_key())
+            .transport(mock.clone());
+        builder
+    }
+
+    #[test]
+    fn test_blocking_client() -> anyhow::Result<()> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let mock = MockTransport::new(private_key.clone(), "PUB_KEY_ID_0000000000000001");
+        mock.on(
+            Method::POST,
+            "/v3/pay/transactions/out-trade-no/1217752501201407033233368018/close",
+            StatusCode::NO_CONTENT,
+            "",
+        );
+
+        let client = WechatPayBlockingClient::new(&mut builder(&mock, private_key.clone()))?;
+        client.close_trade("1217752501201407033233368018")?;
+        assert_eq!(mock.requests().len(), 1);
+        let signature = client
+            .sign_jsapi_trade("wx201410272009395522657a690389285100", "wxd678efh567hg6787")?;
+        assert_eq!(signature.app_id, "wxd678efh567hg6787");
+
+        // 在 async 上下文中调用返回错误，而非 panic
+        let runtime = Builder::new_current_thread().build()?;
+        let res = runtime.block_on(async { client.close_trade("1217752501201407033233368018") });
+        assert!(matches!(res, Err(WechatPayError::Other(_))));
+        let res = runtime
+            .block_on(async { WechatPayBlockingClient::new(&mut builder(&mock, private_key)) });
+        assert!(matches!(res, Err(WechatPayError::Other(_))));
+        assert_eq!(mock.requests().len(), 1);
+        Ok(())
+    }
+}
@@ -1,23 +1,97 @@
-use crate::credential::MchCredential;
+use crate::audit::{CallRecorder, PendingCall};
+use crate::clock::{Clock, OffsetClock, SystemClock};
+use crate::credential::{request_body, MchCredential, NonceProvider, RandomNonce, RequestSigner};
+use crate::dedup::NotificationDedupStore;
 use crate::error::WechatPayApiError;
+use crate::error::{Result, WechatPayError};
+use crate::failover::{Failover, FailoverOptions};
+use crate::interceptor::Interceptor;
+use crate::metrics::{MetricsHook, RequestMetrics};
+use crate::platform_certificate::{self, WechatPayPublicKey, WECHATPAY_PUBLIC_KEY_ID_PREFIX};
+#[cfg(feature = "x509")]
 use crate::platform_certificate::{
-    get_platform_certificates, PlatformCertificate, PlatformCertificateState,
+    get_platform_certificates_with_transport, PlatformCertificate, PlatformCertificateState,
 };
-use anyhow::Result;
-use reqwest::{Client, Request, Response};
-use std::sync::{Arc, Mutex};
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::transport::{BufferedResponse, Transport};
+use arc_swap::ArcSwap;
+use bytes::Bytes;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, ClientBuilder, Method, Request, Response, StatusCode};
+use rsa::RsaPublicKey;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+#[cfg(feature = "x509")]
+use {futures::future::BoxFuture, rand::Rng, tokio::task::JoinHandle};
 
 #[derive(Debug, Clone)]
 pub struct WechatPayClient {
     pub(crate) client: Client,
-    pub(crate) mch_credential: MchCredential,
-    pub(crate) platform_certificate_state: Arc<Mutex<PlatformCertificateState>>,
+    /// 商户凭证。可通过 `swap_credential` 在运行时替换。
+    pub(crate) mch_credential: Arc<ArcSwap<MchCredential>>,
+    /// 外部签名器。未指定时使用商户凭证中的私钥签名。
+    pub(crate) request_signer: Option<Arc<dyn RequestSigner>>,
+    /// 平台证书。验签时无锁读取，拉取到新证书后整体原子替换。
+    #[cfg(feature = "x509")]
+    pub(crate) platform_certificate_state: Arc<ArcSwap<PlatformCertificateState>>,
+    /// 上次因未知 serial_no 拉取平台证书的时间，尚未拉取过时为 None。
+    /// 同时作为拉取时的锁，避免并发重复拉取。
+    #[cfg(feature = "x509")]
+    pub(crate) last_unknown_serial_fetch: Arc<tokio::sync::Mutex<Option<Instant>>>,
+    /// 微信支付公钥
+    pub(crate) wechatpay_public_key: Option<WechatPayPublicKey>,
+    /// 重试策略
+    pub(crate) retry_policy: Option<RetryPolicy>,
+    /// 读超时
+    pub(crate) read_timeout: Option<Duration>,
+    /// 拦截器
+    pub(crate) interceptors: Vec<Arc<dyn Interceptor>>,
+    /// 调用审计记录器
+    pub(crate) call_recorder: Option<Arc<dyn CallRecorder>>,
+    /// 指标回调
+    pub(crate) metrics_hook: Option<Arc<dyn MetricsHook>>,
+    /// 通知去重存储
+    pub(crate) notification_dedup_store: Option<Arc<dyn NotificationDedupStore>>,
+    /// 限流器
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    /// 响应体大小的上限(字节)
+    pub(crate) max_response_body_size: Option<usize>,
+    /// 并发请求数的上限
+    pub(crate) concurrency_limiter: Option<Arc<tokio::sync::Semaphore>>,
+    /// 接口的基础 URL
+    pub(crate) base_url: String,
+    /// 传输层
+    pub(crate) transport: Arc<dyn Transport>,
+    /// 双域名容灾
+    pub(crate) failover: Option<Arc<Failover>>,
+    /// 默认的应用 ID，用于简化下单等接口
+    pub(crate) default_app_id: Option<String>,
+    /// 默认的支付结果通知地址，用于简化下单等接口
+    pub(crate) default_notify_url: Option<String>,
+    /// 通知的 Wechatpay-Timestamp 与本地时间之差的上限，用于防止重放攻击
+    pub(crate) notification_max_age: Duration,
+    /// 签名及验签时间戳所用的时钟
+    pub(crate) clock: Arc<dyn Clock>,
+    /// 签名所用随机串的来源
+    pub(crate) nonce_provider: Arc<dyn NonceProvider>,
 }
 
+/// 默认的基础 URL
 pub(crate) const BASE_URL: &str = "https://api.mch.weixin.qq.com/v3";
 
 pub(crate) const USER_AGENT: &str = "wechatpay Rust client";
 
+/// 遇到未知 serial_no 时自动拉取平台证书的最小间隔。
+/// 距上次拉取不足此间隔时不再拉取，避免收到伪造的 serial_no 时频繁请求证书接口。
+#[cfg(feature = "x509")]
+const UNKNOWN_SERIAL_FETCH_DEBOUNCE: Duration = Duration::from_secs(60);
+
+/// 通知的 Wechatpay-Timestamp 与本地时间之差的默认上限
+const DEFAULT_NOTIFICATION_MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
 impl WechatPayClient {
     pub fn builder() -> WechatPayClientBuilder {
         WechatPayClientBuilder::new()
@@ -25,60 +99,694 @@ impl WechatPayClient {
 
     /// 执行 HTTP 请求
     /// 请求发送时，先进行签名；收到响应时，先进行验签，通过后再返回。
+    /// 如配置了重试策略，GET 请求遇到网络错误或 5xx 响应时会自动重试。
     /// (本 crate 未实现的接口，可以通过此方法访问)
     pub async fn execute(&self, req: Request) -> Result<Response> {
+        let idempotent = matches!(*req.method(), Method::GET | Method::HEAD);
+        self.execute_with_retry(req, idempotent).await
+    }
+
+    /// 执行幂等的 HTTP 请求，其他同 `execute`。
+    /// 请求带有幂等号(如 out_trade_no、out_refund_no)，重复发送不会产生副作用时使用。
+    /// 如配置了重试策略，遇到网络错误或 5xx 响应时会自动重试。
+    pub async fn execute_idempotent(&self, req: Request) -> Result<Response> {
+        self.execute_with_retry(req, true).await
+    }
+
+    /// 执行 HTTP 请求，并将 JSON 响应解析为 T，其他同 `execute`。
+    /// T 中的 `Encrypted` 字段会被自动使用商户私钥解密。
+    pub async fn execute_json<T: DeserializeOwned>(&self, req: Request) -> Result<T> {
+        let res = self.execute(req).await?;
+        self.json_response(res).await
+    }
+
+    /// 执行 HTTP 请求，返回状态码、响应头及原始响应 body，其他同 `execute`。
+    /// 适用于非 JSON 响应，如账单、电子回单等。
+    pub async fn execute_raw(&self, req: Request) -> Result<(StatusCode, HeaderMap, Bytes)> {
+        let res = self.execute(req).await?;
+        let res = BufferedResponse::read(res, None).await?;
+        Ok((res.status, res.headers, res.body))
+    }
+
+    async fn execute_with_retry(&self, req: Request, idempotent: bool) -> Result<Response> {
+        let method = req.method().clone();
+        let path = req.url().path().to_string();
+        self.observe(
+            &method,
+            &path,
+            self.execute_with_retry_inner(req, idempotent),
+        )
+        .await
+    }
+
+    /// 执行一次 API 调用，并记录 tracing span(启用 `tracing` feature 时)及指标。
+    async fn observe<F>(&self, method: &Method, path: &str, fut: F) -> Result<Response>
+    where
+        F: Future<Output = Result<Response>>,
+    {
+        #[cfg(feature = "tracing")]
+        let fut = crate::trace::instrument(
+            crate::trace::request_span(&self.mch_credential().mch_id, method, path),
+            fut,
+        );
+        let start = Instant::now();
+        let res = fut.await;
+        if let Some(metrics_hook) = &self.metrics_hook {
+            metrics_hook.on_request(&RequestMetrics::new(method, path, start.elapsed(), &res));
+        }
+        res
+    }
+
+    async fn execute_with_retry_inner(&self, req: Request, idempotent: bool) -> Result<Response> {
         let mut req = req;
         // 根据 https://pay.weixin.qq.com/wiki/doc/apiv3/wechatpay/wechatpay2_0.shtml#part-1
         // 给所有请求都加上 accept header。
         req.headers_mut()
             .append("Accept", "application/json".parse().unwrap());
 
-        let req = self.mch_credential.sign_request(req)?;
-        let res = self.client.execute(req).await?;
+        let policy = match &self.retry_policy {
+            Some(policy) if idempotent => policy,
+            _ => {
+                let req = self.intercept(req)?;
+                let req = self.sign_request(req).await?;
+                return self.send_signed(req).await;
+            }
+        };
+
+        let mut retries = 0;
+        loop {
+            // 每次发送都需重新签名，因此保留一份未签名的请求。
+            let next = if retries < policy.max_retries {
+                req.try_clone()
+            } else {
+                None
+            };
+            let intercepted = self.intercept(req)?;
+            let signed = self.sign_request(intercepted).await?;
+            match (self.send_signed(signed).await, next) {
+                (Err(e), Some(next)) if should_retry(&e) => {
+                    let backoff = policy.backoff(retries);
+                    retries += 1;
+                    log::warn!(
+                        "request failed, retry {} after {:?}: {}",
+                        retries,
+                        backoff,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    req = next;
+                }
+                (res, _) => return res,
+            }
+        }
+    }
+
+    /// 执行 HTTP 请求，参与签名的 body 由 signed_body 指定，其他同 `execute`。
+    /// 用于文件上传等 body 为 multipart/form-data 的接口，此时 signed_body 为 meta 部分的 JSON。
+    /// (本 crate 未实现的文件上传类接口，可以通过此方法访问)
+    pub async fn execute_with_signed_body(
+        &self,
+        req: Request,
+        signed_body: Option<&[u8]>,
+    ) -> Result<Response> {
+        let method = req.method().clone();
+        let path = req.url().path().to_string();
+        self.observe(
+            &method,
+            &path,
+            self.execute_with_signed_body_inner(req, signed_body),
+        )
+        .await
+    }
+
+    async fn execute_with_signed_body_inner(
+        &self,
+        req: Request,
+        signed_body: Option<&[u8]>,
+    ) -> Result<Response> {
+        let mut req = req;
+        req.headers_mut()
+            .append("Accept", "application/json".parse().unwrap());
+
+        let req = self.intercept(req)?;
+        let req = self.sign_request_with_body(req, signed_body).await?;
+        self.send_signed(req).await
+    }
+
+    /// 对请求签名，签名时间戳及随机串取自配置的时钟与随机串来源。
+    pub(crate) async fn sign_request(&self, req: Request) -> Result<Request> {
+        let body = request_body(&req)?;
+        self.sign_request_with_body(req, body.as_deref()).await
+    }
+
+    /// 对请求签名，参与签名的 body 由 signed_body 指定。
+    /// 配置了 `request_signer` 时由其签名，否则使用商户私钥签名。
+    async fn sign_request_with_body(
+        &self,
+        req: Request,
+        signed_body: Option<&[u8]>,
+    ) -> Result<Request> {
+        let mch_credential = self.mch_credential.load_full();
+        let local_signer = mch_credential.signer();
+        let signer: &dyn RequestSigner = match &self.request_signer {
+            Some(signer) => signer.as_ref(),
+            None => local_signer.as_ref(),
+        };
+        mch_credential
+            .sign_request_with_signer(
+                req,
+                signed_body,
+                signer,
+                self.clock.as_ref(),
+                self.nonce_provider.as_ref(),
+            )
+            .await
+    }
+
+    /// 签名前依次调用拦截器的 before_send。
+    fn intercept(&self, req: Request) -> Result<Request> {
+        let mut req = req;
+        for interceptor in &self.interceptors {
+            interceptor.before_send(&mut req)?;
+        }
+        Ok(req)
+    }
+
+    /// 执行文件下载请求，返回 body 未读取的 Response。
+    /// 同 `execute` 经过拦截器、审计、tracing 及指标，但下载接口的响应不带签名，因此不做验签；
+    /// 也不重试，审计中不记录响应 body。
+    pub(crate) async fn execute_download(&self, req: Request) -> Result<Response> {
+        let method = req.method().clone();
+        let path = req.url().path().to_string();
+        self.observe(&method, &path, async {
+            let req = self.intercept(req)?;
+            let req = self.sign_request(req).await?;
+            self.send_recorded(req, true).await
+        })
+        .await
+    }
+
+    /// 发送已签名的请求，并对响应进行验签。
+    async fn send_signed(&self, req: Request) -> Result<Response> {
+        self.send_recorded(req, false).await
+    }
+
+    /// 发送已签名的请求。download 为 true 时不读取响应 body，也不验签。
+    /// 收到响应(并验签)后，先记录审计日志，再依次调用拦截器的 after_receive。
+    async fn send_recorded(&self, req: Request, download: bool) -> Result<Response> {
+        let send = |req| async move {
+            if download {
+                self.send_download(req).await
+            } else {
+                self.send_and_verify(req).await
+            }
+        };
+        if self.interceptors.is_empty() && self.call_recorder.is_none() {
+            return send(req).await;
+        }
+
+        let method = req.method().clone();
+        let url = req.url().clone();
+        let res = match &self.call_recorder {
+            Some(recorder) => {
+                let call = PendingCall::new(&req);
+                let start = Instant::now();
+                let res = send(req).await;
+                call.finish(recorder.as_ref(), res, start.elapsed(), !download)
+                    .await
+            }
+            None => send(req).await,
+        };
+        for interceptor in &self.interceptors {
+            interceptor.after_receive(&method, &url, &res);
+        }
+        res
+    }
+
+    /// 发送请求，读取响应体并验签。
+    /// 响应体只读取一次，验签直接在读到的 bytes 上进行，返回的 Response 复用同一份 body。
+    async fn send_and_verify(&self, req: Request) -> Result<Response> {
+        let res = self.send(req).await?;
+        let res = BufferedResponse::read(res, self.max_response_body_size).await?;
 
         // 请求出错时，响应中可能不存在验签相关的字段。因此直接返回 error。
+        if !res.status.is_success() {
+            return Err(WechatPayApiError::from_parts(
+                res.status,
+                res.headers,
+                &res.body,
+            ));
+        }
+        self.verify_response_parts(&res.headers, &res.body).await?;
+        Ok(res.into())
+    }
+
+    /// 发送文件下载请求，不读取响应 body。响应的状态码不为 2xx 时返回错误。
+    async fn send_download(&self, req: Request) -> Result<Response> {
+        let res = self.send(req).await?;
         if !res.status().is_success() {
-            let e: WechatPayApiError = res.json().await?;
-            Err(e.into())
-        } else {
-            let res = self.verify_response(res).await?;
-            Ok(res)
+            return Err(WechatPayApiError::from_response(res).await);
+        }
+        Ok(res)
+    }
+
+    /// 发送请求，不做签名与验签。如配置了读超时，等待响应超时将返回 `WechatPayError::Timeout`。
+    /// 如配置了限流，发送前先获取令牌。
+    /// 如配置了双域名容灾，按容灾状态选择域名；连接失败时请求未发出，立即改用下一个域名重发。
+    pub(crate) async fn send(&self, req: Request) -> Result<Response> {
+        let failover = match &self.failover {
+            Some(failover) => failover,
+            None => return self.send_once(req).await,
+        };
+
+        let index = failover.select();
+        let mut req = req;
+        failover.rewrite(&mut req, 0, index)?;
+        let backup = req.try_clone();
+        let res = self.send_once(req).await;
+        failover.report(index, !is_failover_error(&res));
+
+        match (res, backup) {
+            (Err(WechatPayError::Http(e)), Some(mut backup)) if e.is_connect() => {
+                let next = failover.next(index);
+                log::warn!("failed to connect, resend to backup domain: {}", e);
+                failover.rewrite(&mut backup, index, next)?;
+                let res = self.send_once(backup).await;
+                failover.report(next, !is_failover_error(&res));
+                res
+            }
+            (res, _) => res,
+        }
+    }
+
+    async fn send_once(&self, req: Request) -> Result<Response> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire(req.url().path()).await?;
+        }
+        // 许可在收到响应 header 后释放，读取响应 body 不受并发上限约束
+        let _permit = match &self.concurrency_limiter {
+            Some(semaphore) => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .map_err(|e| WechatPayError::Other(e.to_string()))?,
+            ),
+            None => None,
+        };
+        #[cfg(feature = "tracing")]
+        crate::trace::log_request(&req);
+        match self.read_timeout {
+            Some(read_timeout) => tokio::time::timeout(read_timeout, self.transport.send(req))
+                .await
+                .map_err(|_| {
+                    WechatPayError::Timeout(format!("no response after {:?}", read_timeout))
+                })?,
+            None => self.transport.send(req).await,
+        }
+    }
+
+    /// 对响应(或通知)的 header 及原始 body 进行验签。
+    /// 根据 Wechatpay-Serial header 选择微信支付公钥或对应的平台证书，
+    /// 使用其他 HTTP 客户端(如 hyper、isahc)调用微信支付 API 时，可以此复用验签逻辑。
+    pub async fn verify_response_parts(&self, headers: &HeaderMap, body: &[u8]) -> Result<()> {
+        let res = match self.verifying_public_key(headers).await {
+            Ok(public_key) => {
+                platform_certificate::verify_response_parts(&public_key, headers, body)
+            }
+            Err(e) => Err(e),
+        };
+        if let (Err(WechatPayError::Verify(_)), Some(metrics_hook)) = (&res, &self.metrics_hook) {
+            metrics_hook.on_verify_failure();
         }
+        res
     }
 
-    /// 对响应进行数字签名验证。
-    pub(crate) async fn verify_response(&self, res: Response) -> Result<Response> {
-        let serial_no = res
-            .headers()
+    /// 根据 Wechatpay-Serial header 取得验签所用的公钥。
+    async fn verifying_public_key(&self, headers: &HeaderMap) -> Result<RsaPublicKey> {
+        let serial_no = headers
             .get("Wechatpay-Serial")
-            .ok_or_else(|| anyhow::format_err!("missing `Wechatpay-Serial` header"))?
-            .to_str()?
+            .ok_or_else(|| WechatPayError::Verify("missing `Wechatpay-Serial` header".to_string()))?
+            .to_str()
+            .map_err(|e| WechatPayError::Verify(e.to_string()))?
             .to_string();
 
+        if serial_no.starts_with(WECHATPAY_PUBLIC_KEY_ID_PREFIX) {
+            let public_key = self
+                .wechatpay_public_key
+                .as_ref()
+                .filter(|k| k.public_key_id == serial_no)
+                .ok_or_else(|| {
+                    WechatPayError::Verify(format!(
+                        "no wechatpay public key found for id: {}",
+                        serial_no
+                    ))
+                })?;
+            return Ok(public_key.public_key.clone());
+        }
+        self.platform_certificate_public_key(&serial_no).await
+    }
+
+    /// 根据 serial_no 取得平台证书的公钥。
+    #[cfg(feature = "x509")]
+    async fn platform_certificate_public_key(&self, serial_no: &str) -> Result<RsaPublicKey> {
         let certificate = self
             .platform_certificate_state
-            .lock()
-            .unwrap()
-            .get_platform_certificate(&serial_no)?;
-        let res = certificate.verify_response(res).await?;
-        Ok(res)
+            .load()
+            .get_platform_certificate(serial_no);
+        let certificate = match certificate {
+            Ok(certificate) => certificate,
+            Err(_) => {
+                // 本地没有对应的证书，可能是平台证书已轮换。拉取一次最新证书后重试。
+                self.fetch_platform_certificates_for_serial(serial_no)
+                    .await?;
+                self.platform_certificate_state
+                    .load()
+                    .get_platform_certificate(serial_no)?
+            }
+        };
+        certificate.public_key()
+    }
+
+    /// 未启用 `x509` feature 时不支持平台证书，仅能使用微信支付公钥验签。
+    #[cfg(not(feature = "x509"))]
+    async fn platform_certificate_public_key(&self, serial_no: &str) -> Result<RsaPublicKey> {
+        Err(WechatPayError::Verify(format!(
+            "platform certificate {} is not supported without the `x509` feature",
+            serial_no
+        )))
+    }
+
+    /// 本地缺少 serial_no 对应的平台证书时，拉取最新的平台证书列表。
+    /// 并发调用时只会拉取一次；距上次拉取不足 `UNKNOWN_SERIAL_FETCH_DEBOUNCE` 时不拉取。
+    #[cfg(feature = "x509")]
+    async fn fetch_platform_certificates_for_serial(&self, serial_no: &str) -> Result<()> {
+        let mut last_fetch = self.last_unknown_serial_fetch.lock().await;
+        if self
+            .platform_certificate_state
+            .load()
+            .get_platform_certificate(serial_no)
+            .is_ok()
+            || last_fetch.is_some_and(|t| t.elapsed() < UNKNOWN_SERIAL_FETCH_DEBOUNCE)
+        {
+            return Ok(());
+        }
+        *last_fetch = Some(Instant::now());
+        log::info!(
+            "unknown platform certificate serial_no: {}, fetching platform certificates",
+            serial_no
+        );
+        self.get_platform_certificates().await?;
+        Ok(())
+    }
+
+    /// 当前使用的商户凭证
+    pub fn mch_credential(&self) -> Arc<MchCredential> {
+        self.mch_credential.load_full()
+    }
+
+    /// 替换商户凭证，如 API v3 密钥轮换、更换商户 API 证书后。替换对 client 的所有克隆生效，
+    /// 已发出的请求不受影响。mch_id 须保持不变。
+    pub fn swap_credential(&self, mch_credential: MchCredential) -> Result<()> {
+        if mch_credential.mch_id != self.mch_credential.load().mch_id {
+            return Err(WechatPayError::InvalidParams(
+                "`mch_id` of the new credential does not match".to_string(),
+            ));
+        }
+        self.mch_credential.store(Arc::new(mch_credential));
+        Ok(())
     }
 
     /// 获取平台证书列表。
+    #[cfg(feature = "x509")]
     pub async fn get_platform_certificates(&self) -> Result<Vec<PlatformCertificate>> {
-        let platform_certificates = get_platform_certificates(&self.mch_credential).await?;
-        let mut state = self.platform_certificate_state.lock().unwrap();
-        *state = PlatformCertificateState::new(platform_certificates.clone())?;
+        let mch_credential = self.mch_credential.load_full();
+        let platform_certificates = get_platform_certificates_with_transport(
+            &self.client,
+            self.transport.as_ref(),
+            &self.base_url,
+            &mch_credential,
+            self.request_signer.as_deref(),
+            self.clock.as_ref(),
+        )
+        .await?;
+        let state = PlatformCertificateState::new(platform_certificates.clone())?;
+        self.platform_certificate_state.store(Arc::new(state));
         Ok(platform_certificates)
     }
+
+    /// 当前使用的平台证书列表。
+    /// 可通过 `platform_certificate::save_platform_certificates` 持久化，供进程重启时加载。
+    #[cfg(feature = "x509")]
+    pub fn platform_certificates(&self) -> Vec<PlatformCertificate> {
+        self.platform_certificate_state
+            .load()
+            .certificates()
+            .clone()
+    }
+
+    /// 启动后台任务，定期获取最新的平台证书列表。
+    /// 平台证书会定期轮换，新证书启用前会提前发布，定期刷新可避免证书轮换时验签失败。
+    /// 每次刷新的间隔为 `options.interval` 加上 [0, `options.jitter`) 范围内的随机值；
+    /// 刷新失败时，间隔 `options.retry_interval` 后重试，至多重试 `options.max_retries` 次。
+    /// 后台任务不会阻止 client 被释放，所有 client 都被释放后，后台任务自动退出。
+    #[cfg(feature = "x509")]
+    pub fn spawn_platform_certificate_refresher(
+        &self,
+        options: CertificateRefreshOptions,
+    ) -> JoinHandle<()> {
+        let client = self.client.clone();
+        let transport = self.transport.clone();
+        let base_url = self.base_url.clone();
+        let mch_credential = self.mch_credential.clone();
+        let request_signer = self.request_signer.clone();
+        let clock = self.clock.clone();
+        let state = Arc::downgrade(&self.platform_certificate_state);
+
+        tokio::spawn(async move {
+            loop {
+                let jitter = if options.jitter.is_zero() {
+                    Duration::ZERO
+                } else {
+                    rand::thread_rng().gen_range(Duration::ZERO..options.jitter)
+                };
+                tokio::time::sleep(options.interval + jitter).await;
+
+                let mut retries = 0;
+                let platform_certificates = loop {
+                    if state.strong_count() == 0 {
+                        return;
+                    }
+                    match get_platform_certificates_with_transport(
+                        &client,
+                        transport.as_ref(),
+                        &base_url,
+                        &mch_credential.load(),
+                        request_signer.as_deref(),
+                        clock.as_ref(),
+                    )
+                    .await
+                    {
+                        Ok(platform_certificates) => break Some(platform_certificates),
+                        Err(e) if retries < options.max_retries => {
+                            retries += 1;
+                            log::warn!(
+                                "failed to refresh platform certificates, retry {}: {}",
+                                retries,
+                                e
+                            );
+                            tokio::time::sleep(options.retry_interval).await;
+                        }
+                        Err(e) => {
+                            log::error!("failed to refresh platform certificates: {}", e);
+                            break None;
+                        }
+                    }
+                };
+
+                let state = match state.upgrade() {
+                    Some(state) => state,
+                    None => return,
+                };
+                if let Some(platform_certificates) = platform_certificates {
+                    match PlatformCertificateState::new(platform_certificates) {
+                        Ok(new_state) => state.store(Arc::new(new_state)),
+                        Err(e) => log::error!("failed to refresh platform certificates: {}", e),
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// 是否应当重试：网络错误(连接失败、超时)或 5xx 响应。
+fn should_retry(e: &WechatPayError) -> bool {
+    match e {
+        WechatPayError::Http(e) => e.is_connect() || e.is_timeout(),
+        WechatPayError::Timeout(_) => true,
+        WechatPayError::Api(e) => e.status().is_some_and(|s| s.is_server_error()),
+        _ => false,
+    }
+}
+
+/// 是否为域名故障：网络错误(连接失败、超时)或 5xx 响应。
+fn is_failover_error(res: &Result<Response>) -> bool {
+    match res {
+        Ok(res) => res.status().is_server_error(),
+        Err(WechatPayError::Http(e)) => e.is_connect() || e.is_timeout(),
+        Err(WechatPayError::Timeout(_)) => true,
+        Err(_) => false,
+    }
+}
+
+/// 请求重试策略。
+/// 仅对幂等请求(GET 请求，或通过 `execute_idempotent` 发送的请求)在网络错误或 5xx 响应时重试，
+/// 重试间隔按指数退避增长。
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最大重试次数
+    pub max_retries: u32,
+    /// 首次重试的间隔
+    pub initial_backoff: Duration,
+    /// 重试间隔的上限
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// 第 retries 次(从 0 开始)重试前的等待时间。
+    fn backoff(&self, retries: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retries));
+        backoff.min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+/// 轮询的配置。
+/// 每次查询之间的间隔从 `initial_interval` 开始按指数增长，至多为 `max_interval`；
+/// 总耗时超过 `timeout` 时，返回 `WechatPayError::Timeout`。
+#[derive(Debug, Clone)]
+pub struct PollOptions {
+    /// 首次轮询的间隔
+    pub initial_interval: Duration,
+    /// 轮询间隔的上限
+    pub max_interval: Duration,
+    /// 轮询的总时长上限
+    pub timeout: Duration,
+}
+
+impl PollOptions {
+    /// 第 attempt 次(从 0 开始)查询后的等待时间。
+    pub(crate) fn interval(&self, attempt: u32) -> Duration {
+        let interval = self
+            .initial_interval
+            .saturating_mul(2u32.saturating_pow(attempt));
+        interval.min(self.max_interval)
+    }
+}
+
+impl Default for PollOptions {
+    fn default() -> Self {
+        PollOptions {
+            initial_interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// 平台证书自动刷新的配置
+#[cfg(feature = "x509")]
+#[derive(Debug, Clone)]
+pub struct CertificateRefreshOptions {
+    /// 刷新间隔
+    pub interval: Duration,
+    /// 刷新间隔的随机抖动上限，避免多个实例同时刷新
+    pub jitter: Duration,
+    /// 刷新失败时的重试间隔
+    pub retry_interval: Duration,
+    /// 刷新失败时的最大重试次数
+    pub max_retries: u32,
+}
+
+#[cfg(feature = "x509")]
+impl Default for CertificateRefreshOptions {
+    fn default() -> Self {
+        CertificateRefreshOptions {
+            interval: Duration::from_secs(12 * 60 * 60),
+            jitter: Duration::from_secs(10 * 60),
+            retry_interval: Duration::from_secs(60),
+            max_retries: 5,
+        }
+    }
+}
+
+/// 定制 `reqwest::ClientBuilder` 的钩子
+struct ClientBuilderHook(Box<dyn FnOnce(ClientBuilder) -> ClientBuilder + Send>);
+
+impl fmt::Debug for ClientBuilderHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ClientBuilderHook")
+    }
+}
+
+/// build 时加载初始平台证书的闭包
+#[cfg(feature = "x509")]
+struct PlatformCertificateLoader(
+    Box<dyn FnOnce() -> BoxFuture<'static, Result<Vec<PlatformCertificate>>> + Send>,
+);
+
+#[cfg(feature = "x509")]
+impl fmt::Debug for PlatformCertificateLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PlatformCertificateLoader")
+    }
 }
 
 /// builder for `WechatPayClient`.
 #[derive(Debug, Default)]
 pub struct WechatPayClientBuilder {
     mch_credential: Option<MchCredential>,
+    #[cfg(feature = "x509")]
     platform_certificates: Option<Vec<PlatformCertificate>>,
+    #[cfg(feature = "x509")]
+    platform_certificate_loader: Option<PlatformCertificateLoader>,
+    #[cfg(feature = "x509")]
     fetch_platform_certificates: bool,
+    #[cfg(feature = "x509")]
+    platform_certificate_refresh: Option<CertificateRefreshOptions>,
+    wechatpay_public_key: Option<WechatPayPublicKey>,
+    retry_policy: Option<RetryPolicy>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    http_client: Option<Client>,
+    http_client_builder_hook: Option<ClientBuilderHook>,
+    request_signer: Option<Arc<dyn RequestSigner>>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    call_recorder: Option<Arc<dyn CallRecorder>>,
+    metrics_hook: Option<Arc<dyn MetricsHook>>,
+    notification_dedup_store: Option<Arc<dyn NotificationDedupStore>>,
+    rate_limits: Vec<(String, RateLimit)>,
+    max_concurrent_requests: Option<usize>,
+    max_response_body_size: Option<usize>,
+    base_url: Option<String>,
+    transport: Option<Arc<dyn Transport>>,
+    failover: Option<FailoverOptions>,
+    default_app_id: Option<String>,
+    default_notify_url: Option<String>,
+    notification_max_age: Option<Duration>,
+    clock: Option<Arc<dyn Clock>>,
+    nonce_provider: Option<Arc<dyn NonceProvider>>,
 
     user_agent: Option<String>,
 }
@@ -95,7 +803,15 @@ impl WechatPayClientBuilder {
         self
     }
 
+    /// 外部签名器，如接入云 KMS、HSM 的 `RequestSigner`。指定后，所有请求(包括获取平台证书)均由其签名，
+    /// 商户凭证中的私钥仅用于解密敏感信息。如果未指定，则使用商户凭证中的私钥签名。
+    pub fn request_signer<S: RequestSigner + 'static>(&mut self, signer: S) -> &mut Self {
+        self.request_signer = Some(Arc::new(signer));
+        self
+    }
+
     /// 平台证书列表。如果指定 fetch_platform_certificates 为 true，则此参数无效。
+    #[cfg(feature = "x509")]
     pub fn platform_certificates(
         &mut self,
         platform_certificates: Vec<PlatformCertificate>,
@@ -104,13 +820,203 @@ impl WechatPayClientBuilder {
         self
     }
 
+    /// build 时调用 loader 加载初始的平台证书列表，如从本地缓存、数据库或配置中心读取，
+    /// 避免每次启动都调用证书接口。指定后 platform_certificates 无效；
+    /// 如果指定 fetch_platform_certificates 为 true，则此参数无效。
+    /// loader 返回错误时 build 失败，返回空列表时同未指定平台证书。
+    #[cfg(feature = "x509")]
+    pub fn platform_certificate_loader<F, Fut>(&mut self, loader: F) -> &mut Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Vec<PlatformCertificate>>> + Send + 'static,
+    {
+        self.platform_certificate_loader = Some(PlatformCertificateLoader(Box::new(move || {
+            Box::pin(loader())
+        })));
+        self
+    }
+
     /// build 时是否获取最新的平台证书列表。
     /// 如果指定 platform_certificates，则指定的  platform_certificates 无效。
+    #[cfg(feature = "x509")]
     pub fn fetch_platform_certificates(&mut self) -> &mut Self {
         self.fetch_platform_certificates = true;
         self
     }
 
+    /// build 时启动后台任务，定期刷新平台证书列表。
+    /// 参见 `WechatPayClient::spawn_platform_certificate_refresher`。
+    #[cfg(feature = "x509")]
+    pub fn platform_certificate_refresh(
+        &mut self,
+        options: CertificateRefreshOptions,
+    ) -> &mut Self {
+        self.platform_certificate_refresh = Some(options);
+        self
+    }
+
+    /// 微信支付公钥。
+    /// 仅有微信支付公钥而没有平台证书的商户，指定此参数后可不指定平台证书。
+    /// 未启用 `x509` feature 时，必须指定此参数。
+    /// 响应的 Wechatpay-Serial 以 `PUB_KEY_ID_` 开头时，使用此公钥验签，否则使用平台证书验签。
+    pub fn wechatpay_public_key(&mut self, public_key: WechatPayPublicKey) -> &mut Self {
+        self.wechatpay_public_key = Some(public_key);
+        self
+    }
+
+    /// 请求重试策略。如果未指定，则不重试。
+    pub fn retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// 整体请求超时，从开始连接到响应体读取完毕。如果未指定，则不超时。
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 连接超时。如果未指定，则不超时。
+    pub fn connect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// 读超时。发出请求后等待响应的超时时间；对于文件下载，为每次读取数据的超时时间。
+    /// 如果未指定，则不超时。
+    pub fn read_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// 使用已有的 `reqwest::Client` 发送请求，便于与应用共用连接池、代理、TLS 等设置。
+    /// 指定此参数后，user_agent、timeout、connect_timeout 及 http_client_builder 均无效，
+    /// 请自行在传入的 client 中设置 User Agent。
+    pub fn http_client(&mut self, client: Client) -> &mut Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// 定制内部使用的 `reqwest::ClientBuilder`，如设置代理、TLS、连接池等。
+    /// 钩子在 user_agent、timeout 等设置之后调用。
+    pub fn http_client_builder<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnOnce(ClientBuilder) -> ClientBuilder + Send + 'static,
+    {
+        self.http_client_builder_hook = Some(ClientBuilderHook(Box::new(f)));
+        self
+    }
+
+    /// 注册拦截器。可注册多个，按注册顺序依次调用。
+    pub fn interceptor<I: Interceptor + 'static>(&mut self, interceptor: I) -> &mut Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// 调用审计记录器，记录每次 API 调用的脱敏摘要，参见 `audit`。
+    pub fn call_recorder<R: CallRecorder + 'static>(&mut self, recorder: R) -> &mut Self {
+        self.call_recorder = Some(Arc::new(recorder));
+        self
+    }
+
+    /// 通知的 Wechatpay-Timestamp 与本地时间之差的上限，默认为 5 分钟。
+    /// 验签时超出此上限返回 `WechatPayError::TimestampExpired`，用于防止重放攻击。
+    pub fn notification_max_age(&mut self, max_age: Duration) -> &mut Self {
+        self.notification_max_age = Some(max_age);
+        self
+    }
+
+    /// 签名时间戳相对本地时间的固定偏移(秒)，可以为负。
+    /// 服务器时钟漂移导致微信支付校验签名时间戳失败时使用；同时用于校验通知的时间戳。
+    pub fn clock_offset(&mut self, offset: i64) -> &mut Self {
+        self.clock = Some(Arc::new(OffsetClock::new(offset)));
+        self
+    }
+
+    /// 自定义时钟，用于生成签名时间戳及校验通知的时间戳，如使用 NTP 校正过的时间。参见 `clock`。
+    pub fn clock<C: Clock + 'static>(&mut self, clock: C) -> &mut Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// 自定义签名所用随机串的来源，默认为 `RandomNonce`。
+    /// 与 `clock` 一同设置为固定值时，签名结果是确定的，可用于编写可重现的测试。
+    pub fn nonce_provider<N: NonceProvider + 'static>(&mut self, nonce_provider: N) -> &mut Self {
+        self.nonce_provider = Some(Arc::new(nonce_provider));
+        self
+    }
+
+    /// 通知去重存储，`process_notification` 及 `NotificationDispatcher` 使用。参见 `dedup`。
+    pub fn notification_dedup_store<S: NotificationDedupStore + 'static>(
+        &mut self,
+        store: S,
+    ) -> &mut Self {
+        self.notification_dedup_store = Some(Arc::new(store));
+        self
+    }
+
+    /// 指标回调，用于接入 Prometheus 等监控系统。
+    pub fn metrics_hook<M: MetricsHook + 'static>(&mut self, metrics_hook: M) -> &mut Self {
+        self.metrics_hook = Some(Arc::new(metrics_hook));
+        self
+    }
+
+    /// 对路径以 path_prefix 开头的接口限流，如 `/v3/pay/transactions`。可多次调用，为不同接口配置限流；
+    /// 请求路径匹配多个前缀时，使用最长的前缀。重试的请求同样受限流约束。
+    pub fn rate_limit(&mut self, path_prefix: &str, limit: RateLimit) -> &mut Self {
+        self.rate_limits.push((path_prefix.to_string(), limit));
+        self
+    }
+
+    /// 响应体大小的上限(字节)。响应体超过上限时中断读取，返回 `WechatPayError::ResponseTooLarge`，
+    /// 防止异常或恶意的响应耗尽内存。如果未指定，则不限制。
+    /// 文件下载(如 `download_bill`)以流式写入，不受此限制。
+    pub fn max_response_body_size(&mut self, max_response_body_size: usize) -> &mut Self {
+        self.max_response_body_size = Some(max_response_body_size);
+        self
+    }
+
+    /// 同时发送的请求数上限，超过上限的请求排队等待，避免突发流量触发微信支付的频率限制。
+    /// 如果未指定，则不限制。
+    pub fn max_concurrent_requests(&mut self, max_concurrent_requests: usize) -> &mut Self {
+        self.max_concurrent_requests = Some(max_concurrent_requests);
+        self
+    }
+
+    /// 覆盖接口的基础 URL，用于对接 mock 网关、内部代理或灰度环境。
+    /// 须包含版本路径，如 `https://api.mch.weixin.qq.com/v3`。如果未指定，则使用微信支付的正式地址。
+    pub fn base_url(&mut self, base_url: &str) -> &mut Self {
+        self.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// 替换发送请求的传输层。如果未指定，则使用 reqwest 发送请求。
+    /// 测试时可指定 `transport::MockTransport`，返回固定的响应。
+    pub fn transport<T: Transport + 'static>(&mut self, transport: T) -> &mut Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// 启用双域名容灾。主域名(base_url)故障时，自动切换到备用域名。
+    /// 参见 `failover` 模块。
+    pub fn failover(&mut self, options: FailoverOptions) -> &mut Self {
+        self.failover = Some(options);
+        self
+    }
+
+    /// 默认的应用 ID，`jsapi_pay` 等简化下单接口使用。
+    /// 商户号则使用 mch_credential 中的 mch_id。
+    pub fn default_app_id(&mut self, app_id: String) -> &mut Self {
+        self.default_app_id = Some(app_id);
+        self
+    }
+
+    /// 默认的支付结果通知地址，`jsapi_pay` 等简化下单接口使用。
+    pub fn default_notify_url(&mut self, notify_url: String) -> &mut Self {
+        self.default_notify_url = Some(notify_url);
+        self
+    }
+
     /// 指定 User Agent。
     /// 如果未指定，将默认使用 "wechatpay Rust client"。
     /// 对于未指定 User Agent header 的请求，微信支付可能会拒绝。
@@ -124,35 +1030,385 @@ impl WechatPayClientBuilder {
         let mch_credential = self
             .mch_credential
             .take()
-            .ok_or_else(|| anyhow::format_err!("missing `mch_credential`"))?;
+            .ok_or_else(|| WechatPayError::InvalidParams("missing `mch_credential`".to_string()))?;
+
+        let client = match self.http_client.take() {
+            Some(client) => client,
+            None => {
+                let ua = if let Some(ua) = &self.user_agent {
+                    ua
+                } else {
+                    USER_AGENT
+                };
+                let mut client_builder = Client::builder().user_agent(ua);
+                if let Some(timeout) = self.timeout {
+                    client_builder = client_builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    client_builder = client_builder.connect_timeout(timeout);
+                }
+                if let Some(hook) = self.http_client_builder_hook.take() {
+                    client_builder = (hook.0)(client_builder);
+                }
+
+                client_builder.build()?
+            }
+        };
+
+        let base_url = self.base_url.take().unwrap_or_else(|| BASE_URL.to_string());
+        let transport = self
+            .transport
+            .take()
+            .unwrap_or_else(|| Arc::new(client.clone()));
+        let wechatpay_public_key = self.wechatpay_public_key.take();
+        let clock = self.clock.take().unwrap_or_else(|| Arc::new(SystemClock));
 
+        #[cfg(feature = "x509")]
         let platform_certificates = if self.fetch_platform_certificates {
-            get_platform_certificates(&mch_credential).await?
+            Some(
+                get_platform_certificates_with_transport(
+                    &client,
+                    transport.as_ref(),
+                    &base_url,
+                    &mch_credential,
+                    self.request_signer.as_deref(),
+                    clock.as_ref(),
+                )
+                .await?,
+            )
+        } else if let Some(loader) = self.platform_certificate_loader.take() {
+            Some((loader.0)().await?).filter(|certificates| !certificates.is_empty())
         } else {
-            self.platform_certificates
-                .take()
-                .ok_or_else(|| anyhow::format_err!("missing `platform_certificates`"))?
+            self.platform_certificates.take()
         };
 
-        if platform_certificates.is_empty() {
-            return Err(anyhow::format_err!("empty `platform_certificates`"));
+        #[cfg(feature = "x509")]
+        let platform_certificate_state = match platform_certificates {
+            Some(platform_certificates) => {
+                if platform_certificates.is_empty() {
+                    return Err(WechatPayError::InvalidParams(
+                        "empty `platform_certificates`".to_string(),
+                    ));
+                }
+                PlatformCertificateState::new(platform_certificates)?
+            }
+            None if wechatpay_public_key.is_some() => PlatformCertificateState::empty(),
+            None => {
+                return Err(WechatPayError::InvalidParams(
+                    "missing `platform_certificates`".to_string(),
+                ))
+            }
+        };
+        #[cfg(feature = "x509")]
+        let platform_certificate_state =
+            Arc::new(ArcSwap::from_pointee(platform_certificate_state));
+        #[cfg(not(feature = "x509"))]
+        if wechatpay_public_key.is_none() {
+            return Err(WechatPayError::InvalidParams(
+                "missing `wechatpay_public_key`, platform certificates require the `x509` feature"
+                    .to_string(),
+            ));
+        }
+        if self.max_concurrent_requests == Some(0) {
+            return Err(WechatPayError::InvalidParams(
+                "`max_concurrent_requests` must be greater than 0".to_string(),
+            ));
         }
 
-        let platform_certificate_state = Arc::new(Mutex::new(PlatformCertificateState::new(
-            platform_certificates,
-        )?));
+        let client = WechatPayClient {
+            client,
+            mch_credential: Arc::new(ArcSwap::from_pointee(mch_credential)),
+            request_signer: self.request_signer.take(),
+            #[cfg(feature = "x509")]
+            platform_certificate_state,
+            #[cfg(feature = "x509")]
+            last_unknown_serial_fetch: Arc::new(tokio::sync::Mutex::new(None)),
+            wechatpay_public_key,
+            retry_policy: self.retry_policy.take(),
+            read_timeout: self.read_timeout,
+            interceptors: std::mem::take(&mut self.interceptors),
+            call_recorder: self.call_recorder.take(),
+            metrics_hook: self.metrics_hook.take(),
+            notification_dedup_store: self.notification_dedup_store.take(),
+            rate_limiter: if self.rate_limits.is_empty() {
+                None
+            } else {
+                Some(Arc::new(RateLimiter::new(std::mem::take(
+                    &mut self.rate_limits,
+                ))))
+            },
+            max_response_body_size: self.max_response_body_size,
+            concurrency_limiter: self
+                .max_concurrent_requests
+                .map(|n| Arc::new(tokio::sync::Semaphore::new(n))),
+            failover: self
+                .failover
+                .take()
+                .map(|options| Arc::new(Failover::new(&base_url, options))),
+            base_url,
+            transport,
+            default_app_id: self.default_app_id.take(),
+            default_notify_url: self.default_notify_url.take(),
+            notification_max_age: self
+                .notification_max_age
+                .unwrap_or(DEFAULT_NOTIFICATION_MAX_AGE),
+            clock,
+            nonce_provider: self
+                .nonce_provider
+                .take()
+                .unwrap_or_else(|| Arc::new(RandomNonce)),
+        };
+        #[cfg(feature = "x509")]
+        if let Some(options) = self.platform_certificate_refresh.take() {
+            client.spawn_platform_certificate_refresher(options);
+        }
+        Ok(client)
+    }
+}
 
-        let ua = if let Some(ua) = &self.user_agent {
-            ua
-        } else {
-            USER_AGENT
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
         };
-        let client_builder = Client::builder().user_agent(ua);
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff(40), Duration::from_millis(500));
+    }
 
-        Ok(WechatPayClient {
-            client: client_builder.build()?,
-            mch_credential,
-            platform_certificate_state,
+    #[tokio::test]
+    async fn test_clock_offset() -> anyhow::Result<()> {
+        use crate::transport::mock_client_with;
+
+        let (client, mock) = mock_client_with(|builder| {
+            builder.clock_offset(-3600);
         })
+        .await?;
+
+        let req = client
+            .client
+            .get(format!("{}/certificates", client.base_url))
+            .build()?;
+        let req = client.sign_request(req).await?;
+        let authorization = req.headers()["Authorization"].to_str()?;
+        let timestamp: i64 = authorization
+            .split(',')
+            .find_map(|kv| kv.strip_prefix("timestamp="))
+            .unwrap()
+            .trim_matches('"')
+            .parse()?;
+        assert!((SystemClock.timestamp() - 3600 - timestamp).abs() <= 1);
+
+        // 通知的时间戳按偏移后的时钟校验
+        let body = "{}";
+        let headers = mock.signature_headers(body)?;
+        let err = client
+            .verify_notification_parts(&headers, body.as_bytes())
+            .await;
+        assert!(matches!(err, Err(WechatPayError::TimestampExpired(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_json_and_raw() -> anyhow::Result<()> {
+        use crate::transport::mock_client;
+
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::GET,
+            "/v3/custom/json",
+            StatusCode::OK,
+            r#"{"foo":"bar"}"#,
+        );
+        mock.on(Method::GET, "/v3/custom/raw", StatusCode::OK, "a,b,c");
+
+        let url = format!("{}/custom/json", client.base_url);
+        let req = client.client.get(url).build()?;
+        let v: serde_json::Value = client.execute_json(req).await?;
+        assert_eq!(v["foo"], "bar");
+
+        let url = format!("{}/custom/raw", client.base_url);
+        let req = client.client.get(url).build()?;
+        let (status, headers, body) = client.execute_raw(req).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers.contains_key("Wechatpay-Signature"));
+        assert_eq!(&body[..], b"a,b,c");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_response_body_size() -> anyhow::Result<()> {
+        use crate::transport::mock_client_with;
+
+        let (client, mock) = mock_client_with(|builder| {
+            builder.max_response_body_size(64);
+        })
+        .await?;
+        mock.on(Method::GET, "/v3/custom/small", StatusCode::OK, "{}");
+        mock.on(
+            Method::GET,
+            "/v3/custom/large",
+            StatusCode::OK,
+            &format!(r#"{{"data":"{}"}}"#, "x".repeat(100)),
+        );
+        mock.on(
+            Method::GET,
+            "/v3/custom/error",
+            StatusCode::BAD_REQUEST,
+            &format!(
+                r#"{{"code":"PARAM_ERROR","message":"{}"}}"#,
+                "x".repeat(100)
+            ),
+        );
+
+        for (path, ok) in [("small", true), ("large", false), ("error", false)] {
+            let url = format!("{}/custom/{}", client.base_url, path);
+            let req = client.client.get(url).build()?;
+            let res = client.execute_json::<serde_json::Value>(req).await;
+            if ok {
+                res?;
+            } else {
+                assert!(matches!(res, Err(WechatPayError::ResponseTooLarge(_))));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "x509")]
+    #[tokio::test]
+    async fn test_platform_certificate_loader() -> anyhow::Result<()> {
+        use crate::platform_certificate::PersistedPlatformCertificate;
+        use crate::transport::MockTransport;
+        use chrono::Local;
+        use rsa::RsaPrivateKey;
+
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let certificate = PlatformCertificate::try_from(PersistedPlatformCertificate {
+            serial_no: "1DDE55AD98ED71D6EDD4A4A16996DE7B47773A8C".to_string(),
+            effective_time: Local::now() - chrono::Duration::days(1),
+            expire_time: Local::now() + chrono::Duration::days(365),
+            certificate: std::fs::read_to_string(format!("{}/apiclient_cert.pem", dir))?,
+        })?;
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let mock = MockTransport::new(private_key.clone(), "PUB_KEY_ID_0000000000000001");
+        let credential = || {
+            MchCredential::new(
+                "1900000001".to_string(),
+                "serial".to_string(),
+                private_key.clone(),
+                "0".repeat(32),
+            )
+        };
+
+        let loaded = certificate.clone();
+        let client = WechatPayClient::builder()
+            .mch_credential(credential())
+            .transport(mock.clone())
+            .platform_certificate_loader(move || async move { Ok(vec![loaded]) })
+            .build()
+            .await?;
+        assert_eq!(client.platform_certificates(), vec![certificate]);
+        assert!(mock.requests().is_empty());
+
+        // 刚构建完成时遇到未知 serial_no，也会拉取一次平台证书；之后的拉取受防抖限制
+        let fetches = || {
+            mock.requests()
+                .iter()
+                .filter(|r| r.path == "/v3/certificates")
+                .count()
+        };
+        assert!(client
+            .platform_certificate_public_key("UNKNOWN")
+            .await
+            .is_err());
+        assert_eq!(fetches(), 1);
+        assert!(client
+            .platform_certificate_public_key("UNKNOWN")
+            .await
+            .is_err());
+        assert_eq!(fetches(), 1);
+
+        // 返回空列表时同未指定平台证书
+        let res = WechatPayClient::builder()
+            .mch_credential(credential())
+            .transport(mock.clone())
+            .platform_certificate_loader(|| async { Ok(vec![]) })
+            .build()
+            .await;
+        assert!(matches!(res, Err(WechatPayError::InvalidParams(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_requests() -> anyhow::Result<()> {
+        use crate::transport::MockTransport;
+        use async_trait::async_trait;
+        use rsa::RsaPrivateKey;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Clone)]
+        struct SlowTransport {
+            inner: MockTransport,
+            in_flight: Arc<AtomicUsize>,
+            max_in_flight: Arc<AtomicUsize>,
+        }
+
+        #[async_trait]
+        impl Transport for SlowTransport {
+            async fn send(&self, req: Request) -> Result<Response> {
+                let n = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_in_flight.fetch_max(n, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                self.inner.send(req).await
+            }
+        }
+
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let mock = MockTransport::new(private_key.clone(), "PUB_KEY_ID_0000000000000001");
+        mock.on(Method::GET, "/v3/custom/json", StatusCode::OK, "{}");
+        let transport = SlowTransport {
+            inner: mock.clone(),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            max_in_flight: Arc::new(AtomicUsize::new(0)),
+        };
+
+        let mut builder = WechatPayClient::builder();
+        builder
+            .mch_credential(MchCredential::new(
+                "1900000001".to_string(),
+                "serial".to_string(),
+                private_key,
+                "0".repeat(32),
+            ))
+            .wechatpay_public_key(mock.wechatpay_public_key())
+            .transport(transport.clone())
+            .max_concurrent_requests(2);
+        let client = builder.build().await?;
+
+        let tasks: Vec<_> = (0..6)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    let url = format!("{}/custom/json", client.base_url);
+                    let req = client.client.get(url).build()?;
+                    client.execute_json::<serde_json::Value>(req).await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await??;
+        }
+        assert_eq!(transport.max_in_flight.load(Ordering::SeqCst), 2);
+        assert_eq!(mock.requests().len(), 6);
+        Ok(())
     }
 }
@@ -0,0 +1,79 @@
+//! 时钟。
+//! 请求签名及通知验签都依赖当前时间，微信支付会拒绝时间戳与其服务器相差过大的请求。
+//! 服务器时钟不准又无法校正时，可通过 `WechatPayClientBuilder::clock_offset` 设置固定偏移，
+//! 或通过 `WechatPayClientBuilder::clock` 指定自定义的时钟。
+
+use chrono::Local;
+use std::fmt;
+
+/// 时钟
+pub trait Clock: Send + Sync {
+    /// 当前的 Unix 时间戳(秒)
+    fn timestamp(&self) -> i64;
+}
+
+impl fmt::Debug for dyn Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clock")
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> i64 + Send + Sync,
+{
+    fn timestamp(&self) -> i64 {
+        self()
+    }
+}
+
+/// 系统时钟
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn timestamp(&self) -> i64 {
+        Local::now().timestamp()
+    }
+}
+
+/// 在另一时钟的基础上附加固定的偏移(秒)，偏移可以为负。
+#[derive(Debug, Clone, Copy)]
+pub struct OffsetClock<C = SystemClock> {
+    clock: C,
+    offset: i64,
+}
+
+impl OffsetClock {
+    /// 在系统时钟的基础上附加偏移
+    pub fn new(offset: i64) -> OffsetClock {
+        OffsetClock::with_clock(SystemClock, offset)
+    }
+}
+
+impl<C: Clock> OffsetClock<C> {
+    pub fn with_clock(clock: C, offset: i64) -> OffsetClock<C> {
+        OffsetClock { clock, offset }
+    }
+}
+
+impl<C: Clock> Clock for OffsetClock<C> {
+    fn timestamp(&self) -> i64 {
+        self.clock.timestamp().saturating_add(self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_clock() {
+        let clock = OffsetClock::with_clock(|| 1_600_000_000, -30);
+        assert_eq!(clock.timestamp(), 1_599_999_970);
+
+        let now = SystemClock.timestamp();
+        let ts = OffsetClock::new(3600).timestamp();
+        assert!((ts - now - 3600).abs() <= 1);
+    }
+}
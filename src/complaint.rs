@@ -0,0 +1,424 @@
+//! 消费者投诉相关接口。
+//! 微信支付要求商户在收到投诉后 24 小时内回复用户，并在处理完成后反馈处理完成，否则会影响商户的投诉考核。
+
+use crate::client::WechatPayClient;
+use crate::error::Result;
+use crate::paginate::{Page, Paginated};
+use crate::util::datetime_fmt;
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDate};
+use futures::stream::{self, Stream};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+impl WechatPayClient {
+    /// 查询投诉单列表。
+    /// 查询的日期范围(begin_date 至 end_date)不能超过 30 天。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter10_2_11.shtml>
+    pub async fn query_complaints(&self, params: &ComplaintQueryParams) -> Result<ComplaintList> {
+        let url = format!(
+            "{}/merchant-service/complaints-v2?limit={}&offset={}&begin_date={}&end_date={}&complainted_mchid={}",
+            self.base_url,
+            params.limit,
+            params.offset,
+            params.begin_date.format("%Y-%m-%d"),
+            params.end_date.format("%Y-%m-%d"),
+            &self.mch_credential().mch_id
+        );
+        let req = self.client.get(url).build()?;
+        let res = self.execute(req).await?;
+        let res: ComplaintList = res.json().await?;
+        Ok(res)
+    }
+
+    /// 分页查询日期范围内的投诉单，每页 limit 条，可通过 `into_stream` 自动翻页。
+    pub fn query_complaints_paginated(
+        &self,
+        begin_date: NaiveDate,
+        end_date: NaiveDate,
+        limit: u32,
+    ) -> Paginated<Complaint> {
+        let client = self.clone();
+        Paginated::new(limit, move |offset, limit| {
+            let client = client.clone();
+            async move {
+                let params = ComplaintQueryParams {
+                    limit,
+                    offset,
+                    begin_date,
+                    end_date,
+                };
+                client.query_complaints(&params).await.map(Page::from)
+            }
+        })
+    }
+
+    /// 周期性拉取新投诉。
+    /// 适合没有公网回调地址、无法接收投诉通知的商户。
+    /// 每隔 `options.interval` 拉取一次最近 `options.lookback_days` 天内的投诉单，内部处理分页，
+    /// 并按 complaint_id 去重，已产出过的投诉单不会重复产出。
+    /// 拉取失败时记录日志，并在下个周期重试。
+    pub fn poll_complaints(&self, options: ComplaintPollOptions) -> impl Stream<Item = Complaint> {
+        struct State {
+            client: WechatPayClient,
+            options: ComplaintPollOptions,
+            seen: HashSet<String>,
+            buffer: VecDeque<Complaint>,
+            first: bool,
+        }
+
+        let state = State {
+            client: self.clone(),
+            options,
+            seen: HashSet::new(),
+            buffer: VecDeque::new(),
+            first: true,
+        };
+
+        stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(complaint) = state.buffer.pop_front() {
+                    return Some((complaint, state));
+                }
+
+                if !state.first {
+                    tokio::time::sleep(state.options.interval).await;
+                }
+                state.first = false;
+
+                match state.client.fetch_recent_complaints(&state.options).await {
+                    Ok(complaints) => {
+                        // 只保留当前时间窗口内的 complaint_id，避免 seen 无限增长。
+                        let mut seen = HashSet::new();
+                        for complaint in complaints {
+                            seen.insert(complaint.complaint_id.clone());
+                            if !state.seen.contains(&complaint.complaint_id) {
+                                state.buffer.push_back(complaint);
+                            }
+                        }
+                        state.seen = seen;
+                    }
+                    Err(e) => {
+                        log::warn!("failed to poll complaints: {}", e);
+                    }
+                }
+            }
+        })
+    }
+
+    /// 拉取时间窗口内的所有投诉单(自动翻页)。
+    async fn fetch_recent_complaints(
+        &self,
+        options: &ComplaintPollOptions,
+    ) -> Result<Vec<Complaint>> {
+        let end_date = Local::now().date_naive();
+        let begin_date = end_date - ChronoDuration::days(options.lookback_days as i64);
+
+        self.query_complaints_paginated(begin_date, end_date, options.page_size)
+            .into_stream()
+            .try_collect()
+            .await
+    }
+
+    /// 回复用户。
+    /// 商户可在投诉处理过程中多次回复用户，回复内容会展示在用户侧的投诉详情中。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter10_2_14.shtml>
+    pub async fn reply_complaint(
+        &self,
+        complaint_id: &str,
+        params: &ComplaintReplyParams,
+    ) -> Result<()> {
+        #[derive(Debug, Clone, Serialize)]
+        struct ComplaintReplyRequest<'a> {
+            complainted_mchid: &'a str,
+            #[serde(flatten)]
+            params: &'a ComplaintReplyParams,
+        }
+
+        let url = format!(
+            "{}/merchant-service/complaints-v2/{}/response",
+            self.base_url, complaint_id
+        );
+        let mch_credential = self.mch_credential();
+        let req = ComplaintReplyRequest {
+            complainted_mchid: &mch_credential.mch_id,
+            params,
+        };
+        let req = self.client.post(url).json(&req).build()?;
+        let _res = self.execute(req).await?;
+        Ok(())
+    }
+
+    /// 反馈处理完成。
+    /// 商户处理完投诉后调用此接口，投诉单状态将变为处理完成。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter10_2_15.shtml>
+    pub async fn complete_complaint(&self, complaint_id: &str) -> Result<()> {
+        #[derive(Debug, Clone, Serialize)]
+        struct CompleteComplaintRequest<'a> {
+            complainted_mchid: &'a str,
+        }
+
+        let url = format!(
+            "{}/merchant-service/complaints-v2/{}/complete",
+            self.base_url, complaint_id
+        );
+        let mch_credential = self.mch_credential();
+        let req = CompleteComplaintRequest {
+            complainted_mchid: &mch_credential.mch_id,
+        };
+        let req = self.client.post(url).json(&req).build()?;
+        let _res = self.execute(req).await?;
+        Ok(())
+    }
+}
+
+/// 回复用户的参数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplaintReplyParams {
+    /// 回复内容。不超过 200 字符。
+    pub response_content: String,
+    /// 回复图片。为图片上传接口返回的 media_id，至多 4 张。
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub response_images: Vec<String>,
+    /// 跳转链接。商户可在回复中附带一个跳转链接，如在线客服、退款入口等。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub jump_url: Option<String>,
+    /// 跳转链接文案。传入 jump_url 时必填，不超过 10 个字符。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub jump_url_text: Option<String>,
+}
+
+impl ComplaintReplyParams {
+    /// 仅包含文字内容的回复
+    pub fn new(response_content: String) -> ComplaintReplyParams {
+        ComplaintReplyParams {
+            response_content,
+            response_images: vec![],
+            jump_url: None,
+            jump_url_text: None,
+        }
+    }
+}
+
+/// 投诉通知的解密内容。
+/// 对应 event_type:
+/// * COMPLAINT.CREATE：产生新投诉
+/// * COMPLAINT.STATE_CHANGE：投诉状态变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ComplaintNotification {
+    /// 投诉单号
+    pub complaint_id: String,
+    /// 动作类型。
+    /// * CREATE_COMPLAINT：用户提交投诉
+    /// * CONTINUE_COMPLAINT：用户继续投诉
+    /// * USER_RESPONSE：用户新留言
+    /// * RESPONSE_BY_PLATFORM：平台新留言
+    /// * SELLER_REFUND：商户发起全额退款
+    /// * MERCHANT_RESPONSE：商户新回复
+    /// * MERCHANT_CONFIRM_COMPLETE：商户反馈处理完成
+    /// * USER_APPLY_PLATFORM_SERVICE：用户申请平台协助
+    /// * USER_CANCEL_PLATFORM_SERVICE：用户取消平台协助
+    /// * PLATFORM_SERVICE_FINISHED：客服结束平台协助
+    pub action_type: String,
+}
+
+/// 查询投诉单列表的参数。
+#[derive(Debug, Clone)]
+pub struct ComplaintQueryParams {
+    /// 分页大小，取值范围为 [1, 50]。
+    pub limit: u32,
+    /// 分页开始位置，从 0 开始。
+    pub offset: u32,
+    /// 开始日期，投诉发生的开始日期。
+    pub begin_date: NaiveDate,
+    /// 结束日期，投诉发生的结束日期。与 begin_date 相差不能超过 30 天。
+    pub end_date: NaiveDate,
+}
+
+/// 投诉单列表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ComplaintList {
+    /// 投诉单列表
+    #[serde(default)]
+    pub data: Vec<Complaint>,
+    /// 分页大小
+    pub limit: u32,
+    /// 分页开始位置
+    pub offset: u32,
+    /// 投诉单总数
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub total_count: Option<u32>,
+}
+
+impl From<ComplaintList> for Page<Complaint> {
+    fn from(list: ComplaintList) -> Self {
+        Page {
+            items: list.data,
+            total_count: list.total_count,
+        }
+    }
+}
+
+/// 投诉单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Complaint {
+    /// 投诉单号
+    pub complaint_id: String,
+    /// 投诉时间
+    #[serde(with = "datetime_fmt")]
+    pub complaint_time: DateTime<Local>,
+    /// 投诉详情
+    pub complaint_detail: String,
+    /// 投诉单状态。
+    /// * PENDING：待处理
+    /// * PROCESSING：处理中
+    /// * PROCESSED：已处理完成
+    pub complaint_state: String,
+    /// 投诉人联系方式。已使用商户公钥加密。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer_phone: Option<String>,
+    /// 投诉单关联的订单信息
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub complaint_order_info: Vec<ComplaintOrderInfo>,
+    /// 投诉单是否已全额退款
+    #[serde(default)]
+    pub complaint_full_refunded: bool,
+    /// 是否有待回复的用户留言
+    #[serde(default)]
+    pub incoming_user_response: bool,
+    /// 用户投诉次数
+    #[serde(default)]
+    pub user_complaint_times: i32,
+    /// 问题描述
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub problem_description: Option<String>,
+}
+
+/// 投诉单关联的订单信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ComplaintOrderInfo {
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 商户订单号
+    pub out_trade_no: String,
+    /// 订单金额，单位为分。
+    pub amount: i32,
+}
+
+/// 投诉轮询的配置
+#[derive(Debug, Clone)]
+pub struct ComplaintPollOptions {
+    /// 拉取间隔
+    pub interval: Duration,
+    /// 每次拉取最近多少天内的投诉单，不超过 30 天。
+    pub lookback_days: u32,
+    /// 分页大小，取值范围为 [1, 50]。
+    pub page_size: u32,
+}
+
+impl Default for ComplaintPollOptions {
+    fn default() -> Self {
+        ComplaintPollOptions {
+            interval: Duration::from_secs(60),
+            lookback_days: 1,
+            page_size: 50,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock_client;
+    use futures::StreamExt;
+    use reqwest::{Method, StatusCode};
+
+    fn complaint(complaint_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "complaint_id": complaint_id,
+            "complaint_time": "2015-05-20T13:29:35+08:00",
+            "complaint_detail": "反馈一个重复扣费的问题",
+            "complaint_state": "PENDING",
+        })
+    }
+
+    #[tokio::test]
+    async fn test_query_complaints_paginated() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        let path = |offset: u32| {
+            format!(
+                "/v3/merchant-service/complaints-v2?limit=2&offset={}&begin_date=2015-05-01&end_date=2015-05-30&complainted_mchid=1900000001",
+                offset
+            )
+        };
+        // 未返回 total_count 时，以本页数据不足 limit 条作为结束条件
+        mock.on(
+            Method::GET,
+            &path(0),
+            StatusCode::OK,
+            &serde_json::json!({"data": [complaint("1"), complaint("2")], "limit": 2, "offset": 0})
+                .to_string(),
+        );
+        mock.on(
+            Method::GET,
+            &path(2),
+            StatusCode::OK,
+            &serde_json::json!({"data": [complaint("3")], "limit": 2, "offset": 2}).to_string(),
+        );
+
+        let begin_date = NaiveDate::from_ymd_opt(2015, 5, 1).unwrap();
+        let end_date = NaiveDate::from_ymd_opt(2015, 5, 30).unwrap();
+        let complaints: Vec<Complaint> = client
+            .query_complaints_paginated(begin_date, end_date, 2)
+            .into_stream()
+            .try_collect()
+            .await?;
+        let ids: Vec<_> = complaints.iter().map(|c| c.complaint_id.as_str()).collect();
+        assert_eq!(ids, ["1", "2", "3"]);
+        assert_eq!(mock.requests().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_poll_complaints() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        let path = "/v3/merchant-service/complaints-v2";
+        let list = |data: Vec<serde_json::Value>| {
+            serde_json::json!({"total_count": data.len(), "data": data, "limit": 50, "offset": 0})
+                .to_string()
+        };
+        mock.on(
+            Method::GET,
+            path,
+            StatusCode::OK,
+            &list(vec![complaint("1"), complaint("2")]),
+        );
+
+        let options = ComplaintPollOptions {
+            interval: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let mut complaints = Box::pin(client.poll_complaints(options));
+        assert_eq!(complaints.next().await.unwrap().complaint_id, "1");
+        assert_eq!(complaints.next().await.unwrap().complaint_id, "2");
+
+        // 之后的拉取返回同样的投诉单，不会重复产出
+        let next = tokio::time::timeout(Duration::from_millis(100), complaints.next()).await;
+        assert!(next.is_err());
+        assert!(mock.requests().len() > 1);
+
+        mock.on(
+            Method::GET,
+            path,
+            StatusCode::OK,
+            &list(vec![complaint("1"), complaint("2"), complaint("3")]),
+        );
+        assert_eq!(complaints.next().await.unwrap().complaint_id, "3");
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! 通知去重。
+//! 微信支付可能重复发送同一通知，`NotificationDedupStore` 按通知 id 判重。
+
+use crate::error::Result;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// 通知去重存储。
+/// 通过 `WechatPayClientBuilder::notification_dedup_store` 注册后，`process_notification` 及
+/// `NotificationDispatcher` 在处理通知前以 `insert_if_absent` 记录通知 id，已记录的通知返回
+/// `WechatPayError::DuplicateNotification`；处理失败时以 `remove` 删除，以便微信支付重新通知时再次处理。
+/// 多实例部署时，可基于 Redis 等共享存储实现。
+#[async_trait]
+pub trait NotificationDedupStore: Send + Sync {
+    /// 通知 id 是否已记录
+    async fn contains(&self, id: &str) -> Result<bool>;
+
+    /// 通知 id 未记录时记录，并返回 true；已记录时返回 false。
+    /// 判断与记录须为原子操作(如 Redis 的 `SET NX`)，否则并发的重复通知可能同时被处理。
+    async fn insert_if_absent(&self, id: &str) -> Result<bool>;
+
+    /// 删除通知 id。业务处理失败、需要微信支付重新通知时调用。
+    async fn remove(&self, id: &str) -> Result<()>;
+}
+
+impl fmt::Debug for dyn NotificationDedupStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NotificationDedupStore")
+    }
+}
+
+/// 基于内存的通知去重存储，超出容量时淘汰最近最少使用的通知 id。
+/// 克隆后共享同一存储。
+#[derive(Debug, Clone)]
+pub struct MemoryDedupStore {
+    inner: Arc<Mutex<Lru>>,
+}
+
+#[derive(Debug)]
+struct Lru {
+    capacity: usize,
+    seq: u64,
+    ids: HashMap<String, u64>,
+    order: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn touch(&mut self, id: &str) -> bool {
+        self.seq += 1;
+        match self.ids.get_mut(id) {
+            Some(seq) => {
+                self.order.remove(seq);
+                *seq = self.seq;
+                self.order.insert(self.seq, id.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl MemoryDedupStore {
+    /// capacity 为最多记录的通知 id 数
+    pub fn new(capacity: usize) -> MemoryDedupStore {
+        MemoryDedupStore {
+            inner: Arc::new(Mutex::new(Lru {
+                capacity: capacity.max(1),
+                seq: 0,
+                ids: HashMap::new(),
+                order: BTreeMap::new(),
+            })),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationDedupStore for MemoryDedupStore {
+    async fn contains(&self, id: &str) -> Result<bool> {
+        Ok(self.inner.lock().unwrap().touch(id))
+    }
+
+    async fn insert_if_absent(&self, id: &str) -> Result<bool> {
+        let mut lru = self.inner.lock().unwrap();
+        if lru.touch(id) {
+            return Ok(false);
+        }
+        if lru.ids.len() >= lru.capacity {
+            if let Some((_, oldest)) = lru.order.pop_first() {
+                lru.ids.remove(&oldest);
+            }
+        }
+        let seq = lru.seq;
+        lru.ids.insert(id.to_string(), seq);
+        lru.order.insert(seq, id.to_string());
+        Ok(true)
+    }
+
+    async fn remove(&self, id: &str) -> Result<()> {
+        let mut lru = self.inner.lock().unwrap();
+        if let Some(seq) = lru.ids.remove(id) {
+            lru.order.remove(&seq);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_dedup_store() -> anyhow::Result<()> {
+        let store = MemoryDedupStore::new(2);
+        assert!(store.insert_if_absent("a").await?);
+        assert!(store.insert_if_absent("b").await?);
+        assert!(!store.insert_if_absent("a").await?);
+        // b 最近最少使用，被淘汰
+        assert!(store.insert_if_absent("c").await?);
+        assert!(!store.contains("b").await?);
+        assert!(store.contains("a").await?);
+        assert!(store.contains("c").await?);
+
+        store.remove("a").await?;
+        assert!(!store.contains("a").await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+//! 通知分发器。
+//! 注册各类通知的异步回调，`NotificationDispatcher::dispatch` 完成验签、去重、解密后，
+//! 按通知类型调用对应的回调，并生成应答给微信支付的状态码及 body。
+//! ```ignore
+//! let mut dispatcher = NotificationDispatcher::new(client);
+//! dispatcher
+//!     .on_payment(|trade| async move {
+//!         // ...
+//!         Ok(())
+//!     })
+//!     .on_refund(|refund| async move { Ok(()) });
+//!
+//! let (status, body) = dispatcher.dispatch(&headers, &body).await;
+//! ```
+
+use crate::client::WechatPayClient;
+use crate::complaint::ComplaintNotification;
+use crate::error::{Result, WechatPayError};
+use crate::fapiao::FapiaoNotification;
+use crate::notify::{CouponNotification, NotifyEventType, NotifyResponse, WechatPayNotification};
+use crate::refund::RefundNotification;
+use crate::trade::TradeQueryResponse;
+use futures::future::BoxFuture;
+use http::{HeaderMap, StatusCode};
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::future::Future;
+
+type Handler<T> = Box<dyn Fn(T) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+fn boxed<T, F, Fut>(f: F) -> Handler<T>
+where
+    F: Fn(T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    Box::new(move |t| Box::pin(f(t)))
+}
+
+/// 通知分发器
+pub struct NotificationDispatcher {
+    client: WechatPayClient,
+    on_payment: Option<Handler<TradeQueryResponse>>,
+    on_refund: Option<Handler<RefundNotification>>,
+    on_coupon_use: Option<Handler<CouponNotification>>,
+    on_fapiao: Option<Handler<FapiaoNotification>>,
+    on_complaint: Option<Handler<ComplaintNotification>>,
+    on_other: Option<Handler<WechatPayNotification>>,
+}
+
+impl fmt::Debug for NotificationDispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotificationDispatcher")
+            .field("client", &self.client)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NotificationDispatcher {
+    pub fn new(client: WechatPayClient) -> NotificationDispatcher {
+        NotificationDispatcher {
+            client,
+            on_payment: None,
+            on_refund: None,
+            on_coupon_use: None,
+            on_fapiao: None,
+            on_complaint: None,
+            on_other: None,
+        }
+    }
+
+    /// 支付成功通知(TRANSACTION.SUCCESS)
+    pub fn on_payment<F, Fut>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(TradeQueryResponse) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_payment = Some(boxed(f));
+        self
+    }
+
+    /// 退款结果通知(REFUND.SUCCESS、REFUND.ABNORMAL、REFUND.CLOSED)
+    pub fn on_refund<F, Fut>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(RefundNotification) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_refund = Some(boxed(f));
+        self
+    }
+
+    /// 代金券核销通知(COUPON.USE)
+    pub fn on_coupon_use<F, Fut>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(CouponNotification) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_coupon_use = Some(boxed(f));
+        self
+    }
+
+    /// 电子发票通知(FAPIAO.*)
+    pub fn on_fapiao<F, Fut>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(FapiaoNotification) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_fapiao = Some(boxed(f));
+        self
+    }
+
+    /// 投诉通知(COMPLAINT.*)
+    pub fn on_complaint<F, Fut>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(ComplaintNotification) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_complaint = Some(boxed(f));
+        self
+    }
+
+    /// 其他通知，即未注册对应回调的通知。参数为未解密的通知，可通过
+    /// `WechatPayClient::decrypt_notification_resource` 解密。
+    /// 未注册时，这类通知直接应答成功。
+    pub fn on_other<F, Fut>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(WechatPayNotification) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.on_other = Some(boxed(f));
+        self
+    }
+
+    /// 处理通知，返回应答给微信支付的 HTTP 状态码及 body。
+    /// 回调返回 Err 时应答失败，微信支付稍后会重新通知。
+    /// 如 client 配置了通知去重存储，调用回调前记录通知 id，回调失败时删除；重复的通知直接应答成功。
+    pub async fn dispatch(&self, headers: &HeaderMap, body: &[u8]) -> (StatusCode, NotifyResponse) {
+        match self.try_dispatch(headers, body).await {
+            Ok(()) => (StatusCode::OK, NotifyResponse::success()),
+            Err(Failure::Invalid(e)) => {
+                log::warn!("invalid notification: {}", e);
+                NotifyResponse::from_error(&e)
+            }
+            Err(Failure::Handler(e)) => {
+                log::error!("failed to handle notification: {}", e);
+                let res = NotifyResponse::fail("FAIL", &e.to_string());
+                (res.status_code(), res)
+            }
+        }
+    }
+
+    async fn try_dispatch(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), Failure> {
+        self.client
+            .verify_notification_parts(headers, body)
+            .await
+            .map_err(Failure::Invalid)?;
+        let noti: WechatPayNotification =
+            serde_json::from_slice(body).map_err(|e| Failure::Invalid(e.into()))?;
+
+        let store = match &self.client.notification_dedup_store {
+            Some(store) => store,
+            None => return self.call_handlers(&noti).await,
+        };
+        if !store
+            .insert_if_absent(&noti.id)
+            .await
+            .map_err(Failure::Handler)?
+        {
+            return Err(Failure::Invalid(WechatPayError::DuplicateNotification(
+                noti.id,
+            )));
+        }
+        let result = self.call_handlers(&noti).await;
+        if result.is_err() {
+            if let Err(e) = store.remove(&noti.id).await {
+                log::error!("failed to remove notification id {}: {}", noti.id, e);
+            }
+        }
+        result
+    }
+
+    /// 按通知类型调用回调
+    async fn call_handlers(&self, noti: &WechatPayNotification) -> Result<(), Failure> {
+        let category = noti.event_type.category();
+        let called = match noti.event_type {
+            NotifyEventType::TransactionSuccess => self.call(&self.on_payment, noti).await?,
+            _ if category == "REFUND" => self.call(&self.on_refund, noti).await?,
+            NotifyEventType::CouponUse => self.call(&self.on_coupon_use, noti).await?,
+            _ if category == "FAPIAO" => self.call(&self.on_fapiao, noti).await?,
+            _ if category == "COMPLAINT" => self.call(&self.on_complaint, noti).await?,
+            _ => false,
+        };
+        if !called {
+            match &self.on_other {
+                Some(f) => f(noti.clone()).await.map_err(Failure::Handler)?,
+                None => log::warn!(
+                    "no handler for notification {}, event_type: {}",
+                    noti.id,
+                    noti.event_type.as_str()
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// 解密并调用回调。未注册回调时返回 false。
+    async fn call<T: DeserializeOwned>(
+        &self,
+        handler: &Option<Handler<T>>,
+        noti: &WechatPayNotification,
+    ) -> Result<bool, Failure> {
+        let handler = match handler {
+            Some(handler) => handler,
+            None => return Ok(false),
+        };
+        let resource = self
+            .client
+            .decrypt_notification_resource(noti)
+            .map_err(Failure::Invalid)?;
+        handler(resource).await.map_err(Failure::Handler)?;
+        Ok(true)
+    }
+}
+
+/// 通知处理失败的原因
+enum Failure {
+    /// 通知本身有问题，如验签失败、解密失败，或重复的通知
+    Invalid(WechatPayError),
+    /// 回调或去重存储出错
+    Handler(WechatPayError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::MemoryDedupStore;
+    use crate::transport::{mock_client_with, mock_notification};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn coupon(stock_id: &str) -> String {
+        serde_json::json!({
+            "stock_creator_mchid": "1900000001",
+            "stock_id": stock_id,
+            "coupon_id": "98674556",
+            "status": "USED",
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() -> anyhow::Result<()> {
+        let (client, mock) = mock_client_with(|builder| {
+            builder.notification_dedup_store(MemoryDedupStore::new(16));
+        })
+        .await?;
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut dispatcher = NotificationDispatcher::new(client);
+        let c = count.clone();
+        dispatcher.on_coupon_use(move |v| {
+            let c = c.clone();
+            async move {
+                if v.stock_id == "bad" {
+                    return Err(WechatPayError::Other("boom".to_string()));
+                }
+                c.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        });
+
+        let body = mock_notification("EV-1", "COUPON.USE", "coupon", &coupon("1"));
+        let headers = mock.signature_headers(&body)?;
+        let (status, res) = dispatcher.dispatch(&headers, body.as_bytes()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(res.is_success());
+        // 重复的通知，应答成功但不再调用回调
+        let (status, _) = dispatcher.dispatch(&headers, body.as_bytes()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let body = mock_notification("EV-2", "COUPON.USE", "coupon", &coupon("bad"));
+        let headers = mock.signature_headers(&body)?;
+        let (status, res) = dispatcher.dispatch(&headers, body.as_bytes()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!res.is_success());
+        // 回调失败的通知未记录，重新通知时再次调用回调
+        let (status, _) = dispatcher.dispatch(&headers, body.as_bytes()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let (status, _) = dispatcher
+            .dispatch(&HeaderMap::new(), body.as_bytes())
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        Ok(())
+    }
+}
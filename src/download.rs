@@ -0,0 +1,321 @@
+//! 文件下载。
+//! 账单、电子回单、发票文件等，均是先获取 download_url，再通过签名的 GET 请求下载文件内容。
+
+use crate::client::WechatPayClient;
+use crate::error::{Result, WechatPayError};
+use crate::util::hex_encode;
+use bytes::Bytes;
+use reqwest::Response;
+use rsa::sha2::Sha256;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// 文件摘要。用于校验下载的文件是否完整。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHash {
+    /// 摘要算法，如 SHA1、SHA256。
+    pub hash_type: String,
+    /// 摘要值，十六进制编码。
+    pub hash_value: String,
+}
+
+enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn new(hash_type: &str) -> Result<Hasher> {
+        match hash_type.to_ascii_uppercase().as_str() {
+            "SHA1" => Ok(Hasher::Sha1(Sha1::new())),
+            "SHA256" => Ok(Hasher::Sha256(Sha256::new())),
+            _ => Err(WechatPayError::InvalidParams(format!(
+                "unsupported hash type: {}",
+                hash_type
+            ))),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        let digest = match self {
+            Hasher::Sha1(h) => h.finalize().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+        };
+        hex_encode(&digest)
+    }
+}
+
+impl WechatPayClient {
+    /// 申请交易账单，返回账单的下载地址及摘要。
+    /// bill_date 格式为 yyyy-MM-DD，仅支持三个月内的账单。
+    /// bill_type 为 ALL(默认)、SUCCESS 或 REFUND。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_4_6.shtml>
+    pub async fn get_trade_bill(
+        &self,
+        bill_date: &str,
+        bill_type: Option<&str>,
+    ) -> Result<BillDownloadInfo> {
+        let mut url = format!("{}/bill/tradebill?bill_date={}", self.base_url, bill_date);
+        if let Some(bill_type) = bill_type {
+            url = format!("{}&bill_type={}", url, bill_type);
+        }
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 申请资金账单，返回账单的下载地址及摘要。
+    /// account_type 为 BASIC(基本账户，默认)、OPERATION(运营账户) 或 FEES(手续费账户)。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_4_7.shtml>
+    pub async fn get_fund_flow_bill(
+        &self,
+        bill_date: &str,
+        account_type: Option<&str>,
+    ) -> Result<BillDownloadInfo> {
+        let mut url = format!(
+            "{}/bill/fundflowbill?bill_date={}",
+            self.base_url, bill_date
+        );
+        if let Some(account_type) = account_type {
+            url = format!("{}&account_type={}", url, account_type);
+        }
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 下载账单，将账单内容写入 writer，返回写入的字节数。下载完成后校验账单摘要。
+    /// 账单未压缩，为 csv 格式的文本。
+    pub async fn download_bill<W>(&self, bill: &BillDownloadInfo, writer: &mut W) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.verified_download(&bill.download_url, &bill.file_hash(), writer)
+            .await
+    }
+
+    /// 下载文件，将内容写入 writer，返回写入的字节数。下载完成后校验文件摘要，不一致时返回 `WechatPayError::Verify`。
+    /// 适用于账单、电子回单等返回 download_url 及 hash_type/hash_value 的接口。
+    /// 注意：校验失败时，内容已写入 writer，调用方需自行丢弃。
+    pub async fn verified_download<W>(
+        &self,
+        url: &str,
+        expect_hash: &FileHash,
+        writer: &mut W,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.download_to(url, Some(expect_hash), writer).await
+    }
+
+    /// 下载文件，并将内容写入 writer，返回写入的字节数。
+    /// 下载接口的响应不带签名，因此不做验签；如指定了 expect_hash，则在下载完成后校验摘要。
+    pub(crate) async fn download_to<W>(
+        &self,
+        url: &str,
+        expect_hash: Option<&FileHash>,
+        writer: &mut W,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut hasher = match expect_hash {
+            Some(hash) => Some(Hasher::new(&hash.hash_type)?),
+            None => None,
+        };
+
+        let req = self.client.get(url).build()?;
+        let mut res = self.execute_download(req).await?;
+
+        let mut n = 0;
+        while let Some(chunk) = self.read_chunk(&mut res).await? {
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(&chunk);
+            }
+            writer.write_all(&chunk).await?;
+            n += chunk.len() as u64;
+        }
+        writer.flush().await?;
+
+        if let (Some(hasher), Some(expect_hash)) = (hasher, expect_hash) {
+            let actual = hasher.finalize_hex();
+            if !actual.eq_ignore_ascii_case(&expect_hash.hash_value) {
+                return Err(WechatPayError::Verify(format!(
+                    "hash mismatch, expected: {}, actual: {}",
+                    expect_hash.hash_value, actual
+                )));
+            }
+        }
+        Ok(n)
+    }
+
+    /// 读取响应的下一块数据。如配置了读超时，超时将返回 `WechatPayError::Timeout`。
+    async fn read_chunk(&self, res: &mut Response) -> Result<Option<Bytes>> {
+        match self.read_timeout {
+            Some(read_timeout) => tokio::time::timeout(read_timeout, res.chunk())
+                .await
+                .map_err(|_| WechatPayError::Timeout(format!("no data after {:?}", read_timeout)))?
+                .map_err(WechatPayError::from),
+            None => Ok(res.chunk().await?),
+        }
+    }
+}
+
+/// 账单下载信息，由申请交易账单、申请资金账单接口返回。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BillDownloadInfo {
+    /// 摘要算法，固定为 SHA1
+    pub hash_type: String,
+    /// 账单文件的摘要值
+    pub hash_value: String,
+    /// 账单下载地址，5 分钟内有效
+    pub download_url: String,
+}
+
+impl BillDownloadInfo {
+    /// 账单文件的摘要
+    pub fn file_hash(&self) -> FileHash {
+        FileHash {
+            hash_type: self.hash_type.clone(),
+            hash_value: self.hash_value.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{mock_client, mock_client_with};
+    use reqwest::{Method, StatusCode};
+
+    #[tokio::test]
+    async fn test_download_bill() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+
+        let content = "交易时间,公众账号ID,商户号\n";
+        let mut hasher = Sha1::new();
+        hasher.update(content.as_bytes());
+        let hash_value = hex_encode(&hasher.finalize());
+        mock.on(
+            Method::GET,
+            "/v3/bill/tradebill",
+            StatusCode::OK,
+            &serde_json::json!({
+                "hash_type": "SHA1",
+                "hash_value": hash_value,
+                "download_url": "https://api.mch.weixin.qq.com/v3/billdownload/file?token=xxx",
+            })
+            .to_string(),
+        );
+        mock.on(
+            Method::GET,
+            "/v3/billdownload/file",
+            StatusCode::OK,
+            content,
+        );
+
+        let bill = client.get_trade_bill("2019-06-11", Some("SUCCESS")).await?;
+        let mut buf = vec![];
+        let n = client.download_bill(&bill, &mut buf).await?;
+        assert_eq!(n, content.len() as u64);
+        assert_eq!(buf, content.as_bytes());
+        assert_eq!(
+            mock.requests()[0].path,
+            "/v3/bill/tradebill?bill_date=2019-06-11&bill_type=SUCCESS"
+        );
+
+        let bill = BillDownloadInfo {
+            hash_value: "0".repeat(40),
+            ..bill
+        };
+        let err = client.download_bill(&bill, &mut vec![]).await;
+        assert!(matches!(err, Err(WechatPayError::Verify(_))));
+
+        let mut hasher = Sha256::new();
+        hasher.update(content.as_bytes());
+        let hash = FileHash {
+            hash_type: "sha256".to_string(),
+            hash_value: hex_encode(&hasher.finalize()),
+        };
+        let mut buf = vec![];
+        client
+            .verified_download(&bill.download_url, &hash, &mut buf)
+            .await?;
+        assert_eq!(buf, content.as_bytes());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_observed() -> anyhow::Result<()> {
+        use crate::audit::{CallRecord, CallRecorder};
+        use crate::interceptor::Interceptor;
+        use reqwest::{Request, Url};
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<CallRecord>>>);
+
+        impl CallRecorder for Recorder {
+            fn record(&self, record: &CallRecord) {
+                self.0.lock().unwrap().push(record.clone());
+            }
+        }
+
+        #[derive(Clone, Default)]
+        struct Counter(Arc<Mutex<(usize, usize)>>);
+
+        impl Interceptor for Counter {
+            fn before_send(&self, _req: &mut Request) -> Result<()> {
+                self.0.lock().unwrap().0 += 1;
+                Ok(())
+            }
+
+            fn after_receive(&self, _method: &Method, _url: &Url, res: &Result<Response>) {
+                assert!(res.is_ok());
+                self.0.lock().unwrap().1 += 1;
+            }
+        }
+
+        let recorder = Recorder::default();
+        let counter = Counter::default();
+        let (client, mock) = mock_client_with(|builder| {
+            builder
+                .call_recorder(recorder.clone())
+                .interceptor(counter.clone());
+        })
+        .await?;
+        let content = "交易时间,公众账号ID,商户号\n";
+        mock.on(
+            Method::GET,
+            "/v3/billdownload/file",
+            StatusCode::OK,
+            content,
+        );
+
+        let mut hasher = Sha1::new();
+        hasher.update(content.as_bytes());
+        let hash = FileHash {
+            hash_type: "SHA1".to_string(),
+            hash_value: hex_encode(&hasher.finalize()),
+        };
+        let url = "https://api.mch.weixin.qq.com/v3/billdownload/file?token=xxx";
+        let mut buf = vec![];
+        client.verified_download(url, &hash, &mut buf).await?;
+        assert_eq!(buf, content.as_bytes());
+
+        assert_eq!(*counter.0.lock().unwrap(), (1, 1));
+        let records = recorder.0.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].status, Some(StatusCode::OK));
+        assert!(records[0].response_body.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,493 @@
+//! 电商收付通。
+//! 电商平台作为服务商，为入驻的二级商户(sub_mchid)处理收款、补差、分账等资金操作。
+//! 电商分账的接口与字段均与直连商户的分账不同，这里单独建模。
+
+use crate::client::WechatPayClient;
+use crate::error::Result;
+use crate::money::Fen;
+use crate::sensitive::EncryptedString;
+use crate::util::option_datetime_fmt;
+use chrono::{DateTime, Local};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+impl WechatPayClient {
+    /// 请求补差。电商平台对二级商户的订单进行营销补贴，须在分账前调用。
+    pub async fn create_subsidy(&self, params: &SubsidyCreateParams) -> Result<SubsidyResponse> {
+        let url = format!("{}/ecommerce/subsidies/create", self.base_url);
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 请求补差回退。订单发生退款时，电商平台可将补差金额从二级商户回退。
+    pub async fn return_subsidy(
+        &self,
+        params: &SubsidyReturnParams,
+    ) -> Result<SubsidyReturnResponse> {
+        let url = format!("{}/ecommerce/subsidies/return", self.base_url);
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 查询补差结果。
+    pub async fn query_subsidy(
+        &self,
+        sub_mchid: &str,
+        out_subsidy_no: &str,
+    ) -> Result<SubsidyResponse> {
+        let url = format!(
+            "{}/ecommerce/subsidies/out-subsidy-no/{}?sub_mchid={}",
+            self.base_url, out_subsidy_no, sub_mchid
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 添加分账接收方。接收方名称会被自动加密。
+    pub async fn add_ecommerce_profit_sharing_receiver(
+        &self,
+        params: &EcommerceReceiverAddParams,
+    ) -> Result<EcommerceReceiver> {
+        let url = format!("{}/ecommerce/profitsharing/receivers/add", self.base_url);
+        let req = self.json_request(Method::POST, &url, params)?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 删除分账接收方。
+    pub async fn delete_ecommerce_profit_sharing_receiver(
+        &self,
+        params: &EcommerceReceiverDeleteParams,
+    ) -> Result<EcommerceReceiver> {
+        let url = format!("{}/ecommerce/profitsharing/receivers/delete", self.base_url);
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 请求分账。分账为异步处理，可通过 `query_ecommerce_profit_sharing` 查询结果。
+    /// 接收方名称会被自动加密。
+    pub async fn ecommerce_profit_sharing(
+        &self,
+        params: &EcommerceProfitSharingParams,
+    ) -> Result<EcommerceProfitSharingOrder> {
+        let url = format!("{}/ecommerce/profitsharing/orders", self.base_url);
+        let req = self.json_request(Method::POST, &url, params)?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 查询分账结果。
+    pub async fn query_ecommerce_profit_sharing(
+        &self,
+        sub_mchid: &str,
+        transaction_id: &str,
+        out_order_no: &str,
+    ) -> Result<EcommerceProfitSharingOrder> {
+        let url = format!(
+            "{}/ecommerce/profitsharing/orders?sub_mchid={}&transaction_id={}&out_order_no={}",
+            self.base_url, sub_mchid, transaction_id, out_order_no
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 完结分账。不再分账时调用，订单剩余的待分账金额解冻给二级商户。
+    pub async fn finish_ecommerce_profit_sharing(
+        &self,
+        params: &EcommerceFinishProfitSharingParams,
+    ) -> Result<EcommerceFinishProfitSharingResponse> {
+        let url = format!("{}/ecommerce/profitsharing/finish-order", self.base_url);
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+}
+
+/// 请求补差的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubsidyCreateParams {
+    /// 二级商户号
+    pub sub_mchid: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 补差金额，单位为分，不能超过下单时的补差金额
+    pub amount: Fen,
+    /// 补差描述
+    pub description: String,
+    /// 商户补差单号，二级商户下唯一
+    pub out_subsidy_no: String,
+    /// 微信支付退款单号。订单已退款时，须传入退款单号，补差金额不能超过退款后剩余的可补差金额。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub refund_id: Option<String>,
+}
+
+/// 补差结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SubsidyResponse {
+    /// 二级商户号
+    pub sub_mchid: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 微信补差单号
+    pub subsidy_id: String,
+    /// 补差描述
+    pub description: String,
+    /// 补差金额，单位为分
+    pub amount: Fen,
+    /// 补差结果
+    /// * SUCCESS：补差成功
+    /// * FAIL：补差失败
+    pub result: String,
+    /// 补差完成时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub success_time: Option<DateTime<Local>>,
+}
+
+/// 请求补差回退的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubsidyReturnParams {
+    /// 二级商户号
+    pub sub_mchid: String,
+    /// 商户补差回退单号，二级商户下唯一
+    pub out_order_no: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 微信支付退款单号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub refund_id: Option<String>,
+    /// 补差回退金额，单位为分，不能超过补差金额
+    pub amount: Fen,
+    /// 补差回退描述
+    pub description: String,
+}
+
+/// 补差回退结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SubsidyReturnResponse {
+    /// 二级商户号
+    pub sub_mchid: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 微信补差回退单号
+    pub subsidy_refund_id: String,
+    /// 微信支付退款单号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub refund_id: Option<String>,
+    /// 商户补差回退单号
+    pub out_order_no: String,
+    /// 补差回退金额，单位为分
+    pub amount: Fen,
+    /// 补差回退描述
+    pub description: String,
+    /// 补差回退结果
+    /// * SUCCESS：回退成功
+    /// * FAIL：回退失败
+    pub result: String,
+    /// 补差回退完成时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub success_time: Option<DateTime<Local>>,
+}
+
+/// 添加分账接收方的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EcommerceReceiverAddParams {
+    /// 电商平台的应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 接收方类型
+    /// * MERCHANT_ID：商户号
+    /// * PERSONAL_OPENID：个人 openid
+    #[serde(rename = "type")]
+    pub receiver_type: String,
+    /// 接收方账号
+    pub account: String,
+    /// 接收方名称。接收方类型为 MERCHANT_ID 时必填，为商户全称。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub encrypted_name: Option<EncryptedString>,
+    /// 与分账方的关系类型，如 SUPPLIER(供应商)、DISTRIBUTOR(分销商)、SERVICE_PROVIDER(服务商)等
+    pub relation_type: String,
+}
+
+/// 删除分账接收方的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EcommerceReceiverDeleteParams {
+    /// 电商平台的应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 接收方类型，取值同 `EcommerceReceiverAddParams::receiver_type`
+    #[serde(rename = "type")]
+    pub receiver_type: String,
+    /// 接收方账号
+    pub account: String,
+}
+
+/// 分账接收方
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct EcommerceReceiver {
+    /// 接收方类型
+    #[serde(rename = "type")]
+    pub receiver_type: String,
+    /// 接收方账号
+    pub account: String,
+}
+
+/// 请求分账的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EcommerceProfitSharingParams {
+    /// 电商平台的应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 二级商户号
+    pub sub_mchid: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 商户分账单号，电商平台下唯一
+    pub out_order_no: String,
+    /// 分账接收方列表
+    pub receivers: Vec<EcommerceProfitSharingReceiver>,
+    /// 是否分账完成。为 true 时，分账后订单剩余的待分账金额解冻给二级商户。
+    pub finish: bool,
+}
+
+/// 请求分账时的分账接收方
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EcommerceProfitSharingReceiver {
+    /// 接收方类型，取值同 `EcommerceReceiverAddParams::receiver_type`
+    #[serde(rename = "type")]
+    pub receiver_type: String,
+    /// 接收方账号
+    pub receiver_account: String,
+    /// 接收方名称。接收方类型为 MERCHANT_ID 时可传入，以校验账号与名称是否匹配。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub receiver_name: Option<EncryptedString>,
+    /// 分账金额，单位为分
+    pub amount: Fen,
+    /// 分账描述
+    pub description: String,
+}
+
+/// 分账单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct EcommerceProfitSharingOrder {
+    /// 二级商户号
+    pub sub_mchid: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 商户分账单号
+    pub out_order_no: String,
+    /// 微信分账单号
+    pub order_id: String,
+    /// 分账单状态
+    /// * PROCESSING：处理中
+    /// * FINISHED：处理完成
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub status: Option<String>,
+    /// 分账结果
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub receivers: Vec<EcommerceProfitSharingResult>,
+}
+
+/// 分账接收方的分账结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct EcommerceProfitSharingResult {
+    /// 接收方类型
+    #[serde(rename = "type")]
+    pub receiver_type: String,
+    /// 接收方账号
+    pub receiver_account: String,
+    /// 分账金额，单位为分
+    pub amount: Fen,
+    /// 分账描述
+    pub description: String,
+    /// 分账结果
+    /// * PENDING：待分账
+    /// * SUCCESS：分账成功
+    /// * CLOSED：已关闭
+    pub result: String,
+    /// 分账失败原因，分账结果为 CLOSED 时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fail_reason: Option<String>,
+    /// 分账完成时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub finish_time: Option<DateTime<Local>>,
+}
+
+/// 完结分账的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EcommerceFinishProfitSharingParams {
+    /// 二级商户号
+    pub sub_mchid: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 商户分账单号
+    pub out_order_no: String,
+    /// 分账描述
+    pub description: String,
+}
+
+/// 完结分账的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct EcommerceFinishProfitSharingResponse {
+    /// 二级商户号
+    pub sub_mchid: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 商户分账单号
+    pub out_order_no: String,
+    /// 微信分账单号
+    pub order_id: String,
+}
+
+/// 二级商户进件状态变化通知解密后的资源数据。对应 event_type: APPLYMENT_STATE.CHANGE
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ApplymentStateNotification {
+    /// 微信支付申请单号
+    pub applyment_id: u64,
+    /// 业务申请编号
+    pub out_request_no: String,
+    /// 申请状态
+    /// * CHECKING：资料校验中
+    /// * ACCOUNT_NEED_VERIFY：待账户验证
+    /// * AUDITING：审核中
+    /// * REJECTED：已驳回
+    /// * NEED_SIGN：待签约
+    /// * FINISH：完成
+    /// * FROZEN：已冻结
+    /// * CANCELED：已作废
+    pub applyment_state: String,
+    /// 申请状态描述
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub applyment_state_desc: Option<String>,
+    /// 二级商户号，进件完成后返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sub_mchid: Option<String>,
+    /// 签约链接，申请状态为 NEED_SIGN 时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sign_url: Option<String>,
+    /// 驳回原因，申请状态为 REJECTED 时返回
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub audit_detail: Vec<ApplymentAuditDetail>,
+}
+
+/// 进件的驳回原因
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ApplymentAuditDetail {
+    /// 被驳回的参数名称
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub param_name: Option<String>,
+    /// 驳回原因
+    pub reject_reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock_client;
+    use reqwest::{Method, StatusCode};
+
+    #[tokio::test]
+    async fn test_create_subsidy() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::POST,
+            "/v3/ecommerce/subsidies/create",
+            StatusCode::OK,
+            r#"{
+                "sub_mchid": "1900000109",
+                "transaction_id": "4208450740201411110007820472",
+                "subsidy_id": "3008450740201411110007820472",
+                "description": "测试备注",
+                "amount": 10,
+                "result": "SUCCESS",
+                "success_time": "2015-05-20T13:29:35+08:00"
+            }"#,
+        );
+
+        let res = client
+            .create_subsidy(&SubsidyCreateParams {
+                sub_mchid: "1900000109".to_string(),
+                transaction_id: "4208450740201411110007820472".to_string(),
+                amount: Fen(10),
+                description: "测试备注".to_string(),
+                out_subsidy_no: "P20150806125346".to_string(),
+                refund_id: None,
+            })
+            .await?;
+        assert_eq!(res.subsidy_id, "3008450740201411110007820472");
+        assert_eq!(res.amount, Fen(10));
+        let body: serde_json::Value = serde_json::from_slice(&mock.requests()[0].body)?;
+        assert_eq!(body["out_subsidy_no"], "P20150806125346");
+        assert!(body.get("refund_id").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ecommerce_profit_sharing_encrypts_receiver_name() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::POST,
+            "/v3/ecommerce/profitsharing/orders",
+            StatusCode::OK,
+            r#"{
+                "sub_mchid": "1900000109",
+                "transaction_id": "4208450740201411110007820472",
+                "out_order_no": "P20150806125346",
+                "order_id": "3008450740201411110007820472",
+                "status": "PROCESSING",
+                "receivers": [{
+                    "type": "MERCHANT_ID",
+                    "receiver_account": "1900000110",
+                    "amount": 100,
+                    "description": "分给商户1900000110",
+                    "result": "PENDING"
+                }]
+            }"#,
+        );
+
+        let res = client
+            .ecommerce_profit_sharing(&EcommerceProfitSharingParams {
+                app_id: "wx8888888888888888".to_string(),
+                sub_mchid: "1900000109".to_string(),
+                transaction_id: "4208450740201411110007820472".to_string(),
+                out_order_no: "P20150806125346".to_string(),
+                receivers: vec![EcommerceProfitSharingReceiver {
+                    receiver_type: "MERCHANT_ID".to_string(),
+                    receiver_account: "1900000110".to_string(),
+                    receiver_name: Some("示例商户全称".into()),
+                    amount: Fen(100),
+                    description: "分给商户1900000110".to_string(),
+                }],
+                finish: true,
+            })
+            .await?;
+        assert_eq!(res.receivers[0].result, "PENDING");
+
+        let body: serde_json::Value = serde_json::from_slice(&mock.requests()[0].body)?;
+        let receiver_name = body["receivers"][0]["receiver_name"].as_str().unwrap();
+        assert_ne!(receiver_name, "示例商户全称");
+        Ok(())
+    }
+}
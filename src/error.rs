@@ -1,8 +1,92 @@
+use reqwest::header::HeaderMap;
+use reqwest::{Response, StatusCode};
 use serde::{Deserialize, Serialize};
 
+/// 本 crate 的错误类型。
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum WechatPayError {
+    /// 请求签名失败
+    #[error("签名失败: {0}")]
+    Sign(String),
+    /// 响应或通知验签失败，包括缺少验签所需的 header
+    #[error("验签失败: {0}")]
+    Verify(String),
+    /// 解密失败
+    #[error("解密失败: {0}")]
+    Decrypt(String),
+    /// 平台证书相关错误，如证书解析失败、找不到对应的证书等
+    #[error("平台证书错误: {0}")]
+    Certificate(String),
+    /// 参数错误，在发送请求前的本地校验中发现
+    #[error("参数错误: {0}")]
+    InvalidParams(String),
+    /// HTTP 请求错误
+    #[error("HTTP 错误: {0}")]
+    Http(#[from] reqwest::Error),
+    /// 触发客户端限流，参见 `rate_limit`
+    #[error("请求过于频繁: {0}")]
+    RateLimited(String),
+    /// 等待响应超时(读超时)
+    #[error("请求超时: {0}")]
+    Timeout(String),
+    /// 微信支付 API 返回的业务错误
+    #[error(transparent)]
+    Api(Box<WechatPayApiError>),
+    /// JSON 序列化/反序列化错误
+    #[error("JSON 序列化/反序列化错误: {0}")]
+    Json(#[from] serde_json::Error),
+    /// IO 错误
+    #[error("IO 错误: {0}")]
+    Io(#[from] std::io::Error),
+    /// 通知的 Wechatpay-Timestamp 与本地时间相差过大，可能是重放攻击。
+    /// 参见 `WechatPayClientBuilder::notification_max_age`。
+    #[error("时间戳过期: {0}")]
+    TimestampExpired(String),
+    /// 重复的通知，参见 `dedup`。值为通知 id。
+    /// 此前已成功处理，应答成功即可。
+    #[error("重复的通知: {0}")]
+    DuplicateNotification(String),
+    /// 响应体超过上限，参见 `WechatPayClientBuilder::max_response_body_size`
+    #[error("响应体过大: {0}")]
+    ResponseTooLarge(String),
+    /// 其他错误
+    #[error("{0}")]
+    Other(String),
+}
+
+pub type Result<T, E = WechatPayError> = std::result::Result<T, E>;
+
+impl From<WechatPayApiError> for WechatPayError {
+    fn from(e: WechatPayApiError) -> Self {
+        WechatPayError::Api(Box::new(e))
+    }
+}
+
+impl WechatPayError {
+    /// 若为微信支付 API 返回的业务错误，则返回之。
+    pub fn api_error(&self) -> Option<&WechatPayApiError> {
+        match self {
+            WechatPayError::Api(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// 是否可以重试。
+    /// 网络超时、连接失败，以及微信支付返回的系统错误、频率限制等，可以稍后重试。
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            WechatPayError::Http(e) => e.is_timeout() || e.is_connect(),
+            WechatPayError::Timeout(_) | WechatPayError::RateLimited(_) => true,
+            WechatPayError::Api(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, thiserror::Error)]
 #[serde(default)]
-#[error("微信支付错误: {message}")]
+#[error("微信支付错误: {message}, code: {code}, request_id: {}", request_id.as_deref().unwrap_or("-"))]
 pub struct WechatPayApiError {
     /// 错误码
     code: String,
@@ -10,9 +94,109 @@ pub struct WechatPayApiError {
     message: String,
     /// 错误详情
     detail: WechatPayErrorDetail,
+
+    /// 响应的 Request-ID。向微信支付客服反馈问题时需要提供。
+    #[serde(skip)]
+    request_id: Option<String>,
+    /// 响应的 HTTP 状态码
+    #[serde(skip)]
+    status: Option<StatusCode>,
+    /// 响应头
+    #[serde(skip)]
+    headers: HeaderMap,
+}
+
+impl WechatPayApiError {
+    /// 以错误码与错误描述构造错误，用于非 JSON 格式的响应(如 APIv2 接口)。
+    #[cfg(feature = "v2")]
+    pub(crate) fn new(code: &str, message: &str) -> WechatPayApiError {
+        WechatPayApiError {
+            code: code.to_string(),
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// 从失败的响应中构造错误，保留 Request-ID、HTTP 状态码与响应头。
+    /// 若响应体不是合法的错误 JSON(如网关返回的 HTML)，则以响应体原文作为错误描述。
+    pub(crate) async fn from_response(res: Response) -> WechatPayError {
+        let status = res.status();
+        let headers = res.headers().clone();
+        match res.bytes().await {
+            Ok(body) => WechatPayApiError::from_parts(status, headers, &body),
+            Err(e) => e.into(),
+        }
+    }
+
+    /// 从失败响应的状态码、响应头及响应体构造错误，参见 `from_response`。
+    pub(crate) fn from_parts(
+        status: StatusCode,
+        headers: HeaderMap,
+        body: &[u8],
+    ) -> WechatPayError {
+        let mut e = serde_json::from_slice::<WechatPayApiError>(body).unwrap_or_else(|_| {
+            WechatPayApiError {
+                message: String::from_utf8_lossy(body).into_owned(),
+                ..Default::default()
+            }
+        });
+        e.request_id = headers
+            .get("Request-ID")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        e.status = Some(status);
+        e.headers = headers;
+        e.into()
+    }
+
+    /// 响应的 Request-ID
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// 响应的 HTTP 状态码
+    pub fn status(&self) -> Option<StatusCode> {
+        self.status
+    }
+
+    /// 响应头
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// 错误码原文
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// 错误码
+    pub fn error_code(&self) -> ErrorCode {
+        ErrorCode::from(self.code.as_str())
+    }
+
+    /// 错误描述
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// 错误详情
+    pub fn detail(&self) -> &WechatPayErrorDetail {
+        &self.detail
+    }
+
+    /// 是否可以重试。参见 `ErrorCode::is_retryable`。
+    pub fn is_retryable(&self) -> bool {
+        self.error_code().is_retryable()
+    }
+
+    /// 是否为用户侧的支付错误。参见 `ErrorCode::is_user_pay_error`。
+    pub fn is_user_pay_error(&self) -> bool {
+        self.error_code().is_user_pay_error()
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[non_exhaustive]
 #[serde(default)]
 pub struct WechatPayErrorDetail {
     /// 指示错误参数的位置
@@ -24,3 +208,158 @@ pub struct WechatPayErrorDetail {
     /// 出错的位置
     pub location: String,
 }
+
+/// 微信支付 API 的错误码。
+/// 参见各接口文档中的错误码列表，如 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_1.shtml>
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// 系统错误
+    SystemError,
+    /// 频率超限
+    FrequencyLimited,
+    /// 银行系统异常
+    BankError,
+    /// 参数错误
+    ParamError,
+    /// 请求参数不符合参数格式
+    InvalidRequest,
+    /// 签名错误
+    SignError,
+    /// 商户号不存在
+    MchNotExists,
+    /// AppID 和 mch_id 不匹配
+    AppidMchidNotMatch,
+    /// 商户无权限
+    NoAuth,
+    /// 业务规则限制
+    RuleLimit,
+    /// 交易错误
+    TradeError,
+    /// 订单不存在
+    OrderNotExist,
+    /// 订单已关闭
+    OrderClosed,
+    /// 订单号重复
+    OutTradeNoUsed,
+    /// 订单已支付
+    OrderPaid,
+    /// 账号异常
+    AccountError,
+    /// 余额不足
+    NotEnough,
+    /// 用户支付中，需要输入密码
+    UserPaying,
+    /// 查询的资源不存在
+    ResourceNotExists,
+    /// 资源已存在
+    ResourceAlreadyExists,
+    /// openid 和 appid 不匹配
+    OpenidMismatch,
+    /// 用户账户异常
+    UserAccountAbnormal,
+    /// 其他错误码，保留原文
+    Other(String),
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ErrorCode::SystemError => "SYSTEM_ERROR",
+            ErrorCode::FrequencyLimited => "FREQUENCY_LIMITED",
+            ErrorCode::BankError => "BANK_ERROR",
+            ErrorCode::ParamError => "PARAM_ERROR",
+            ErrorCode::InvalidRequest => "INVALID_REQUEST",
+            ErrorCode::SignError => "SIGN_ERROR",
+            ErrorCode::MchNotExists => "MCH_NOT_EXISTS",
+            ErrorCode::AppidMchidNotMatch => "APPID_MCHID_NOT_MATCH",
+            ErrorCode::NoAuth => "NO_AUTH",
+            ErrorCode::RuleLimit => "RULE_LIMIT",
+            ErrorCode::TradeError => "TRADE_ERROR",
+            ErrorCode::OrderNotExist => "ORDER_NOT_EXIST",
+            ErrorCode::OrderClosed => "ORDER_CLOSED",
+            ErrorCode::OutTradeNoUsed => "OUT_TRADE_NO_USED",
+            ErrorCode::OrderPaid => "ORDERPAID",
+            ErrorCode::AccountError => "ACCOUNT_ERROR",
+            ErrorCode::NotEnough => "NOT_ENOUGH",
+            ErrorCode::UserPaying => "USERPAYING",
+            ErrorCode::ResourceNotExists => "RESOURCE_NOT_EXISTS",
+            ErrorCode::ResourceAlreadyExists => "RESOURCE_ALREADY_EXISTS",
+            ErrorCode::OpenidMismatch => "OPENID_MISMATCH",
+            ErrorCode::UserAccountAbnormal => "USER_ACCOUNT_ABNORMAL",
+            ErrorCode::Other(s) => s,
+        }
+    }
+
+    /// 是否可以重试。
+    /// 系统错误、频率限制、银行系统异常均为暂时性错误，可以稍后使用相同参数重试。
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::SystemError | ErrorCode::FrequencyLimited | ErrorCode::BankError
+        )
+    }
+
+    /// 是否为用户侧的支付错误。
+    /// 如余额不足、账号异常等，需要提示用户更换支付方式或处理后再支付，重试无效。
+    pub fn is_user_pay_error(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::NotEnough
+                | ErrorCode::AccountError
+                | ErrorCode::UserPaying
+                | ErrorCode::UserAccountAbnormal
+                | ErrorCode::OpenidMismatch
+        )
+    }
+}
+
+impl From<&str> for ErrorCode {
+    fn from(s: &str) -> Self {
+        match s {
+            "SYSTEM_ERROR" | "SYSTEMERROR" => ErrorCode::SystemError,
+            "FREQUENCY_LIMITED" => ErrorCode::FrequencyLimited,
+            "BANK_ERROR" => ErrorCode::BankError,
+            "PARAM_ERROR" => ErrorCode::ParamError,
+            "INVALID_REQUEST" => ErrorCode::InvalidRequest,
+            "SIGN_ERROR" => ErrorCode::SignError,
+            "MCH_NOT_EXISTS" => ErrorCode::MchNotExists,
+            "APPID_MCHID_NOT_MATCH" => ErrorCode::AppidMchidNotMatch,
+            "NO_AUTH" => ErrorCode::NoAuth,
+            "RULE_LIMIT" => ErrorCode::RuleLimit,
+            "TRADE_ERROR" => ErrorCode::TradeError,
+            "ORDER_NOT_EXIST" | "ORDERNOTEXIST" => ErrorCode::OrderNotExist,
+            "ORDER_CLOSED" | "ORDERCLOSED" => ErrorCode::OrderClosed,
+            "OUT_TRADE_NO_USED" => ErrorCode::OutTradeNoUsed,
+            "ORDERPAID" | "ORDER_PAID" => ErrorCode::OrderPaid,
+            "ACCOUNT_ERROR" => ErrorCode::AccountError,
+            "NOT_ENOUGH" => ErrorCode::NotEnough,
+            "USERPAYING" => ErrorCode::UserPaying,
+            "RESOURCE_NOT_EXISTS" => ErrorCode::ResourceNotExists,
+            "RESOURCE_ALREADY_EXISTS" => ErrorCode::ResourceAlreadyExists,
+            "OPENID_MISMATCH" => ErrorCode::OpenidMismatch,
+            "USER_ACCOUNT_ABNORMAL" => ErrorCode::UserAccountAbnormal,
+            _ => ErrorCode::Other(s.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code() -> anyhow::Result<()> {
+        let e: WechatPayApiError =
+            serde_json::from_str(r#"{"code":"SYSTEM_ERROR","message":"系统错误"}"#)?;
+        assert_eq!(e.error_code(), ErrorCode::SystemError);
+        assert!(e.is_retryable());
+        assert!(!e.is_user_pay_error());
+
+        let code = ErrorCode::from("SOME_NEW_CODE");
+        assert_eq!(code, ErrorCode::Other("SOME_NEW_CODE".to_string()));
+        assert_eq!(code.as_str(), "SOME_NEW_CODE");
+        assert!(ErrorCode::NotEnough.is_user_pay_error());
+        Ok(())
+    }
+}
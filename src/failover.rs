@@ -0,0 +1,187 @@
+//! 双域名容灾。
+//! 微信支付提供了备用域名 api2.mch.weixin.qq.com。主域名连续请求失败达到阈值后，自动切换到备用域名；
+//! 切换一段时间后，下一个请求会再次探测主域名，成功则回切，失败则继续使用备用域名。
+//! 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/Practices/chapter1_1_4.shtml>
+
+use crate::error::{Result, WechatPayError};
+use reqwest::{Request, Url};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 容灾切换的配置
+#[derive(Debug, Clone)]
+pub struct FailoverOptions {
+    /// 备用的基础 URL，按顺序切换。须包含版本路径，如 `https://api2.mch.weixin.qq.com/v3`。
+    pub backup_base_urls: Vec<String>,
+    /// 连续失败多少次后切换到下一个域名
+    pub failure_threshold: u32,
+    /// 切换到备用域名后，间隔多久探测主域名
+    pub recover_after: Duration,
+}
+
+impl Default for FailoverOptions {
+    fn default() -> Self {
+        FailoverOptions {
+            backup_base_urls: vec!["https://api2.mch.weixin.qq.com/v3".to_string()],
+            failure_threshold: 3,
+            recover_after: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FailoverState {
+    /// 当前使用的域名
+    active: usize,
+    /// 当前域名的连续失败次数
+    failures: u32,
+    /// 最近一次切换到备用域名，或探测主域名失败的时间
+    switched_at: Instant,
+}
+
+/// 容灾切换的状态。base_urls[0] 为主域名。
+#[derive(Debug)]
+pub(crate) struct Failover {
+    base_urls: Vec<String>,
+    failure_threshold: u32,
+    recover_after: Duration,
+    state: Mutex<FailoverState>,
+}
+
+impl Failover {
+    pub(crate) fn new(base_url: &str, options: FailoverOptions) -> Failover {
+        let mut base_urls = vec![base_url.to_string()];
+        base_urls.extend(
+            options
+                .backup_base_urls
+                .into_iter()
+                .map(|url| url.trim_end_matches('/').to_string()),
+        );
+        Failover {
+            base_urls,
+            failure_threshold: options.failure_threshold.max(1),
+            recover_after: options.recover_after,
+            state: Mutex::new(FailoverState {
+                active: 0,
+                failures: 0,
+                switched_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// 选择本次请求使用的域名。使用备用域名超过 recover_after 时，选择主域名进行探测。
+    pub(crate) fn select(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        if state.active != 0 && state.switched_at.elapsed() >= self.recover_after {
+            0
+        } else {
+            state.active
+        }
+    }
+
+    /// index 之后的下一个域名
+    pub(crate) fn next(&self, index: usize) -> usize {
+        (index + 1) % self.base_urls.len()
+    }
+
+    /// 报告请求结果
+    pub(crate) fn report(&self, index: usize, success: bool) {
+        self.report_at(index, success, Instant::now());
+    }
+
+    fn report_at(&self, index: usize, success: bool, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            if index == 0 && state.active != 0 {
+                log::info!("switch back to {}", self.base_urls[0]);
+                state.active = 0;
+            }
+            if index == state.active {
+                state.failures = 0;
+            }
+        } else if index == 0 && state.active != 0 {
+            // 探测主域名失败，继续使用备用域名
+            state.switched_at = now;
+        } else if index == state.active {
+            state.failures += 1;
+            if state.failures >= self.failure_threshold {
+                let next = self.next(index);
+                log::warn!(
+                    "{} failed {} times, switch to {}",
+                    self.base_urls[index],
+                    state.failures,
+                    self.base_urls[next]
+                );
+                state.active = next;
+                state.failures = 0;
+                state.switched_at = now;
+            }
+        }
+    }
+
+    /// 将请求的域名从 base_urls[from] 替换为 base_urls[to]。不以 base_urls[from] 开头的请求(如文件下载)不做替换。
+    pub(crate) fn rewrite(&self, req: &mut Request, from: usize, to: usize) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+        let url = req.url().as_str();
+        if let Some(rest) = url.strip_prefix(&self.base_urls[from]) {
+            let url = format!("{}{}", self.base_urls[to], rest);
+            *req.url_mut() =
+                Url::parse(&url).map_err(|e| WechatPayError::InvalidParams(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failover() {
+        let failover = Failover::new(
+            "https://api.mch.weixin.qq.com/v3",
+            FailoverOptions {
+                failure_threshold: 2,
+                ..Default::default()
+            },
+        );
+        let now = Instant::now();
+        assert_eq!(failover.select(), 0);
+        failover.report_at(0, false, now);
+        assert_eq!(failover.select(), 0);
+        failover.report_at(0, true, now);
+        failover.report_at(0, false, now);
+        assert_eq!(failover.select(), 0);
+        failover.report_at(0, false, now);
+        assert_eq!(failover.select(), 1);
+
+        // 到时间后探测主域名，失败则继续使用备用域名
+        failover.state.lock().unwrap().switched_at = now - Duration::from_secs(600);
+        assert_eq!(failover.select(), 0);
+        failover.report_at(0, false, now);
+        assert_eq!(failover.select(), 1);
+
+        // 探测成功则回切
+        failover.state.lock().unwrap().switched_at = now - Duration::from_secs(600);
+        assert_eq!(failover.select(), 0);
+        failover.report_at(0, true, now);
+        assert_eq!(failover.select(), 0);
+    }
+
+    #[test]
+    fn test_rewrite() -> anyhow::Result<()> {
+        let failover = Failover::new("https://api.mch.weixin.qq.com/v3", Default::default());
+        let client = reqwest::Client::new();
+        let mut req = client
+            .get("https://api.mch.weixin.qq.com/v3/pay/transactions/id/1?mchid=2")
+            .build()?;
+        failover.rewrite(&mut req, 0, 1)?;
+        assert_eq!(
+            req.url().as_str(),
+            "https://api2.mch.weixin.qq.com/v3/pay/transactions/id/1?mchid=2"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,544 @@
+//! 电子发票相关接口。
+
+use crate::client::WechatPayClient;
+use crate::download::FileHash;
+use crate::error::Result;
+use crate::sensitive::EncryptedString;
+use crate::util::datetime_fmt;
+use chrono::{DateTime, Local};
+use reqwest::Method;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use tokio::io::AsyncWrite;
+
+impl WechatPayClient {
+    /// 获取抬头填写链接。
+    /// 商户可以引导用户打开此链接(小程序页面)填写发票抬头。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter4_8_4.shtml>
+    pub async fn get_fapiao_title_url(
+        &self,
+        params: &FapiaoTitleUrlParams,
+    ) -> Result<FapiaoTitleUrlResponse> {
+        let url = format!(
+            "{}/new-tax-control-fapiao/user-title/title-url",
+            self.base_url
+        );
+        let req = self.client.get(url).query(params).build()?;
+        let res = self.execute(req).await?;
+        let res: FapiaoTitleUrlResponse = res.json().await?;
+        Ok(res)
+    }
+
+    /// 获取用户填写的抬头。phone、email 会被自动使用商户私钥解密。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter4_8_5.shtml>
+    pub async fn query_fapiao_user_title(
+        &self,
+        fapiao_apply_id: &str,
+        scene: FapiaoScene,
+    ) -> Result<FapiaoUserTitle> {
+        let url = format!(
+            "{}/new-tax-control-fapiao/user-title?fapiao_apply_id={}&scene={}",
+            self.base_url,
+            fapiao_apply_id,
+            scene.as_str()
+        );
+        let req = self.client.get(url).build()?;
+        let res = self.execute(req).await?;
+        self.json_response(res).await
+    }
+
+    /// 创建电子发票卡券模板。
+    /// 开具的发票会以卡券形式插入用户微信卡包，需先创建卡券模板。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter4_8_2.shtml>
+    pub async fn create_fapiao_card_template(
+        &self,
+        params: &FapiaoCardTemplateParams,
+    ) -> Result<FapiaoCardTemplateResponse> {
+        let url = format!("{}/new-tax-control-fapiao/card-template", self.base_url);
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute(req).await?;
+        let res: FapiaoCardTemplateResponse = res.json().await?;
+        Ok(res)
+    }
+
+    /// 开具电子发票。
+    /// 开票为异步处理，受理成功后通过回调通知或查询接口获取开票结果。
+    /// 购买方信息中的 phone、email 会被自动加密，并设置对应的 Wechatpay-Serial header。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter4_8_6.shtml>
+    pub async fn issue_fapiao(&self, params: &IssueFapiaoParams) -> Result<()> {
+        let url = format!(
+            "{}/new-tax-control-fapiao/fapiao-applications",
+            self.base_url
+        );
+        let req = self.json_request(Method::POST, &url, params)?;
+        let _res = self.execute(req).await?;
+        Ok(())
+    }
+
+    /// 获取发票下载信息。
+    /// fapiao_id 为 None 时，返回该发票申请单下所有发票的下载信息。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter4_8_9.shtml>
+    pub async fn get_fapiao_files(
+        &self,
+        fapiao_apply_id: &str,
+        fapiao_id: Option<&str>,
+    ) -> Result<Vec<FapiaoDownloadInfo>> {
+        #[derive(Debug, Clone, Deserialize)]
+        struct GetFapiaoFilesResponse {
+            #[serde(default)]
+            fapiao_download_info_list: Vec<FapiaoDownloadInfo>,
+        }
+
+        let mut url = format!(
+            "{}/new-tax-control-fapiao/fapiao-applications/{}/fapiao-files",
+            self.base_url, fapiao_apply_id
+        );
+        if let Some(fapiao_id) = fapiao_id {
+            url = format!("{}?fapiao_id={}", url, fapiao_id);
+        }
+        let req = self.client.get(url).build()?;
+        let res = self.execute(req).await?;
+        let res: GetFapiaoFilesResponse = res.json().await?;
+        Ok(res.fapiao_download_info_list)
+    }
+
+    /// 下载发票文件(PDF 或 OFD 格式)，将文件内容写入 writer，返回写入的字节数。
+    /// download_url 来自 `get_fapiao_files` 的返回结果，其中不含文件摘要；如从其他渠道获得了摘要，
+    /// 可通过 expect_hash 指定，下载完成后校验，参见 `verified_download`。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter4_8_10.shtml>
+    pub async fn download_fapiao_file<W>(
+        &self,
+        download_url: &str,
+        expect_hash: Option<&FileHash>,
+        writer: &mut W,
+    ) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.download_to(download_url, expect_hash, writer).await
+    }
+}
+
+/// 获取抬头填写链接的参数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FapiaoTitleUrlParams {
+    /// 发票申请单号。商户系统内部的唯一标识，只能是字母、数字、中划线-、下划线_、竖线|、星号*，
+    /// 长度应在 [1, 64] 字符之间。
+    pub fapiao_apply_id: String,
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 用户在商户 app_id 下的唯一标识。
+    pub openid: String,
+    /// 总金额，单位为分。开票时的总金额，用于在抬头填写页面展示。
+    pub total_amount: i64,
+    /// 开票来源。
+    /// * WEB：微信H5开票
+    /// * MINIPROGRAM：微信小程序开票
+    pub source: String,
+    /// 销售方名称。若不传则默认取商户号关联的名称。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub seller_name: Option<String>,
+}
+
+/// 获取抬头填写链接的响应。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct FapiaoTitleUrlResponse {
+    /// 抬头填写小程序的 app_id
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub miniprogram_appid: Option<String>,
+    /// 抬头填写小程序的页面路径
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub miniprogram_path: Option<String>,
+    /// 抬头填写小程序的原始 ID
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub miniprogram_user_name: Option<String>,
+}
+
+/// 开票场景
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FapiaoScene {
+    /// 使用微信支付支付成功后开票
+    WithWechatPay,
+    /// 未使用微信支付支付成功后开票
+    WithoutWechatPay,
+    /// 其他场景，保留原文。
+    Other(String),
+}
+
+impl FapiaoScene {
+    pub fn as_str(&self) -> &str {
+        match self {
+            FapiaoScene::WithWechatPay => "WITH_WECHATPAY",
+            FapiaoScene::WithoutWechatPay => "WITHOUT_WECHATPAY",
+            FapiaoScene::Other(s) => s,
+        }
+    }
+}
+
+impl FromStr for FapiaoScene {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "WITH_WECHATPAY" => Ok(FapiaoScene::WithWechatPay),
+            "WITHOUT_WECHATPAY" => Ok(FapiaoScene::WithoutWechatPay),
+            _ => Ok(FapiaoScene::Other(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for FapiaoScene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for FapiaoScene {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+}
+
+impl Serialize for FapiaoScene {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// 用户填写的发票抬头。
+/// 作为开票时的购买方信息(`FapiaoBuyerInformation`)时，可通过 `new` 构建后再设置其他字段。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct FapiaoUserTitle {
+    /// 购买方类型。
+    /// * INDIVIDUAL：个人
+    /// * ORGANIZATION：单位
+    #[serde(rename = "type")]
+    pub buyer_type: String,
+    /// 名称
+    pub name: String,
+    /// 纳税人识别号。购买方为单位时返回。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub taxpayer_id: Option<String>,
+    /// 地址
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub address: Option<String>,
+    /// 电话
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub telephone: Option<String>,
+    /// 开户银行
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub bank_name: Option<String>,
+    /// 银行账号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub bank_account: Option<String>,
+    /// 手机号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub phone: Option<EncryptedString>,
+    /// 邮箱地址
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub email: Option<EncryptedString>,
+}
+
+impl FapiaoUserTitle {
+    /// buyer_type 为 INDIVIDUAL 或 ORGANIZATION，其他字段为空。
+    pub fn new(buyer_type: impl Into<String>, name: impl Into<String>) -> Self {
+        FapiaoUserTitle {
+            buyer_type: buyer_type.into(),
+            name: name.into(),
+            taxpayer_id: None,
+            address: None,
+            telephone: None,
+            bank_name: None,
+            bank_account: None,
+            phone: None,
+            email: None,
+        }
+    }
+}
+
+/// 创建电子发票卡券模板的参数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FapiaoCardTemplateParams {
+    /// 插卡公众号 app_id。电子发票卡券将插入此公众号对应的卡包。
+    pub card_appid: String,
+    /// 卡券模板信息
+    pub card_template_information: FapiaoCardTemplateInformation,
+}
+
+/// 卡券模板信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FapiaoCardTemplateInformation {
+    /// 收款方名称，显示在电子发票卡券信息中。若不传则默认取商户名称。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payee_name: Option<String>,
+    /// 卡券 logo 地址。需通过微信公众平台的上传图片接口获得，
+    /// 图片尺寸为 120*120，支持 PNG 和 JPG 格式。
+    pub logo_url: String,
+    /// 卡券自定义入口
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub custom_cell: Option<FapiaoCardCustomCell>,
+}
+
+/// 卡券自定义入口。
+/// jump_url 与 miniprogram_user_name、miniprogram_path 至少填写一组。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FapiaoCardCustomCell {
+    /// 自定义入口名称。不超过 5 个汉字。
+    pub words: String,
+    /// 自定义入口右侧的引导文字。不超过 6 个汉字。
+    pub description: String,
+    /// 自定义入口跳转的网页链接
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub jump_url: Option<String>,
+    /// 自定义入口跳转的小程序原始 ID
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub miniprogram_user_name: Option<String>,
+    /// 自定义入口跳转的小程序页面路径
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub miniprogram_path: Option<String>,
+}
+
+/// 创建电子发票卡券模板的响应。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct FapiaoCardTemplateResponse {
+    /// 插卡公众号 app_id
+    pub card_appid: String,
+    /// 卡券模板 ID
+    #[serde(rename = "card_id")]
+    pub card_template_id: String,
+}
+
+/// 开具电子发票的参数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueFapiaoParams {
+    /// 开票场景
+    pub scene: FapiaoScene,
+    /// 发票申请单号。商户系统内部的唯一标识，只能是字母、数字、中划线-、下划线_、竖线|、星号*，
+    /// 长度应在 [1, 64] 字符之间。
+    pub fapiao_apply_id: String,
+    /// 购买方信息。一般来自用户填写的抬头。
+    pub buyer_information: FapiaoBuyerInformation,
+    /// 需要开具的发票信息。同一申请单下可同时开具多张发票，至多 5 张。
+    pub fapiao_information: Vec<FapiaoInformation>,
+}
+
+/// 购买方信息，与用户填写的发票抬头字段一致。
+pub type FapiaoBuyerInformation = FapiaoUserTitle;
+
+/// 需要开具的发票信息。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FapiaoInformation {
+    /// 商户发票单号。同一发票申请单号下唯一，只能是字母、数字、中划线-、下划线_、竖线|、星号*，
+    /// 长度应在 [1, 20] 字符之间。
+    pub fapiao_id: String,
+    /// 总价税合计金额，单位为分。须与发票行金额之和一致。
+    pub total_amount: i64,
+    /// 是否以清单形式开具发票。
+    pub need_list: bool,
+    /// 发票备注。不超过 200 字符。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub remark: Option<String>,
+    /// 发票行信息。单张发票至多 8 行(以清单形式开具时至多 2000 行)。
+    pub items: Vec<FapiaoLineItem>,
+}
+
+/// 发票行信息。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FapiaoLineItem {
+    /// 税局侧规定的 19 位税收分类编码
+    pub tax_code: String,
+    /// 商品和服务名称。不超过 100 字符。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub goods_name: Option<String>,
+    /// 商户侧商品编码
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub goods_id: Option<String>,
+    /// 规格型号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub specification: Option<String>,
+    /// 计量单位
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub unit: Option<String>,
+    /// 数量。为实际数量乘以 10^8，如 1.5 个应填写 150000000。
+    pub quantity: i64,
+    /// 单行金额合计，单位为分。为单价乘以数量(含税)。
+    pub total_amount: i64,
+    /// 税率。为实际税率乘以 10^4，如 6% 应填写 600。
+    pub tax_rate: i32,
+    /// 税收优惠政策标识。
+    /// * NO_FAVORABLE：无优惠
+    /// * OUTSIDE_VAT：不征税
+    /// * VAT_EXEMPT：免税
+    /// * ZERO_RATE：普通零税率
+    /// * EXPORT_ZERO_RATE：出口零税率
+    pub tax_prefer_mark: String,
+    /// 是否是折扣行
+    pub discount: bool,
+}
+
+/// 发票下载信息。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct FapiaoDownloadInfo {
+    /// 商户发票单号
+    pub fapiao_id: String,
+    /// 发票文件下载地址。有效期为 60 秒。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub download_url: Option<String>,
+    /// 发票状态。
+    /// * ISSUE_ACCEPTED：开票已受理
+    /// * ISSUED：已开具
+    /// * REVERSE_ACCEPTED：冲红已受理
+    /// * REVERSED：已冲红
+    pub status: String,
+}
+
+/// 电子发票通知的解密内容。
+/// 对应 event_type:
+/// * FAPIAO.USER_APPLIED：用户提交抬头
+/// * FAPIAO.ISSUED：发票开具完成
+/// * FAPIAO.REVERSED：发票冲红完成
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct FapiaoNotification {
+    /// 商户号
+    #[serde(rename = "mchid")]
+    pub mch_id: String,
+    /// 发票申请单号
+    pub fapiao_apply_id: String,
+    /// 申请时间
+    #[serde(with = "datetime_fmt")]
+    pub apply_time: DateTime<Local>,
+    /// 发票信息。用户提交抬头的通知中不包含此字段。
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub fapiao_information: Vec<FapiaoNotificationInformation>,
+}
+
+/// 电子发票通知中的发票信息。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct FapiaoNotificationInformation {
+    /// 商户发票单号
+    pub fapiao_id: String,
+    /// 发票状态。
+    /// * ISSUED：已开具
+    /// * REVERSED：已冲红
+    pub fapiao_status: String,
+    /// 电子发票卡券状态。
+    /// * INSERT_ACCEPTED：已受理插卡
+    /// * INSERTED：已插卡
+    /// * DISCARD_ACCEPTED：已受理作废
+    /// * DISCARDED：已作废
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub card_status: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WechatPayError;
+    use crate::sensitive::rsa_oaep_encrypt;
+    use crate::transport::mock_client;
+    use reqwest::StatusCode;
+
+    #[tokio::test]
+    async fn test_fapiao_encrypts_buyer_information() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::POST,
+            "/v3/new-tax-control-fapiao/fapiao-applications",
+            StatusCode::ACCEPTED,
+            "",
+        );
+
+        let mut buyer_information = FapiaoBuyerInformation::new("INDIVIDUAL", "张三");
+        buyer_information.phone = Some("13900000000".into());
+        buyer_information.email = Some("zhangsan@example.com".into());
+        let params = IssueFapiaoParams {
+            scene: FapiaoScene::WithWechatPay,
+            fapiao_apply_id: "4200000444201910177461284488".to_string(),
+            buyer_information,
+            fapiao_information: vec![],
+        };
+        client.issue_fapiao(&params).await?;
+
+        let req = &mock.requests()[0];
+        assert_eq!(
+            req.headers.get("Wechatpay-Serial").unwrap(),
+            "PUB_KEY_ID_0000000000000001"
+        );
+        let body: serde_json::Value = serde_json::from_slice(&req.body)?;
+        let phone = body["buyer_information"]["phone"].as_str().unwrap();
+        assert!(!phone.is_empty() && phone != "13900000000");
+        assert_ne!(body["buyer_information"]["email"], "zhangsan@example.com");
+        assert_eq!(body["buyer_information"]["name"], "张三");
+
+        let public_key = client.mch_credential().mch_rsa_private_key.to_public_key();
+        let phone = rsa_oaep_encrypt(&public_key, "13900000000")?;
+        mock.on(
+            Method::GET,
+            "/v3/new-tax-control-fapiao/user-title",
+            StatusCode::OK,
+            &format!(
+                r#"{{"type":"INDIVIDUAL","name":"张三","phone":"{}"}}"#,
+                phone
+            ),
+        );
+        let title = client
+            .query_fapiao_user_title("4200000444201910177461284488", FapiaoScene::WithWechatPay)
+            .await?;
+        assert_eq!(title.phone.unwrap().into_inner(), "13900000000");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fapiao_scene() -> anyhow::Result<()> {
+        let scene: FapiaoScene = serde_json::from_str(r#""WITHOUT_WECHATPAY""#)?;
+        assert_eq!(scene, FapiaoScene::WithoutWechatPay);
+        let scene: FapiaoScene = serde_json::from_str(r#""WITH_OTHER_PAY""#)?;
+        assert_eq!(scene, FapiaoScene::Other("WITH_OTHER_PAY".to_string()));
+        assert_eq!(serde_json::to_string(&scene)?, r#""WITH_OTHER_PAY""#);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_fapiao_file() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::GET,
+            "/v3/new-tax-control-fapiao/download",
+            StatusCode::OK,
+            "%PDF-1.4",
+        );
+        let url =
+            "https://api.mch.weixin.qq.com/v3/new-tax-control-fapiao/download?mchid=1900000001";
+
+        let mut buf = vec![];
+        let n = client.download_fapiao_file(url, None, &mut buf).await?;
+        assert_eq!(n, 8);
+        assert_eq!(buf, b"%PDF-1.4");
+
+        let hash = FileHash {
+            hash_type: "SHA256".to_string(),
+            hash_value: "0".repeat(64),
+        };
+        let err = client
+            .download_fapiao_file(url, Some(&hash), &mut vec![])
+            .await;
+        assert!(matches!(err, Err(WechatPayError::Verify(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,623 @@
+//! 境外商户(Global)接口。
+//! 境外商户的接口位于 `/global` 路径下，下单时须指定交易类型 trade_type 及商户类目 merchant_category_code，
+//! 金额可使用人民币以外的币种，用户以人民币支付时按汇率换算。
+//! 境外商户通常使用香港接入点，可通过 `WechatPayClientBuilder::base_url(GLOBAL_BASE_URL)` 切换。
+
+use crate::client::WechatPayClient;
+use crate::error::{Result, WechatPayError};
+use crate::ids::{OutRefundNo, OutTradeNo};
+use crate::money::Fen;
+use crate::paginate::{Page, Paginated};
+use crate::refund::{RefundStatus, TradeId};
+use crate::trade::{
+    CreateTradeGoodsDetail, CreateTradeSceneInfo, Payer, TradePromotionDetail, TradeState,
+    TradeType,
+};
+use crate::util::{datetime_fmt, option_datetime_fmt};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// 境外商户接口的香港接入点
+pub const GLOBAL_BASE_URL: &str = "https://apihk.mch.weixin.qq.com/v3";
+
+impl WechatPayClient {
+    /// 境外商户下单。根据 params.trade_type 调用 JSAPI、Native、APP 或 H5 下单接口，
+    /// 返回结果中分别带有 prepay_id、code_url、prepay_id 或 h5_url。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/api/wxpay/v3_global/apis/chapter3_1_1.shtml>
+    pub async fn global_create_trade(
+        &self,
+        params: &GlobalCreateTradeParams,
+    ) -> Result<GlobalCreateTradeResponse> {
+        let path = match params.trade_type {
+            TradeType::JsApi => "jsapi",
+            TradeType::Native => "native",
+            TradeType::App => "app",
+            TradeType::Mweb => "mweb",
+            ref trade_type => {
+                return Err(WechatPayError::InvalidParams(format!(
+                    "unsupported trade_type for global trade: {}",
+                    trade_type
+                )))
+            }
+        };
+        let url = format!("{}/global/transactions/{}", self.base_url, path);
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 境外商户通过微信支付订单号查询订单。
+    pub async fn global_query_trade_by_transaction_id(
+        &self,
+        transaction_id: &str,
+    ) -> Result<GlobalTradeQueryResponse> {
+        let url = format!(
+            "{}/global/transactions/id/{}?mchid={}",
+            self.base_url,
+            transaction_id,
+            &self.mch_credential().mch_id
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 境外商户通过商户订单号查询订单。
+    pub async fn global_query_trade_by_out_trade_no(
+        &self,
+        out_trade_no: &str,
+    ) -> Result<GlobalTradeQueryResponse> {
+        let url = format!(
+            "{}/global/transactions/out-trade-no/{}?mchid={}",
+            self.base_url,
+            out_trade_no,
+            &self.mch_credential().mch_id
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 境外商户申请退款。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/api/wxpay/v3_global/apis/chapter3_1_6.shtml>
+    pub async fn global_apply_refund(
+        &self,
+        params: &GlobalRefundParams,
+    ) -> Result<GlobalRefundResponse> {
+        let url = format!("{}/global/refunds", self.base_url);
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 境外商户通过商户退款单号查询退款。
+    pub async fn global_query_refund(&self, out_refund_no: &str) -> Result<GlobalRefundResponse> {
+        let url = format!(
+            "{}/global/refunds/out-refund-no/{}?mchid={}",
+            self.base_url,
+            out_refund_no,
+            &self.mch_credential().mch_id
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 查询外币兑人民币的汇率。
+    /// currency 为标价币种，如 USD；date 为 yyyyMMdd 格式的日期，缺省为当日。
+    pub async fn global_query_exchange_rate(
+        &self,
+        currency: &str,
+        date: Option<&str>,
+    ) -> Result<GlobalExchangeRate> {
+        #[derive(Debug, Deserialize)]
+        struct Response {
+            exchange_rate: GlobalExchangeRate,
+        }
+
+        let mut url = format!(
+            "{}/global/rates?mchid={}&fee_type={}",
+            self.base_url,
+            &self.mch_credential().mch_id,
+            currency
+        );
+        if let Some(date) = date {
+            url = format!("{}&date={}", url, date);
+        }
+        let req = self.client.get(url).build()?;
+        let res: Response = self.execute_json(req).await?;
+        Ok(res.exchange_rate)
+    }
+
+    /// 查询结算资金，用于每日结汇对账。
+    pub async fn global_query_settlements(
+        &self,
+        query: &GlobalSettlementQuery,
+        offset: u32,
+        limit: u32,
+    ) -> Result<GlobalSettlementList> {
+        let url = format!("{}/global/settle/settlements", self.base_url);
+        let req = self
+            .client
+            .get(url)
+            .query(query)
+            .query(&[("offset", offset), ("limit", limit)])
+            .build()?;
+        self.execute_json(req).await
+    }
+
+    /// 查询结算资金，自动翻页。
+    pub fn global_query_settlements_paginated(
+        &self,
+        query: GlobalSettlementQuery,
+        limit: u32,
+    ) -> Paginated<GlobalSettlement> {
+        let client = self.clone();
+        Paginated::new(limit, move |offset, limit| {
+            let client = client.clone();
+            let query = query.clone();
+            async move {
+                client
+                    .global_query_settlements(&query, offset, limit)
+                    .await
+                    .map(Page::from)
+            }
+        })
+    }
+}
+
+/// 境外商户下单参数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalCreateTradeParams {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 商户号
+    #[serde(rename = "mchid")]
+    pub mch_id: String,
+    /// 商品描述。不超过 127 字符。
+    pub description: String,
+    /// 商户订单号
+    pub out_trade_no: OutTradeNo,
+    /// 交易结束时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub time_expire: Option<DateTime<Local>>,
+    /// 附加数据
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub attach: Option<String>,
+    /// 通知地址
+    pub notify_url: String,
+    /// 订单优惠标记
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub goods_tag: Option<String>,
+    /// 交易类型，支持 JSAPI、NATIVE、APP、MWEB
+    pub trade_type: TradeType,
+    /// 商户类目(MCC)，4 位数字，见微信支付境外商户的商户类目表
+    pub merchant_category_code: String,
+    /// 订单金额
+    pub amount: GlobalAmount,
+    /// 支付者。JSAPI 下单时必填。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer: Option<Payer>,
+    /// 优惠功能
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub detail: Option<CreateTradeGoodsDetail>,
+    /// 场景信息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scene_info: Option<CreateTradeSceneInfo>,
+}
+
+/// 境外商户的订单金额
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalAmount {
+    /// 订单总金额，单位为标价币种的最小单位(如港币为分，日元为元)。
+    pub total: Fen,
+    /// 标价币种。符合 ISO 4217 标准的三位字母代码，如 HKD、USD。
+    pub currency: String,
+}
+
+/// 境外商户下单结果。仅与 trade_type 对应的字段有值。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GlobalCreateTradeResponse {
+    /// JSAPI、APP 下单时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub prepay_id: Option<String>,
+    /// Native 下单时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub code_url: Option<String>,
+    /// H5 下单时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub h5_url: Option<String>,
+}
+
+/// 境外商户订单查询响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GlobalTradeQueryResponse {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 商户号
+    #[serde(rename = "mchid")]
+    pub mch_id: String,
+    /// 商户订单号
+    pub out_trade_no: String,
+    /// 微信支付订单号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub transaction_id: Option<String>,
+    /// 交易类型
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub trade_type: Option<TradeType>,
+    /// 交易状态
+    pub trade_state: TradeState,
+    /// 交易状态描述
+    pub trade_state_desc: String,
+    /// 付款银行
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub bank_type: Option<String>,
+    /// 附加数据
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub attach: Option<String>,
+    /// 支付完成时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub success_time: Option<DateTime<Local>>,
+    /// 支付者
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer: Option<Payer>,
+    /// 订单金额信息，当支付成功时返回该字段。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub amount: Option<GlobalPaidAmount>,
+    /// 优惠功能，享受优惠时返回该字段
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub promotion_detail: Vec<TradePromotionDetail>,
+}
+
+/// 境外商户订单的支付金额。
+/// total, currency 为标价金额；payer_total, payer_currency 为用户实际支付的金额。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GlobalPaidAmount {
+    /// 标价金额
+    pub total: Fen,
+    /// 标价币种
+    pub currency: String,
+    /// 用户支付金额
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer_total: Option<Fen>,
+    /// 用户支付币种
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer_currency: Option<String>,
+    /// 汇率信息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub exchange_rate: Option<ExchangeRate>,
+}
+
+/// 汇率
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ExchangeRate {
+    /// 汇率类型，如 SETTLEMENT_RATE
+    #[serde(rename = "type")]
+    pub rate_type: String,
+    /// 汇率值，为实际汇率乘以 10^8
+    pub rate: i64,
+}
+
+/// 外币兑人民币的汇率
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GlobalExchangeRate {
+    /// 标价币种
+    pub fee_type: String,
+    /// 汇率值，为实际汇率乘以 10^8
+    pub rate: i64,
+    /// 汇率的生效时间
+    #[serde(with = "datetime_fmt")]
+    pub rate_time: DateTime<Local>,
+}
+
+/// 查询结算资金的条件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalSettlementQuery {
+    /// 结算状态
+    /// * SETTLED：已结算
+    /// * UNSETTLED：未结算
+    pub settle_state: String,
+    /// 结算开始日期，格式为 yyyyMMdd，查询已结算的资金时必填
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub settle_start_date: Option<String>,
+    /// 结算结束日期，格式为 yyyyMMdd，查询已结算的资金时必填
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub settle_end_date: Option<String>,
+}
+
+/// 结算资金列表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GlobalSettlementList {
+    /// 总数
+    #[serde(default)]
+    pub total_count: u32,
+    #[serde(default)]
+    pub data: Vec<GlobalSettlement>,
+}
+
+impl From<GlobalSettlementList> for Page<GlobalSettlement> {
+    fn from(list: GlobalSettlementList) -> Self {
+        Page {
+            items: list.data,
+            total_count: Some(list.total_count),
+        }
+    }
+}
+
+/// 一笔结算资金
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GlobalSettlement {
+    /// 结算周期的开始日期
+    pub settle_start_date: String,
+    /// 结算周期的结束日期
+    pub settle_end_date: String,
+    /// 结算金额，单位为结算币种的最小单位
+    pub settle_amount: i64,
+    /// 结算币种
+    pub settle_currency: String,
+    /// 结算周期内的交易金额
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub transaction_amount: Option<i64>,
+    /// 结算周期内的退款金额
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub refund_amount: Option<i64>,
+    /// 结算周期内的手续费
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fee_amount: Option<i64>,
+    /// 结算时间，已结算时返回
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub settle_time: Option<DateTime<Local>>,
+}
+
+/// 境外商户申请退款的参数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalRefundParams {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 商户号
+    #[serde(rename = "mchid")]
+    pub mch_id: String,
+    /// 原订单
+    #[serde(flatten)]
+    pub trade_id: TradeId,
+    /// 商户退款单号
+    pub out_refund_no: OutRefundNo,
+    /// 退款原因
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub reason: Option<String>,
+    /// 退款结果回调 url
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub notify_url: Option<String>,
+    /// 金额信息
+    pub amount: GlobalRefundApplyingAmount,
+}
+
+/// 境外商户申请退款的金额信息。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalRefundApplyingAmount {
+    /// 退款金额，不能超过原订单金额
+    pub refund: Fen,
+    /// 原订单金额
+    pub total: Fen,
+    /// 标价币种，须与下单时一致
+    pub currency: String,
+}
+
+/// 境外商户退款结果，申请退款与查询退款均返回此结构。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GlobalRefundResponse {
+    /// 微信支付退款单号
+    #[serde(rename = "id")]
+    pub refund_id: String,
+    /// 商户退款单号
+    pub out_refund_no: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 商户订单号
+    pub out_trade_no: String,
+    /// 退款渠道，如 ORIGINAL、BALANCE
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub channel: Option<String>,
+    /// 退款入账账户
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub recv_account: Option<String>,
+    /// 退款资金来源
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fund_source: Option<String>,
+    /// 退款成功时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub success_time: Option<DateTime<Local>>,
+    /// 退款创建时间
+    #[serde(with = "datetime_fmt")]
+    pub create_time: DateTime<Local>,
+    /// 退款状态
+    pub status: RefundStatus,
+    /// 金额信息
+    pub amount: GlobalRefundActualAmount,
+}
+
+/// 境外商户实际退款的金额信息。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GlobalRefundActualAmount {
+    /// 退款标价金额
+    pub refund: Fen,
+    /// 原订单标价金额
+    pub total: Fen,
+    /// 标价币种
+    pub currency: String,
+    /// 退给用户的金额
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer_refund: Option<Fen>,
+    /// 用户支付金额
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer_total: Option<Fen>,
+    /// 用户支付币种
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer_currency: Option<String>,
+    /// 应结退款金额
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub settlement_refund: Option<Fen>,
+    /// 应结订单金额
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub settlement_total: Option<Fen>,
+    /// 汇率信息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub exchange_rate: Option<ExchangeRate>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock_client;
+    use futures::TryStreamExt;
+    use reqwest::{Method, StatusCode};
+
+    #[tokio::test]
+    async fn test_global_create_trade() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::POST,
+            "/v3/global/transactions/native",
+            StatusCode::OK,
+            r#"{"code_url":"weixin://wxpay/bizpayurl?pr=p4lpSuKzz"}"#,
+        );
+
+        let params = GlobalCreateTradeParams {
+            app_id: "wxdace645e0bc2cXXX".to_string(),
+            mch_id: "1900000001".to_string(),
+            description: "Image形象店-深圳腾大-QQ公仔".to_string(),
+            out_trade_no: OutTradeNo::new("YX201710140020Z")?,
+            time_expire: None,
+            attach: None,
+            notify_url: "https://wxpay.wxutil.com/mch/pay/notify.php".to_string(),
+            goods_tag: None,
+            trade_type: TradeType::Native,
+            merchant_category_code: "4111".to_string(),
+            amount: GlobalAmount {
+                total: Fen(1),
+                currency: "HKD".to_string(),
+            },
+            payer: None,
+            detail: None,
+            scene_info: None,
+        };
+        let res = client.global_create_trade(&params).await?;
+        assert_eq!(
+            res.code_url.as_deref(),
+            Some("weixin://wxpay/bizpayurl?pr=p4lpSuKzz")
+        );
+        let body: serde_json::Value = serde_json::from_slice(&mock.requests()[0].body)?;
+        assert_eq!(body["trade_type"], "NATIVE");
+        assert_eq!(body["merchant_category_code"], "4111");
+        assert_eq!(body["amount"]["currency"], "HKD");
+
+        let params = GlobalCreateTradeParams {
+            trade_type: TradeType::Micropay,
+            ..params
+        };
+        let err = client.global_create_trade(&params).await;
+        assert!(matches!(err, Err(WechatPayError::InvalidParams(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_global_trade_query_response() -> anyhow::Result<()> {
+        let res: GlobalTradeQueryResponse = serde_json::from_str(
+            r#"{
+                "appid": "wxdace645e0bc2cXXX",
+                "mchid": "1900000001",
+                "out_trade_no": "YX201710140020Z",
+                "transaction_id": "4200000000201710149140000000",
+                "trade_type": "NATIVE",
+                "trade_state": "SUCCESS",
+                "trade_state_desc": "支付成功",
+                "bank_type": "OTHERS",
+                "success_time": "2018-06-08T10:34:56+08:00",
+                "payer": {"openid": "oUpF8uMuAJO_M2pxb1Q9zNjWeS6o"},
+                "amount": {
+                    "total": 1,
+                    "currency": "HKD",
+                    "payer_total": 1,
+                    "payer_currency": "CNY",
+                    "exchange_rate": {"type": "SETTLEMENT_RATE", "rate": 82700000}
+                }
+            }"#,
+        )?;
+        assert_eq!(res.trade_state, TradeState::Success);
+        let amount = res.amount.unwrap();
+        assert_eq!(amount.payer_currency.as_deref(), Some("CNY"));
+        assert_eq!(amount.exchange_rate.unwrap().rate, 82700000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_global_rates_and_settlements() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::GET,
+            "/v3/global/rates",
+            StatusCode::OK,
+            r#"{"exchange_rate":{"fee_type":"USD","rate":646680000,"rate_time":"2018-06-08T10:34:56+08:00"}}"#,
+        );
+        mock.on(
+            Method::GET,
+            "/v3/global/settle/settlements",
+            StatusCode::OK,
+            r#"{"total_count":1,"data":[{"settle_start_date":"20180601","settle_end_date":"20180607","settle_amount":100000,"settle_currency":"USD","settle_time":"2018-06-08T10:34:56+08:00"}]}"#,
+        );
+
+        let rate = client
+            .global_query_exchange_rate("USD", Some("20180608"))
+            .await?;
+        assert_eq!(rate.rate, 646680000);
+        assert_eq!(
+            mock.requests()[0].path,
+            "/v3/global/rates?mchid=1900000001&fee_type=USD&date=20180608"
+        );
+
+        let settlements: Vec<GlobalSettlement> = client
+            .global_query_settlements_paginated(
+                GlobalSettlementQuery {
+                    settle_state: "SETTLED".to_string(),
+                    settle_start_date: Some("20180601".to_string()),
+                    settle_end_date: Some("20180630".to_string()),
+                },
+                10,
+            )
+            .into_stream()
+            .try_collect()
+            .await?;
+        assert_eq!(settlements.len(), 1);
+        assert_eq!(settlements[0].settle_amount, 100000);
+        assert_eq!(
+            mock.requests()[1].path,
+            "/v3/global/settle/settlements?settle_state=SETTLED&settle_start_date=20180601&settle_end_date=20180630&offset=0&limit=10"
+        );
+        Ok(())
+    }
+}
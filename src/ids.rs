@@ -0,0 +1,147 @@
+//! 商户侧单号类型。
+//! 微信支付对商户订单号、商户退款单号的长度与字符集有限制，
+//! 这里的类型在构造时即进行校验，不满足要求的单号无法被构造出来。
+
+use crate::error::{Result, WechatPayError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+macro_rules! define_id {
+    ($(#[$meta:meta])* $name:ident, $field:literal, $min:expr, $max:expr, $extra_chars:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn new(s: impl Into<String>) -> Result<$name> {
+                let s = s.into();
+                let valid_char = |c: char| c.is_ascii_alphanumeric() || $extra_chars.contains(c);
+                if s.len() < $min || s.len() > $max || !s.chars().all(valid_char) {
+                    return Err(WechatPayError::InvalidParams(format!(
+                        concat!("invalid ", $field, ": {}"),
+                        s
+                    )));
+                }
+                Ok($name(s))
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn into_string(self) -> String {
+                self.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = WechatPayError;
+
+            fn from_str(s: &str) -> Result<Self> {
+                $name::new(s)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = WechatPayError;
+
+            fn try_from(s: String) -> Result<Self> {
+                $name::new(s)
+            }
+        }
+
+        impl TryFrom<&str> for $name {
+            type Error = WechatPayError;
+
+            fn try_from(s: &str) -> Result<Self> {
+                $name::new(s)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> String {
+                id.0
+            }
+        }
+
+        impl From<&$name> for $name {
+            fn from(id: &$name) -> $name {
+                id.clone()
+            }
+        }
+    };
+}
+
+define_id!(
+    /// 商户订单号。6-32 个字符，只能是数字、大小写字母_-*
+    OutTradeNo,
+    "out_trade_no",
+    6,
+    32,
+    "_-*"
+);
+
+define_id!(
+    /// 商户退款单号。1-64 个字符，只能是数字、大小写字母_-|*@
+    OutRefundNo,
+    "out_refund_no",
+    1,
+    64,
+    "_-|*@"
+);
+
+impl OutTradeNo {
+    /// 随机生成一个商户订单号，见 `generate_out_trade_no`。
+    pub fn generate() -> OutTradeNo {
+        OutTradeNo(crate::trade::generate_out_trade_no())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_out_trade_no() {
+        assert!(OutTradeNo::new("1217752501201407033233368018").is_ok());
+        assert!(OutTradeNo::new("a_b-c*d").is_ok());
+        assert!(OutTradeNo::new("a_b-c|d").is_err());
+        assert!(OutTradeNo::new("12345").is_err());
+        assert!(OutTradeNo::new("1".repeat(33)).is_err());
+        assert!(OutTradeNo::new("order@123").is_err());
+        assert!(OutTradeNo::new("订单号123456").is_err());
+        assert!(OutTradeNo::new(OutTradeNo::generate().into_string()).is_ok());
+
+        let no: OutTradeNo = serde_json::from_str(r#""20150806125346""#).unwrap();
+        assert_eq!(no.as_str(), "20150806125346");
+        assert_eq!(serde_json::to_string(&no).unwrap(), r#""20150806125346""#);
+        assert!(serde_json::from_str::<OutTradeNo>(r#""abc""#).is_err());
+    }
+
+    #[test]
+    fn test_out_refund_no() {
+        assert!(OutRefundNo::new("refund_1|2@3").is_ok());
+        assert!(OutRefundNo::new("1").is_ok());
+        assert!(OutRefundNo::new("").is_err());
+        assert!(OutRefundNo::new("1".repeat(65)).is_err());
+        assert!(OutRefundNo::new("退款单号").is_err());
+        assert_eq!(
+            "refund-1".parse::<OutRefundNo>().unwrap().to_string(),
+            "refund-1"
+        );
+    }
+}
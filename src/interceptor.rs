@@ -0,0 +1,88 @@
+//! 请求/响应拦截器。
+//! 可用于统一打点、注入 header、审计等。
+
+use crate::error::Result;
+use reqwest::{Method, Request, Response, Url};
+use std::fmt;
+
+/// 请求/响应拦截器。
+/// 通过 `WechatPayClientBuilder::interceptor` 注册，可注册多个，按注册顺序依次调用。
+/// 每次实际发送 HTTP 请求(包括重试)时都会调用。
+pub trait Interceptor: Send + Sync {
+    /// 请求签名前调用。可修改请求，如注入 header。
+    /// 返回错误时，请求不会发送，`execute` 返回此错误。
+    fn before_send(&self, _req: &mut Request) -> Result<()> {
+        Ok(())
+    }
+
+    /// 收到响应并验签后调用。请求失败、验签失败时，res 为对应的错误。
+    fn after_receive(&self, _method: &Method, _url: &Url, _res: &Result<Response>) {}
+}
+
+impl fmt::Debug for dyn Interceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Interceptor")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WechatPayError;
+    use crate::transport::mock_client_with;
+    use reqwest::StatusCode;
+    use std::sync::{Arc, Mutex};
+
+    /// 注入 header，并记录收到的响应状态
+    #[derive(Clone, Default)]
+    struct Tagger(Arc<Mutex<Vec<Option<StatusCode>>>>);
+
+    impl Interceptor for Tagger {
+        fn before_send(&self, req: &mut Request) -> Result<()> {
+            req.headers_mut()
+                .insert("X-Request-Tag", "tagged".parse().unwrap());
+            Ok(())
+        }
+
+        fn after_receive(&self, _method: &Method, _url: &Url, res: &Result<Response>) {
+            let status = res.as_ref().ok().map(|res| res.status());
+            self.0.lock().unwrap().push(status);
+        }
+    }
+
+    /// 拒绝所有请求
+    struct Reject;
+
+    impl Interceptor for Reject {
+        fn before_send(&self, _req: &mut Request) -> Result<()> {
+            Err(WechatPayError::InvalidParams("rejected".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interceptor() -> anyhow::Result<()> {
+        let tagger = Tagger::default();
+        let (client, mock) = mock_client_with(|builder| {
+            builder.interceptor(tagger.clone());
+        })
+        .await?;
+        let path = "/v3/pay/transactions/out-trade-no/1217752501201407033233368018/close";
+        mock.on(Method::POST, path, StatusCode::NO_CONTENT, "");
+
+        client.close_trade("1217752501201407033233368018").await?;
+        let requests = mock.requests();
+        assert_eq!(requests[0].headers["X-Request-Tag"], "tagged");
+        assert_eq!(*tagger.0.lock().unwrap(), [Some(StatusCode::NO_CONTENT)]);
+
+        // before_send 返回错误时，请求不会发送
+        let (client, mock) = mock_client_with(|builder| {
+            builder.interceptor(Reject);
+        })
+        .await?;
+        mock.on(Method::POST, path, StatusCode::NO_CONTENT, "");
+        let res = client.close_trade("1217752501201407033233368018").await;
+        assert!(matches!(res, Err(WechatPayError::InvalidParams(_))));
+        assert!(mock.requests().is_empty());
+        Ok(())
+    }
+}
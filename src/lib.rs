@@ -1,12 +1,60 @@
+#[cfg(feature = "actix-web")]
+pub mod actix_ext;
+pub mod apply4subject;
+pub mod attach;
+pub mod audit;
+#[cfg(feature = "axum")]
+pub mod axum_ext;
+pub mod bank;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod client;
+pub mod clock;
+pub mod complaint;
 pub mod credential;
+pub mod dedup;
+pub mod dispatcher;
+pub mod download;
+pub mod ecommerce;
 pub mod error;
+pub mod failover;
+pub mod fapiao;
+pub mod global;
+pub mod ids;
+pub mod interceptor;
+pub mod media;
+pub mod metrics;
+pub mod money;
 pub mod notify;
+#[cfg(feature = "notify-server")]
+pub mod notify_server;
+pub mod paginate;
+pub mod papay;
 pub mod platform_certificate;
+pub mod pool;
+#[cfg(feature = "qrcode")]
+pub mod qrcode;
+pub mod rate_limit;
 pub mod refund;
+pub mod sensitive;
+#[cfg(feature = "tower")]
+pub mod tower_ext;
+#[cfg(feature = "tracing")]
+mod trace;
 pub mod trade;
+pub mod transfer;
+pub mod transport;
 pub mod util;
+#[cfg(feature = "v2")]
+pub mod v2;
+pub mod violation;
 
+#[cfg(feature = "blocking")]
+pub use blocking::WechatPayBlockingClient;
 pub use client::WechatPayClient;
 pub use credential::MchCredential;
+pub use error::WechatPayError;
+#[cfg(feature = "x509")]
 pub use platform_certificate::PlatformCertificate;
+pub use platform_certificate::WechatPayPublicKey;
+pub use pool::WechatPayClientPool;
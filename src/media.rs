@@ -0,0 +1,316 @@
+//! 图片、视频等媒体文件上传接口。
+
+use crate::client::WechatPayClient;
+use crate::error::{Result, WechatPayError};
+use crate::util::hex_encode;
+use reqwest::multipart::{Form, Part};
+use reqwest::Body;
+use rsa::sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// 图片大小上限，2MB。
+pub const MAX_IMAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// 视频大小上限，5MB。
+pub const MAX_VIDEO_SIZE: u64 = 5 * 1024 * 1024;
+
+impl WechatPayClient {
+    /// 上传图片，返回 media_id。
+    /// 图片仅支持 JPG、BMP、PNG 格式，大小不超过 2M。media_id 可用于投诉回复、进件等接口。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter2_1_1.shtml>
+    pub async fn upload_image(&self, filename: &str, content: Vec<u8>) -> Result<String> {
+        if content.len() > MAX_IMAGE_SIZE {
+            return Err(WechatPayError::InvalidParams(format!(
+                "image is too large: {} bytes, at most {} bytes",
+                content.len(),
+                MAX_IMAGE_SIZE
+            )));
+        }
+        let mime = image_mime(filename)?;
+
+        let meta = MediaMeta {
+            filename: filename.to_string(),
+            sha256: hex_encode(&Sha256::digest(&content)),
+        };
+        let file = Part::bytes(content)
+            .file_name(filename.to_string())
+            .mime_str(mime)?;
+        self.upload_media("merchant/media/upload", &meta, file)
+            .await
+    }
+
+    /// 上传视频，返回 media_id。
+    /// 视频内容从 reader 中流式读取，不必将整个文件读入内存。
+    /// 由于 meta 中需要文件的摘要，reader 会被读取两次：先计算摘要，再 seek 回起始位置进行上传。
+    /// 视频支持 AVI、WMV、MPEG、MP4、MOV、MKV、FLV、F4V、M4V、RMVB 格式，大小不超过 5M。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter2_1_2.shtml>
+    pub async fn upload_video<R>(&self, filename: &str, mut reader: R) -> Result<String>
+    where
+        R: AsyncRead + AsyncSeek + Unpin + Send + Sync + 'static,
+    {
+        let mime = video_mime(filename)?;
+
+        let start = reader.stream_position().await?;
+        let mut hasher = Sha256::new();
+        let mut len: u64 = 0;
+        let mut buf = vec![0; 8192];
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            len += n as u64;
+        }
+        if len > MAX_VIDEO_SIZE {
+            return Err(WechatPayError::InvalidParams(format!(
+                "video is too large: {} bytes, at most {} bytes",
+                len, MAX_VIDEO_SIZE
+            )));
+        }
+        reader.seek(SeekFrom::Start(start)).await?;
+
+        let meta = MediaMeta {
+            filename: filename.to_string(),
+            sha256: hex_encode(&hasher.finalize()),
+        };
+        let body = Body::wrap_stream(ReaderStream::new(reader));
+        let file = Part::stream_with_length(body, len)
+            .file_name(filename.to_string())
+            .mime_str(mime)?;
+        self.upload_media("merchant/media/video_upload", &meta, file)
+            .await
+    }
+
+    /// 以 multipart/form-data 上传媒体文件，返回 media_id。
+    /// 此类接口只对 meta 部分进行签名。
+    async fn upload_media(&self, path: &str, meta: &MediaMeta, file: Part) -> Result<String> {
+        let meta = serde_json::to_string(meta)?;
+        let form = Form::new()
+            .part(
+                "meta",
+                Part::text(meta.clone()).mime_str("application/json")?,
+            )
+            .part("file", file);
+
+        let url = format!("{}/{}", self.base_url, path);
+        let req = self.client.post(url).multipart(form).build()?;
+        let res = self
+            .execute_with_signed_body(req, Some(meta.as_bytes()))
+            .await?;
+        let res: UploadMediaResponse = res.json().await?;
+        Ok(res.media_id)
+    }
+}
+
+/// 媒体文件的元信息，是上传接口中参与签名的部分。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MediaMeta {
+    /// 文件名，须带有后缀名
+    filename: String,
+    /// 文件内容的 SHA256 摘要，十六进制编码
+    sha256: String,
+}
+
+/// 上传媒体文件的响应
+#[derive(Debug, Clone, Deserialize)]
+struct UploadMediaResponse {
+    media_id: String,
+}
+
+/// 获取文件的后缀名(小写)
+fn file_ext(filename: &str) -> String {
+    filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// 根据文件后缀名获取图片的 MIME 类型
+fn image_mime(filename: &str) -> Result<&'static str> {
+    match file_ext(filename).as_str() {
+        "jpg" | "jpeg" => Ok("image/jpeg"),
+        "png" => Ok("image/png"),
+        "bmp" => Ok("image/bmp"),
+        _ => Err(WechatPayError::InvalidParams(format!(
+            "unsupported image type: {}",
+            filename
+        ))),
+    }
+}
+
+/// 根据文件后缀名获取视频的 MIME 类型
+fn video_mime(filename: &str) -> Result<&'static str> {
+    match file_ext(filename).as_str() {
+        "avi" => Ok("video/x-msvideo"),
+        "wmv" => Ok("video/x-ms-wmv"),
+        "mpeg" => Ok("video/mpeg"),
+        "mp4" => Ok("video/mp4"),
+        "mov" => Ok("video/quicktime"),
+        "mkv" => Ok("video/x-matroska"),
+        "flv" => Ok("video/x-flv"),
+        "f4v" => Ok("video/x-f4v"),
+        "m4v" => Ok("video/x-m4v"),
+        "rmvb" => Ok("application/vnd.rn-realmedia-vbr"),
+        _ => Err(WechatPayError::InvalidParams(format!(
+            "unsupported video type: {}",
+            filename
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+    use crate::MchCredential;
+    use base64::prelude::*;
+    use rsa::pkcs1v15::{Signature, VerifyingKey};
+    use rsa::signature::Verifier;
+    use rsa::RsaPrivateKey;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// 上传请求的 header 与 body
+    struct Upload {
+        head: String,
+        body: Vec<u8>,
+    }
+
+    impl Upload {
+        /// Authorization header 中的字段
+        fn authorization(&self, key: &str) -> String {
+            let pattern = format!("{}=\"", key);
+            let start = self.head.find(&pattern).unwrap() + pattern.len();
+            let end = start + self.head[start..].find('"').unwrap();
+            self.head[start..end].to_string()
+        }
+
+        fn body_contains(&self, content: &[u8]) -> bool {
+            self.body.windows(content.len()).any(|w| w == content)
+        }
+    }
+
+    /// 在后台应答一次上传请求。multipart/form-data 为流式 body，`MockTransport` 无法读取，
+    /// 因此通过本地 HTTP 服务接收完整的请求，应答使用 mock 的私钥签名。
+    async fn upload_client() -> anyhow::Result<(WechatPayClient, JoinHandle<Upload>)> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let mock = MockTransport::new(private_key.clone(), "PUB_KEY_ID_0000000000000001");
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let base_url = format!("http://{}/v3", listener.local_addr()?);
+        let wechatpay_public_key = mock.wechatpay_public_key();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut req = vec![];
+            let mut buf = vec![0; 8192];
+            let (head, body) = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                req.extend_from_slice(&buf[..n]);
+                let Some(pos) = req.windows(4).position(|w| w == b"\r\n\r\n") else {
+                    continue;
+                };
+                let head = String::from_utf8(req[..pos].to_vec()).unwrap();
+                let len: usize = head
+                    .lines()
+                    .find_map(|l| {
+                        l.to_ascii_lowercase()
+                            .strip_prefix("content-length: ")
+                            .map(|v| v.parse().unwrap())
+                    })
+                    .unwrap();
+                if req.len() >= pos + 4 + len {
+                    break (head, req[pos + 4..].to_vec());
+                }
+            };
+
+            let res = r#"{"media_id":"6uqyGjGrCf2GtyXP8bxrbuH9-aAoTjH-rKeSl3Lf4_So6kdkQu4w8BYVP3bzLtvR38lxt4PjtCDXsQpzqge_hQEovHzOhsLleGFQVRF-U_0"}"#;
+            let mut head_out = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+                res.len()
+            );
+            for (key, value) in &mock.signature_headers(res).unwrap() {
+                head_out.push_str(&format!("{}: {}\r\n", key, value.to_str().unwrap()));
+            }
+            head_out.push_str("\r\n");
+            stream.write_all(head_out.as_bytes()).await.unwrap();
+            stream.write_all(res.as_bytes()).await.unwrap();
+            Upload { head, body }
+        });
+
+        let client = WechatPayClient::builder()
+            .mch_credential(MchCredential::new(
+                "1900000001".to_string(),
+                "serial".to_string(),
+                RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?,
+                "0".repeat(32),
+            ))
+            .wechatpay_public_key(wechatpay_public_key)
+            .base_url(&base_url)
+            .build()
+            .await?;
+        Ok((client, handle))
+    }
+
+    /// 校验签名只覆盖 meta 部分
+    fn verify_signature(client: &WechatPayClient, upload: &Upload, path: &str, meta: &str) {
+        let msg = format!(
+            "POST\n{}\n{}\n{}\n{}\n",
+            path,
+            upload.authorization("timestamp"),
+            upload.authorization("nonce_str"),
+            meta
+        );
+        let signature = BASE64_STANDARD
+            .decode(upload.authorization("signature"))
+            .unwrap();
+        let public_key = client.mch_credential().mch_rsa_private_key.to_public_key();
+        VerifyingKey::<Sha256>::new(public_key)
+            .verify(
+                msg.as_bytes(),
+                &Signature::try_from(signature.as_slice()).unwrap(),
+            )
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_image() -> anyhow::Result<()> {
+        let (client, handle) = upload_client().await?;
+        let content = b"\x89PNG\r\n\x1a\n0000".to_vec();
+        let media_id = client.upload_image("image.png", content.clone()).await?;
+        assert!(media_id.starts_with("6uqyGjGrCf2GtyXP8bxrbuH9"));
+
+        let upload = handle.await?;
+        let meta = serde_json::to_string(&MediaMeta {
+            filename: "image.png".to_string(),
+            sha256: hex_encode(&Sha256::digest(&content)),
+        })?;
+        assert!(upload.body_contains(meta.as_bytes()));
+        assert!(upload.body_contains(&content));
+        verify_signature(&client, &upload, "/v3/merchant/media/upload", &meta);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_video() -> anyhow::Result<()> {
+        let (client, handle) = upload_client().await?;
+        // 大于计算摘要时的读缓冲区，须 seek 回起始位置后才能完整上传
+        let content: Vec<u8> = (0..20000).map(|i| (i % 251) as u8).collect();
+        let reader = std::io::Cursor::new(content.clone());
+        client.upload_video("video.mp4", reader).await?;
+
+        let upload = handle.await?;
+        let meta = serde_json::to_string(&MediaMeta {
+            filename: "video.mp4".to_string(),
+            sha256: hex_encode(&Sha256::digest(&content)),
+        })?;
+        assert!(upload.body_contains(meta.as_bytes()));
+        assert!(upload.body_contains(&content));
+        verify_signature(&client, &upload, "/v3/merchant/media/video_upload", &meta);
+        Ok(())
+    }
+}
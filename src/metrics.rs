@@ -0,0 +1,160 @@
+//! 指标回调。
+//! 实现 `MetricsHook` 并通过 `WechatPayClientBuilder::metrics_hook` 注册，即可将请求数、时延、
+//! 错误码分布、验签失败次数等接入 Prometheus 等监控系统。
+
+use crate::error::{Result, WechatPayError};
+use reqwest::{Method, Response, StatusCode};
+use std::fmt;
+use std::time::Duration;
+
+/// 一次 API 调用的指标
+#[derive(Debug, Clone)]
+pub struct RequestMetrics<'a> {
+    /// 请求方法
+    pub method: &'a Method,
+    /// 请求路径，如 `/v3/pay/transactions/jsapi`。
+    /// 部分接口的路径中带有订单号等参数，用作监控标签时请注意归一化，避免标签基数过大。
+    pub path: &'a str,
+    /// 耗时，包括重试
+    pub elapsed: Duration,
+    /// 响应状态码。网络错误等未收到响应时为 None。
+    pub status: Option<StatusCode>,
+    /// 微信支付返回的错误码。成功或非业务错误时为 None。
+    pub error_code: Option<&'a str>,
+    /// 是否成功
+    pub success: bool,
+}
+
+impl<'a> RequestMetrics<'a> {
+    pub(crate) fn new(
+        method: &'a Method,
+        path: &'a str,
+        elapsed: Duration,
+        res: &'a Result<Response>,
+    ) -> RequestMetrics<'a> {
+        let (status, error_code) = match res {
+            Ok(res) => (Some(res.status()), None),
+            Err(WechatPayError::Api(e)) => (e.status(), Some(e.code())),
+            Err(WechatPayError::Http(e)) => (e.status(), None),
+            Err(_) => (None, None),
+        };
+        RequestMetrics {
+            method,
+            path,
+            elapsed,
+            status,
+            error_code,
+            success: res.is_ok(),
+        }
+    }
+}
+
+/// 指标回调。各方法均有空的默认实现，按需实现即可。
+/// 回调在请求处理流程中同步调用，请勿在其中执行耗时操作。
+pub trait MetricsHook: Send + Sync {
+    /// 每次 API 调用完成后调用(重试只计一次)。
+    fn on_request(&self, _metrics: &RequestMetrics<'_>) {}
+
+    /// 响应验签失败时调用。
+    fn on_verify_failure(&self) {}
+}
+
+impl fmt::Debug for dyn MetricsHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MetricsHook")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{mock_client_with, MockTransport, Transport};
+    use async_trait::async_trait;
+    use reqwest::Request;
+    use rsa::RsaPrivateKey;
+    use std::sync::{Arc, Mutex};
+
+    /// RequestMetrics 中与断言相关的部分
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Recorded {
+        method: Method,
+        path: String,
+        elapsed: Duration,
+        status: Option<StatusCode>,
+        error_code: Option<String>,
+        success: bool,
+    }
+
+    #[derive(Clone, Default)]
+    struct Hook(Arc<Mutex<Vec<Recorded>>>);
+
+    impl MetricsHook for Hook {
+        fn on_request(&self, metrics: &RequestMetrics<'_>) {
+            self.0.lock().unwrap().push(Recorded {
+                method: metrics.method.clone(),
+                path: metrics.path.to_string(),
+                elapsed: metrics.elapsed,
+                status: metrics.status,
+                error_code: metrics.error_code.map(|s| s.to_string()),
+                success: metrics.success,
+            });
+        }
+    }
+
+    /// 延迟应答的传输层，用于校验耗时
+    struct Delayed(MockTransport);
+
+    #[async_trait]
+    impl Transport for Delayed {
+        async fn send(&self, req: Request) -> Result<Response> {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.0.send(req).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_hook() -> anyhow::Result<()> {
+        let hook = Hook::default();
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let mock = MockTransport::new(private_key, "PUB_KEY_ID_0000000000000002");
+        let (client, _) = mock_client_with(|builder| {
+            builder
+                .metrics_hook(hook.clone())
+                .wechatpay_public_key(mock.wechatpay_public_key())
+                .transport(Delayed(mock.clone()));
+        })
+        .await?;
+        let path = "/v3/pay/transactions/out-trade-no/1217752501201407033233368018/close";
+        mock.on(Method::POST, path, StatusCode::NO_CONTENT, "");
+        let failed_path = "/v3/pay/transactions/out-trade-no/1217752501201407033233368019/close";
+        mock.on(
+            Method::POST,
+            failed_path,
+            StatusCode::BAD_REQUEST,
+            r#"{"code":"ORDER_CLOSED","message":"订单已关闭"}"#,
+        );
+
+        client.close_trade("1217752501201407033233368018").await?;
+        assert!(client
+            .close_trade("1217752501201407033233368019")
+            .await
+            .is_err());
+
+        let recorded = hook.0.lock().unwrap().clone();
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[0].method, Method::POST);
+        assert_eq!(recorded[0].path, path);
+        assert_eq!(recorded[0].status, Some(StatusCode::NO_CONTENT));
+        assert_eq!(recorded[0].error_code, None);
+        assert!(recorded[0].success);
+        assert!(recorded[0].elapsed >= Duration::from_millis(20));
+
+        assert_eq!(recorded[1].method, Method::POST);
+        assert_eq!(recorded[1].path, failed_path);
+        assert_eq!(recorded[1].status, Some(StatusCode::BAD_REQUEST));
+        assert_eq!(recorded[1].error_code.as_deref(), Some("ORDER_CLOSED"));
+        assert!(!recorded[1].success);
+        assert!(recorded[1].elapsed >= Duration::from_millis(20));
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! 金额类型。
+//! 微信支付的金额均以分为单位。`Fen` 表示以分为单位的金额，避免与元混淆；
+//! `Money` 则同时绑定币种，不同币种的金额不能相加减。
+
+use crate::error::{Result, WechatPayError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 以分为单位的金额。序列化为整数。
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Fen(pub i64);
+
+impl Fen {
+    pub const ZERO: Fen = Fen(0);
+
+    pub const fn new(fen: i64) -> Fen {
+        Fen(fen)
+    }
+
+    pub const fn value(self) -> i64 {
+        self.0
+    }
+
+    /// 从以元为单位的字符串解析，如 "12.34"、"-0.5"、"100"。最多两位小数。
+    pub fn from_yuan_str(s: &str) -> Result<Fen> {
+        let invalid = || WechatPayError::InvalidParams(format!("invalid yuan amount: {}", s));
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (yuan, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if yuan.is_empty()
+            || fraction.len() > 2
+            || !yuan.chars().all(|c| c.is_ascii_digit())
+            || !fraction.chars().all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        let yuan: i64 = yuan.parse().map_err(|_| invalid())?;
+        let fraction: i64 = format!("{:0<2}", fraction).parse().map_err(|_| invalid())?;
+        let fen = yuan
+            .checked_mul(100)
+            .and_then(|f| f.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        Ok(Fen(if negative { -fen } else { fen }))
+    }
+
+    /// 转换为以元为单位的字符串，保留两位小数，如 "12.34"。
+    pub fn to_yuan_string(self) -> String {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        format!("{}{}.{:02}", sign, abs / 100, abs % 100)
+    }
+
+    pub fn checked_add(self, other: Fen) -> Option<Fen> {
+        self.0.checked_add(other.0).map(Fen)
+    }
+
+    pub fn checked_sub(self, other: Fen) -> Option<Fen> {
+        self.0.checked_sub(other.0).map(Fen)
+    }
+}
+
+impl From<i32> for Fen {
+    fn from(fen: i32) -> Self {
+        Fen(fen as i64)
+    }
+}
+
+impl From<i64> for Fen {
+    fn from(fen: i64) -> Self {
+        Fen(fen)
+    }
+}
+
+impl fmt::Display for Fen {
+    /// 显示为以分为单位的整数
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// 绑定币种的金额
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Money {
+    /// 金额，单位为分。
+    pub amount: Fen,
+    /// 币种。符合 ISO 4217 标准的三位字母代码，如 CNY。
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(amount: Fen, currency: &str) -> Money {
+        Money {
+            amount,
+            currency: currency.to_string(),
+        }
+    }
+
+    /// 人民币金额
+    pub fn cny(amount: impl Into<Fen>) -> Money {
+        Money::new(amount.into(), "CNY")
+    }
+
+    /// 从以元为单位的字符串解析，参见 `Fen::from_yuan_str`。
+    pub fn from_yuan_str(s: &str, currency: &str) -> Result<Money> {
+        Ok(Money::new(Fen::from_yuan_str(s)?, currency))
+    }
+
+    /// 转换为以元为单位的字符串，参见 `Fen::to_yuan_string`。
+    pub fn to_yuan_string(&self) -> String {
+        self.amount.to_yuan_string()
+    }
+
+    /// 相加。币种不同或溢出时返回错误。
+    pub fn checked_add(&self, other: &Money) -> Result<Money> {
+        self.check_currency(other)?;
+        let amount = self
+            .amount
+            .checked_add(other.amount)
+            .ok_or_else(|| WechatPayError::InvalidParams("amount overflow".to_string()))?;
+        Ok(Money::new(amount, &self.currency))
+    }
+
+    /// 相减。币种不同或溢出时返回错误。
+    pub fn checked_sub(&self, other: &Money) -> Result<Money> {
+        self.check_currency(other)?;
+        let amount = self
+            .amount
+            .checked_sub(other.amount)
+            .ok_or_else(|| WechatPayError::InvalidParams("amount overflow".to_string()))?;
+        Ok(Money::new(amount, &self.currency))
+    }
+
+    fn check_currency(&self, other: &Money) -> Result<()> {
+        if self.currency != other.currency {
+            return Err(WechatPayError::InvalidParams(format!(
+                "currency mismatch: {} and {}",
+                self.currency, other.currency
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yuan_str() -> anyhow::Result<()> {
+        assert_eq!(Fen::from_yuan_str("12.34")?, Fen(1234));
+        assert_eq!(Fen::from_yuan_str("12.3")?, Fen(1230));
+        assert_eq!(Fen::from_yuan_str("100")?, Fen(10000));
+        assert_eq!(Fen::from_yuan_str("-0.05")?, Fen(-5));
+        assert!(Fen::from_yuan_str("1.234").is_err());
+        assert!(Fen::from_yuan_str("1,00").is_err());
+        assert!(Fen::from_yuan_str(".5").is_err());
+
+        assert_eq!(Fen(1234).to_yuan_string(), "12.34");
+        assert_eq!(Fen(5).to_yuan_string(), "0.05");
+        assert_eq!(Fen(-5).to_yuan_string(), "-0.05");
+        Ok(())
+    }
+
+    #[test]
+    fn test_money_checked_ops() -> anyhow::Result<()> {
+        let a = Money::cny(100);
+        let b = Money::from_yuan_str("0.5", "CNY")?;
+        assert_eq!(a.checked_add(&b)?.amount, Fen(150));
+        assert_eq!(a.checked_sub(&b)?.to_yuan_string(), "0.50");
+        assert!(a.checked_add(&Money::new(Fen(1), "USD")).is_err());
+        assert!(Money::cny(i64::MAX).checked_add(&Money::cny(1)).is_err());
+        Ok(())
+    }
+}
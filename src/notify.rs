@@ -1,18 +1,30 @@
 //! 微信支付通知。包括支付结果与退款结果的通知。
 
-use crate::refund::RefundQueryResponse;
-use crate::util::datetime_fmt;
+use crate::complaint::ComplaintNotification;
+use crate::ecommerce::ApplymentStateNotification;
+use crate::error::{Result, WechatPayError};
+use crate::fapiao::FapiaoNotification;
+use crate::refund::RefundNotification;
+use crate::transfer::TransferBillNotification;
+use crate::util::{datetime_fmt, option_datetime_fmt};
+use crate::violation::ViolationNotification;
 use crate::{client::WechatPayClient, trade::TradeQueryResponse};
-use anyhow::Result;
-use bytes::Bytes;
+use base64::prelude::*;
+use bytes::{BufMut, Bytes, BytesMut};
 use chrono::{DateTime, Local};
-use http::{StatusCode, Version};
-use hyper::Body;
-use serde::{Deserialize, Serialize};
+use http::{HeaderMap, StatusCode};
+use http_body::Body as HttpBody;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
 
 /// 微信支付通知。
 /// 包括支付结果与退款结果。
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct WechatPayNotification {
     /// 通知的唯一 ID，长度不超过 36 字符。
     pub id: String,
@@ -20,11 +32,7 @@ pub struct WechatPayNotification {
     #[serde(with = "datetime_fmt")]
     pub create_time: DateTime<Local>,
     /// 通知类型。不超过 32 字符。
-    /// TRANSACTION.SUCCESS：支付成功通知。
-    /// REFUND.SUCCESS：退款成功通知
-    /// REFUND.ABNORMAL：退款异常通知
-    /// REFUND.CLOSED：退款关闭通知
-    pub event_type: String,
+    pub event_type: NotifyEventType,
     /// 通知的资源数据类型，不超过 32 字符。支付成功通知为 encrypt-resource。
     pub resource_type: String,
     /// 通知资源数据。
@@ -35,6 +43,7 @@ pub struct WechatPayNotification {
 
 /// 通知资源数据。
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct NotificationResourse {
     /// 加密算法类型。目前只支持AEAD_AES_256_GCM。
     pub algorithm: String,
@@ -50,65 +59,708 @@ pub struct NotificationResourse {
     pub nonce: String,
 }
 
+/// 通知类型。未知的通知类型保留原文，为 `Other`。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NotifyEventType {
+    /// TRANSACTION.SUCCESS：支付成功通知
+    TransactionSuccess,
+    /// REFUND.SUCCESS：退款成功通知
+    RefundSuccess,
+    /// REFUND.ABNORMAL：退款异常通知
+    RefundAbnormal,
+    /// REFUND.CLOSED：退款关闭通知
+    RefundClosed,
+    /// FAPIAO.USER_APPLIED：用户提交抬头通知
+    FapiaoUserApplied,
+    /// FAPIAO.ISSUED：发票开具完成通知
+    FapiaoIssued,
+    /// FAPIAO.REVERSED：发票冲红完成通知
+    FapiaoReversed,
+    /// COMPLAINT.CREATE：产生新投诉通知
+    ComplaintCreate,
+    /// COMPLAINT.STATE_CHANGE：投诉状态变化通知
+    ComplaintStateChange,
+    /// COUPON.USE：代金券核销通知
+    CouponUse,
+    /// VIOLATION.PUNISH：商户违规处罚通知
+    ViolationPunish,
+    /// VIOLATION.INTERCEPT：商户违规拦截通知
+    ViolationIntercept,
+    /// VIOLATION.APPEAL：商户违规申诉通知
+    ViolationAppeal,
+    /// APPLYMENT_STATE.CHANGE：二级商户进件状态变化通知
+    ApplymentStateChange,
+    /// 其他通知类型
+    Other(String),
+}
+
+impl NotifyEventType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            NotifyEventType::TransactionSuccess => "TRANSACTION.SUCCESS",
+            NotifyEventType::RefundSuccess => "REFUND.SUCCESS",
+            NotifyEventType::RefundAbnormal => "REFUND.ABNORMAL",
+            NotifyEventType::RefundClosed => "REFUND.CLOSED",
+            NotifyEventType::FapiaoUserApplied => "FAPIAO.USER_APPLIED",
+            NotifyEventType::FapiaoIssued => "FAPIAO.ISSUED",
+            NotifyEventType::FapiaoReversed => "FAPIAO.REVERSED",
+            NotifyEventType::ComplaintCreate => "COMPLAINT.CREATE",
+            NotifyEventType::ComplaintStateChange => "COMPLAINT.STATE_CHANGE",
+            NotifyEventType::CouponUse => "COUPON.USE",
+            NotifyEventType::ViolationPunish => "VIOLATION.PUNISH",
+            NotifyEventType::ViolationIntercept => "VIOLATION.INTERCEPT",
+            NotifyEventType::ViolationAppeal => "VIOLATION.APPEAL",
+            NotifyEventType::ApplymentStateChange => "APPLYMENT_STATE.CHANGE",
+            NotifyEventType::Other(s) => s,
+        }
+    }
+
+    /// 通知类型的前缀，如 TRANSACTION、REFUND、FAPIAO 等。
+    pub fn category(&self) -> &str {
+        let s = self.as_str();
+        s.split_once('.').map_or(s, |(prefix, _)| prefix)
+    }
+}
+
+impl From<&str> for NotifyEventType {
+    fn from(s: &str) -> Self {
+        match s {
+            "TRANSACTION.SUCCESS" => NotifyEventType::TransactionSuccess,
+            "REFUND.SUCCESS" => NotifyEventType::RefundSuccess,
+            "REFUND.ABNORMAL" => NotifyEventType::RefundAbnormal,
+            "REFUND.CLOSED" => NotifyEventType::RefundClosed,
+            "FAPIAO.USER_APPLIED" => NotifyEventType::FapiaoUserApplied,
+            "FAPIAO.ISSUED" => NotifyEventType::FapiaoIssued,
+            "FAPIAO.REVERSED" => NotifyEventType::FapiaoReversed,
+            "COMPLAINT.CREATE" => NotifyEventType::ComplaintCreate,
+            "COMPLAINT.STATE_CHANGE" => NotifyEventType::ComplaintStateChange,
+            "COUPON.USE" => NotifyEventType::CouponUse,
+            "VIOLATION.PUNISH" => NotifyEventType::ViolationPunish,
+            "VIOLATION.INTERCEPT" => NotifyEventType::ViolationIntercept,
+            "VIOLATION.APPEAL" => NotifyEventType::ViolationAppeal,
+            "APPLYMENT_STATE.CHANGE" => NotifyEventType::ApplymentStateChange,
+            _ => NotifyEventType::Other(s.to_string()),
+        }
+    }
+}
+
+impl FromStr for NotifyEventType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(NotifyEventType::from(s))
+    }
+}
+
+impl fmt::Display for NotifyEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for NotifyEventType {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(NotifyEventType::from(s.as_str()))
+    }
+}
+
+impl Serialize for NotifyEventType {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// 解密后的通知资源数据，按通知类型区分。
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum NotificationEvent {
     Trade(TradeQueryResponse),
-    Refund(RefundQueryResponse),
+    Refund(RefundNotification),
+    Fapiao(FapiaoNotification),
+    Complaint(ComplaintNotification),
+    /// 代金券核销
+    Coupon(CouponNotification),
+    /// 商家券领券等事件
+    BusiFavor(BusiFavorNotification),
+    /// 微信支付分
+    Payscore(PayscoreNotification),
+    /// 分账动账
+    ProfitSharing(ProfitSharingNotification),
+    /// 商家转账
+    Transfer(TransferBillNotification),
+    /// 商户违规
+    Violation(ViolationNotification),
+    /// 二级商户进件状态变化
+    ApplymentState(ApplymentStateNotification),
+    /// 其他类型的通知，为解密后的原始 JSON。
+    Other(serde_json::Value),
+}
+
+/// 代金券核销通知解密后的资源数据。对应 event_type: COUPON.USE
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CouponNotification {
+    /// 创建批次的商户号
+    pub stock_creator_mchid: String,
+    /// 批次号
+    pub stock_id: String,
+    /// 代金券 ID
+    pub coupon_id: String,
+    /// 代金券名称
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub coupon_name: Option<String>,
+    /// 代金券状态。SENDED：可用；USED：已实扣；EXPIRED：已过期
+    pub status: String,
+    /// 券类型。NORMAL：满减券；CUT_TO：减至券
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub coupon_type: Option<String>,
+    /// 是否无资金流
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub no_cash: Option<bool>,
+    /// 满减券信息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub normal_coupon_information: Option<NormalCouponInformation>,
+    /// 实扣代金券信息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub consume_information: Option<CouponConsumeInformation>,
+}
+
+/// 满减券信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct NormalCouponInformation {
+    /// 面额，单位为分。
+    pub coupon_amount: i32,
+    /// 使用券金额门槛，单位为分。
+    pub transaction_minimum: i32,
+}
+
+/// 实扣代金券信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CouponConsumeInformation {
+    /// 核销时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub consume_time: Option<DateTime<Local>>,
+    /// 核销商户号
+    pub consume_mchid: String,
+    /// 核销订单号
+    pub transaction_id: String,
+}
+
+/// 商家券事件通知解密后的资源数据。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BusiFavorNotification {
+    /// 事件类型。如 EVENT_TYPE_BUSICOUPON_SEND：领券事件
+    pub event_type: String,
+    /// 券 code
+    pub coupon_code: String,
+    /// 批次号
+    pub stock_id: String,
+    /// 发放时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub send_time: Option<DateTime<Local>>,
+    /// 用户标识
+    pub openid: String,
+    /// 用户统一标识
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub unionid: Option<String>,
+    /// 发放渠道
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub send_channel: Option<String>,
+    /// 发券商户号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub send_merchant: Option<String>,
+}
+
+/// 微信支付分通知(如确认订单、支付成功)解密后的资源数据。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct PayscoreNotification {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 商户号
+    pub mchid: String,
+    /// 商户服务订单号
+    pub out_order_no: String,
+    /// 服务 ID
+    pub service_id: String,
+    /// 用户标识
+    pub openid: String,
+    /// 服务订单状态。如 CREATED、DOING、DONE、REVOKED、EXPIRED
+    pub state: String,
+    /// 订单状态说明。如 USER_CONFIRM：用户确认；MCH_COMPLETE：商户完结
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub state_description: Option<String>,
+    /// 商户收款总金额，单位为分。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub total_amount: Option<i32>,
+    /// 微信支付服务订单号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub order_id: Option<String>,
+    /// 收款信息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub collection: Option<serde_json::Value>,
+}
+
+/// 分账动账通知解密后的资源数据。对应 event_type: PROFITSHARING.SUCCESS、PROFITSHARING.CLOSED
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ProfitSharingNotification {
+    /// 直连商户号
+    pub mchid: String,
+    /// 微信支付订单号
+    pub transaction_id: String,
+    /// 微信分账/回退单号
+    pub order_id: String,
+    /// 商户分账/回退单号
+    pub out_order_no: String,
+    /// 分账接收方
+    pub receiver: ProfitSharingNotificationReceiver,
+    /// 成功时间
+    #[serde(with = "datetime_fmt")]
+    pub success_time: DateTime<Local>,
+}
+
+/// 分账动账通知中的分账接收方
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ProfitSharingNotificationReceiver {
+    /// 分账接收方类型。MERCHANT_ID：商户号；PERSONAL_OPENID：个人 openid
+    #[serde(rename = "type")]
+    pub receiver_type: String,
+    /// 分账接收方账号
+    pub account: String,
+    /// 分账动账金额，单位为分。
+    pub amount: i32,
+    /// 分账/回退描述
+    pub description: String,
 }
 
 impl WechatPayClient {
     /// 对微信支付结果通知进行验签。
-    /// 为避免对于具体 web 框架的依赖，这里的参数为 `http::Request<hyper::Body>`。
+    /// 为避免对于具体 web 框架的依赖，这里的参数为 `http::Request<B>`，B 为任意实现了
+    /// `http_body::Body` 的类型(如 `hyper::Body`)。
     /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_5.shtml>
-    pub async fn verify_notification(
+    pub async fn verify_notification<B>(
         &self,
-        req: http::Request<Body>,
-    ) -> Result<http::Request<Bytes>> {
-        let method = req.method().clone();
-        let uri = req.uri().clone();
-        let version = req.version();
-
-        // 为避免代码重复，这里从 request 构造出一个 reponse 并进行验签。
-        let mut res_builder = http::Response::builder()
-            .status(StatusCode::OK)
-            .version(Version::HTTP_11);
-        for (key, value) in req.headers() {
-            res_builder = res_builder.header(key, value);
+        req: http::Request<B>,
+    ) -> Result<http::Request<Bytes>>
+    where
+        B: HttpBody + Unpin,
+        B::Error: fmt::Display,
+    {
+        let (parts, mut body) = req.into_parts();
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(|e| WechatPayError::Other(e.to_string()))?;
+            buf.put(chunk);
+        }
+        let body = buf.freeze();
+        self.verify_notification_parts(&parts.headers, &body)
+            .await?;
+        Ok(http::Request::from_parts(parts, body))
+    }
+
+    /// 对微信支付结果通知进行验签。
+    /// 参数为通知请求的 header 及原始 body，任何 web 框架只要能取得二者即可使用。
+    /// 验签通过后，还会校验 Wechatpay-Timestamp 与本地时间之差不超过 `notification_max_age`。
+    pub async fn verify_notification_parts(&self, headers: &HeaderMap, body: &[u8]) -> Result<()> {
+        self.verify_response_parts(headers, body).await?;
+        self.check_notification_timestamp(headers)
+    }
+
+    fn check_notification_timestamp(&self, headers: &HeaderMap) -> Result<()> {
+        let timestamp = headers
+            .get("Wechatpay-Timestamp")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<i64>().ok())
+            .ok_or_else(|| WechatPayError::Verify("invalid `Wechatpay-Timestamp` header".into()))?;
+        let age = (self.clock.timestamp() - timestamp).unsigned_abs();
+        if age > self.notification_max_age.as_secs() {
+            return Err(WechatPayError::TimestampExpired(format!(
+                "timestamp {} differs from local time by {}s",
+                timestamp, age
+            )));
+        }
+        Ok(())
+    }
+
+    /// 处理微信支付通知：验签、解析通知、解密资源数据，并根据通知类型解析为 `NotificationEvent`。
+    /// 参数为通知请求的 header 及原始 body。
+    /// 如配置了通知去重存储，已记录的通知返回 `WechatPayError::DuplicateNotification`。
+    /// 此方法不记录通知 id，业务处理成功后才记录应使用 `process_notification`。
+    pub async fn handle_notification(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<NotificationEvent> {
+        self.verify_notification_parts(headers, body).await?;
+        let noti: WechatPayNotification = serde_json::from_slice(body)?;
+        if let Some(store) = &self.notification_dedup_store {
+            if store.contains(&noti.id).await? {
+                return Err(WechatPayError::DuplicateNotification(noti.id));
+            }
+        }
+        self.decrypt_notification(&noti)
+    }
+
+    /// 同 `handle_notification`，并调用 handler 处理解密后的通知。
+    /// 如配置了通知去重存储，处理前记录通知 id，重复的通知返回 `WechatPayError::DuplicateNotification`
+    /// 且不调用 handler；解密失败或 handler 返回 Err 时删除通知 id，微信支付重新通知时会再次处理。
+    pub async fn process_notification<F, Fut, R>(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        handler: F,
+    ) -> Result<R>
+    where
+        F: FnOnce(NotificationEvent) -> Fut,
+        Fut: Future<Output = Result<R>>,
+    {
+        self.verify_notification_parts(headers, body).await?;
+        let noti: WechatPayNotification = serde_json::from_slice(body)?;
+        let store = match &self.notification_dedup_store {
+            Some(store) => store,
+            None => return handler(self.decrypt_notification(&noti)?).await,
+        };
+
+        if !store.insert_if_absent(&noti.id).await? {
+            return Err(WechatPayError::DuplicateNotification(noti.id));
         }
-        let res: reqwest::Response = res_builder.body(req.into_body())?.into();
-        let res = self.verify_response(res).await?;
-
-        // 验签通过，再又基于 response 构建 request
-        let mut req_builder = http::Request::builder()
-            .method(method)
-            .uri(uri)
-            .version(version);
-        for (key, value) in res.headers() {
-            req_builder = req_builder.header(key, value);
+        let result = match self.decrypt_notification(&noti) {
+            Ok(event) => handler(event).await,
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            if let Err(e) = store.remove(&noti.id).await {
+                log::error!("failed to remove notification id {}: {}", noti.id, e);
+            }
         }
-        let body = res.bytes().await?;
-        let req: http::Request<Bytes> = req_builder.body(body)?;
-        Ok(req)
+        result
     }
 
-    /// 解密微信支付结果通知，解密结果为 TradeQueryResponse
+    /// 解密微信支付通知。根据通知类型，解密结果为 TradeQueryResponse、RefundNotification 等。
     pub fn decrypt_notification(&self, noti: &WechatPayNotification) -> Result<NotificationEvent> {
-        let plain = self.mch_credential.aes_decrypt(
-            noti.resource.ciphertext.as_bytes(),
-            noti.resource.associated_data.as_bytes(),
-            noti.resource.nonce.as_bytes(),
-        )?;
+        let plain = self.decrypt_notification_plaintext(noti)?;
 
         let event = match noti.resource.original_type.as_str() {
             "transaction" => NotificationEvent::Trade(serde_json::from_slice(&plain)?),
             "refund" => NotificationEvent::Refund(serde_json::from_slice(&plain)?),
-            _ => {
-                return Err(anyhow::anyhow!(
-                    "unknown notification type: {}",
-                    noti.resource.original_type
-                ));
+            "coupon" => NotificationEvent::Coupon(serde_json::from_slice(&plain)?),
+            "busifavor" => NotificationEvent::BusiFavor(serde_json::from_slice(&plain)?),
+            "payscore" => NotificationEvent::Payscore(serde_json::from_slice(&plain)?),
+            "profitsharing" => NotificationEvent::ProfitSharing(serde_json::from_slice(&plain)?),
+            "mch_payment" => NotificationEvent::Transfer(serde_json::from_slice(&plain)?),
+            _ if noti.event_type.category() == "FAPIAO" => {
+                NotificationEvent::Fapiao(serde_json::from_slice(&plain)?)
             }
+            _ if noti.event_type.category() == "COMPLAINT" => {
+                NotificationEvent::Complaint(serde_json::from_slice(&plain)?)
+            }
+            _ if noti.event_type.category() == "VIOLATION" => {
+                NotificationEvent::Violation(serde_json::from_slice(&plain)?)
+            }
+            _ if noti.event_type.category() == "APPLYMENT_STATE" => {
+                NotificationEvent::ApplymentState(serde_json::from_slice(&plain)?)
+            }
+            _ => NotificationEvent::Other(serde_json::from_slice(&plain)?),
         };
         Ok(event)
     }
+
+    /// 解密微信支付通知，并将通知资源数据解析为 T。
+    pub fn decrypt_notification_resource<T: DeserializeOwned>(
+        &self,
+        noti: &WechatPayNotification,
+    ) -> Result<T> {
+        let plain = self.decrypt_notification_plaintext(noti)?;
+        Ok(serde_json::from_slice(&plain)?)
+    }
+
+    fn decrypt_notification_plaintext(&self, noti: &WechatPayNotification) -> Result<Vec<u8>> {
+        let ciphertext = BASE64_STANDARD
+            .decode(&noti.resource.ciphertext)
+            .map_err(|e| WechatPayError::Decrypt(e.to_string()))?;
+        self.mch_credential().aes_decrypt(
+            &ciphertext,
+            noti.resource.associated_data.as_bytes(),
+            noti.resource.nonce.as_bytes(),
+        )
+    }
+}
+
+/// 可由微信支付通知解密得到的类型。web 框架集成(如 `axum` feature)中使用。
+pub trait FromNotification: Sized {
+    fn from_notification(client: &WechatPayClient, noti: &WechatPayNotification) -> Result<Self>;
+}
+
+impl FromNotification for NotificationEvent {
+    fn from_notification(client: &WechatPayClient, noti: &WechatPayNotification) -> Result<Self> {
+        client.decrypt_notification(noti)
+    }
+}
+
+macro_rules! impl_from_notification {
+    ($($ty:ty),*) => {
+        $(
+            impl FromNotification for $ty {
+                fn from_notification(
+                    client: &WechatPayClient,
+                    noti: &WechatPayNotification,
+                ) -> Result<Self> {
+                    client.decrypt_notification_resource(noti)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_notification!(
+    TradeQueryResponse,
+    RefundNotification,
+    FapiaoNotification,
+    ComplaintNotification,
+    CouponNotification,
+    BusiFavorNotification,
+    PayscoreNotification,
+    ProfitSharingNotification,
+    TransferBillNotification,
+    ViolationNotification,
+    ApplymentStateNotification
+);
+
+/// 对微信支付通知的应答。
+/// 接收成功时，应答 `{"code":"SUCCESS"}`；失败时，应答 `{"code":"FAIL","message":"失败"}`，
+/// 微信支付会在稍后重新发送通知。
+/// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_5.shtml>
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotifyResponse {
+    /// 返回状态码。SUCCESS 为成功，其他(如 FAIL)为失败。
+    pub code: String,
+    /// 返回信息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub message: Option<String>,
+}
+
+impl NotifyResponse {
+    pub fn success() -> NotifyResponse {
+        NotifyResponse {
+            code: "SUCCESS".to_string(),
+            message: None,
+        }
+    }
+
+    pub fn fail(code: &str, message: &str) -> NotifyResponse {
+        NotifyResponse {
+            code: code.to_string(),
+            message: Some(message.to_string()),
+        }
+    }
+
+    /// 根据验签、解密等处理通知时的错误，生成应答及建议的 HTTP 状态码。
+    /// 验签失败、时间戳过期为 401，其他(如解析或解密失败)为 400。
+    /// 重复的通知此前已处理过，应答成功。
+    pub fn from_error(e: &WechatPayError) -> (StatusCode, NotifyResponse) {
+        let status = match e {
+            WechatPayError::DuplicateNotification(_) => {
+                return (StatusCode::OK, NotifyResponse::success())
+            }
+            WechatPayError::Verify(_) | WechatPayError::TimestampExpired(_) => {
+                StatusCode::UNAUTHORIZED
+            }
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, NotifyResponse::fail("FAIL", &e.to_string()))
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.code == "SUCCESS"
+    }
+
+    /// 仅根据 code 确定的 HTTP 状态码：成功为 200，失败为 500。
+    /// 由错误生成应答时，应使用 `from_error` 返回的状态码，它按错误类型区分 401、400 等。
+    pub fn status_code(&self) -> StatusCode {
+        if self.is_success() {
+            StatusCode::OK
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+
+    /// 序列化为 JSON 字符串
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_response() {
+        let res = NotifyResponse::success();
+        assert_eq!(res.to_json(), r#"{"code":"SUCCESS"}"#);
+        assert_eq!(res.status_code(), StatusCode::OK);
+
+        let res = NotifyResponse::fail("FAIL", "失败");
+        assert_eq!(res.to_json(), r#"{"code":"FAIL","message":"失败"}"#);
+        assert_eq!(res.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let (status, res) =
+            NotifyResponse::from_error(&WechatPayError::Verify("bad signature".to_string()));
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(!res.is_success());
+
+        let (status, res) = NotifyResponse::from_error(&WechatPayError::Decrypt("bad".to_string()));
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(res.message.as_deref(), Some("解密失败: bad"));
+
+        let (status, res) = NotifyResponse::from_error(&WechatPayError::DuplicateNotification(
+            "EV-2018022511223320873".to_string(),
+        ));
+        assert_eq!(status, StatusCode::OK);
+        assert!(res.is_success());
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_notification_resource() -> anyhow::Result<()> {
+        use crate::transport::mock_client;
+        use aes_gcm::aead::{Aead, Payload};
+        use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+
+        let api_v3_key = "0".repeat(32);
+        let (client, _) = mock_client().await?;
+
+        let cipher = Aes256Gcm::new_from_slice(api_v3_key.as_bytes()).unwrap();
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(b"fdasflkja484"),
+                Payload {
+                    msg: br#"{"foo":"bar"}"#,
+                    aad: b"transaction",
+                },
+            )
+            .unwrap();
+        let noti: WechatPayNotification = serde_json::from_value(serde_json::json!({
+            "id": "EV-2018022511223320873",
+            "create_time": "2015-05-20T13:29:35+08:00",
+            "event_type": "TRANSACTION.SUCCESS",
+            "resource_type": "encrypt-resource",
+            "resource": {
+                "algorithm": "AEAD_AES_256_GCM",
+                "ciphertext": BASE64_STANDARD.encode(ciphertext),
+                "associated_data": "transaction",
+                "original_type": "transaction",
+                "nonce": "fdasflkja484",
+            },
+            "summary": "支付成功",
+        }))?;
+        let v: serde_json::Value = client.decrypt_notification_resource(&noti)?;
+        assert_eq!(v["foo"], "bar");
+
+        let mut noti = noti;
+        noti.resource.original_type = "unknown".to_string();
+        match client.decrypt_notification(&noti)? {
+            NotificationEvent::Other(v) => assert_eq!(v["foo"], "bar"),
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        let mut headers = HeaderMap::new();
+        let timestamp = Local::now().timestamp() - 600;
+        headers.insert("Wechatpay-Timestamp", timestamp.to_string().parse()?);
+        assert!(matches!(
+            client.check_notification_timestamp(&headers),
+            Err(WechatPayError::TimestampExpired(_))
+        ));
+        headers.insert(
+            "Wechatpay-Timestamp",
+            Local::now().timestamp().to_string().parse()?,
+        );
+        client.check_notification_timestamp(&headers)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_notification() -> anyhow::Result<()> {
+        use crate::dedup::MemoryDedupStore;
+        use crate::transport::{mock_client_with, mock_notification};
+
+        let (client, mock) = mock_client_with(|builder| {
+            builder.notification_dedup_store(MemoryDedupStore::new(16));
+        })
+        .await?;
+        let plain = r#"{"stock_creator_mchid":"1900000001","stock_id":"9856000","coupon_id":"98674556","status":"USED"}"#;
+        let body = mock_notification("EV-1", "COUPON.USE", "coupon", plain);
+        let headers = mock.signature_headers(&body)?;
+
+        // 处理失败时不记录通知 id，重新通知时再次处理
+        let res = client
+            .process_notification(&headers, body.as_bytes(), |_| async {
+                Err::<(), _>(WechatPayError::Other("boom".to_string()))
+            })
+            .await;
+        assert!(matches!(res, Err(WechatPayError::Other(_))));
+
+        let stock_id = client
+            .process_notification(&headers, body.as_bytes(), |event| async move {
+                match event {
+                    NotificationEvent::Coupon(coupon) => Ok(coupon.stock_id),
+                    event => panic!("unexpected event: {:?}", event),
+                }
+            })
+            .await?;
+        assert_eq!(stock_id, "9856000");
+
+        let res = client
+            .process_notification(&headers, body.as_bytes(), |_| async { Ok(()) })
+            .await;
+        assert!(matches!(res, Err(WechatPayError::DuplicateNotification(_))));
+        assert!(matches!(
+            client.handle_notification(&headers, body.as_bytes()).await,
+            Err(WechatPayError::DuplicateNotification(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_notify_event_type_serde() -> anyhow::Result<()> {
+        let t: NotifyEventType = serde_json::from_str(r#""REFUND.ABNORMAL""#)?;
+        assert_eq!(t, NotifyEventType::RefundAbnormal);
+        assert_eq!(t.category(), "REFUND");
+
+        let t: NotifyEventType = serde_json::from_str(r#""VIOLATION.INTERCEPT""#)?;
+        assert_eq!(t, NotifyEventType::ViolationIntercept);
+        assert_eq!(t.category(), "VIOLATION");
+
+        let t: NotifyEventType = serde_json::from_str(r#""APPLYMENT_STATE.CHANGE""#)?;
+        assert_eq!(t, NotifyEventType::ApplymentStateChange);
+        assert_eq!(t.category(), "APPLYMENT_STATE");
+
+        let t: NotifyEventType = serde_json::from_str(r#""MCHTRANSFER.BILL.FINISHED""#)?;
+        assert_eq!(
+            t,
+            NotifyEventType::Other("MCHTRANSFER.BILL.FINISHED".to_string())
+        );
+        assert_eq!(serde_json::to_string(&t)?, r#""MCHTRANSFER.BILL.FINISHED""#);
+        Ok(())
+    }
 }
@@ -0,0 +1,208 @@
+//! 内置的微信支付通知服务器。启用 `notify-server` feature 后可用。
+//!
+//! 适合不想自己搭建 web 框架的场景。收到通知后完成验签、解密，再交由 `NotifyHandler` 处理：
+//! handler 返回 Ok 时应答 204，微信支付不再重复通知；返回 Err 时应答 500，微信支付稍后会重新通知。
+//! 验签失败应答 401，解析或解密失败应答 400。
+//! client 配置了通知去重存储时，通过 `WechatPayClient::process_notification` 处理，
+//! 重复的通知不再交由 handler 处理，直接应答成功。
+//! ```ignore
+//! struct Handler;
+//!
+//! #[async_trait]
+//! impl NotifyHandler for Handler {
+//!     async fn handle(&self, event: NotificationEvent) -> wechatpay::error::Result<()> {
+//!         // ...
+//!         Ok(())
+//!     }
+//! }
+//!
+//! NotifyServer::new(client, Handler).serve("0.0.0.0:8080".parse()?).await?;
+//! ```
+
+use crate::client::WechatPayClient;
+use crate::error::{Result, WechatPayError};
+use crate::notify::{NotificationEvent, NotifyResponse};
+use async_trait::async_trait;
+use http::{header, HeaderValue, Method, Request, Response, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Server};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// 通知处理器
+#[async_trait]
+pub trait NotifyHandler: Send + Sync + 'static {
+    /// 处理验签、解密后的通知。返回 Err 时，微信支付会重新发送通知。
+    async fn handle(&self, event: NotificationEvent) -> Result<()>;
+}
+
+/// 内置的微信支付通知服务器
+#[derive(Debug)]
+pub struct NotifyServer<H> {
+    client: WechatPayClient,
+    handler: Arc<H>,
+}
+
+impl<H: NotifyHandler> NotifyServer<H> {
+    pub fn new(client: WechatPayClient, handler: H) -> Self {
+        NotifyServer {
+            client,
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// 在 addr 上监听并处理通知，直至出错。
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        self.serve_with_shutdown(addr, std::future::pending()).await
+    }
+
+    /// 在 addr 上监听并处理通知，signal 完成时优雅退出。
+    pub async fn serve_with_shutdown<F>(self, addr: SocketAddr, signal: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let client = self.client;
+        let handler = self.handler;
+        let make_svc = make_service_fn(move |_conn| {
+            let client = client.clone();
+            let handler = handler.clone();
+            let svc = service_fn(move |req: Request<Body>| {
+                let client = client.clone();
+                let handler = handler.clone();
+                async move {
+                    if req.method() != Method::POST {
+                        return Ok::<_, Infallible>(response(
+                            StatusCode::METHOD_NOT_ALLOWED,
+                            "method not allowed",
+                        ));
+                    }
+                    Ok(handle(&client, handler.as_ref(), req).await)
+                }
+            });
+            async move { Ok::<_, Infallible>(svc) }
+        });
+
+        Server::try_bind(&addr)
+            .map_err(|e| WechatPayError::Other(e.to_string()))?
+            .serve(make_svc)
+            .with_graceful_shutdown(signal)
+            .await
+            .map_err(|e| WechatPayError::Other(e.to_string()))
+    }
+}
+
+async fn handle<H: NotifyHandler>(
+    client: &WechatPayClient,
+    handler: &H,
+    req: Request<Body>,
+) -> Response<Body> {
+    let (parts, body) = req.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => return response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+
+    let mut handled = false;
+    let result = client
+        .process_notification(&parts.headers, &body, |event| {
+            handled = true;
+            handler.handle(event)
+        })
+        .await;
+    match result {
+        Ok(()) => {
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = StatusCode::NO_CONTENT;
+            res
+        }
+        Err(e) if handled => response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        Err(e) => {
+            let (status, body) = NotifyResponse::from_error(&e);
+            json_response(status, &body)
+        }
+    }
+}
+
+fn response(status: StatusCode, message: &str) -> Response<Body> {
+    json_response(status, &NotifyResponse::fail("FAIL", message))
+}
+
+fn json_response(status: StatusCode, body: &NotifyResponse) -> Response<Body> {
+    let mut res = Response::new(Body::from(body.to_json()));
+    *res.status_mut() = status;
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::MemoryDedupStore;
+    use crate::transport::{mock_client_with, mock_notification};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Handler(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl NotifyHandler for Handler {
+        async fn handle(&self, event: NotificationEvent) -> Result<()> {
+            match event {
+                NotificationEvent::Coupon(coupon) => self.0.lock().unwrap().push(coupon.stock_id),
+                event => panic!("unexpected event: {:?}", event),
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notify_server() -> anyhow::Result<()> {
+        let (client, mock) = mock_client_with(|builder| {
+            builder.notification_dedup_store(MemoryDedupStore::new(16));
+        })
+        .await?;
+        let handler = Handler::default();
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(
+            NotifyServer::new(client, handler.clone()).serve_with_shutdown(addr, async {
+                rx.await.ok();
+            }),
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let http = reqwest::Client::new();
+        let url = format!("http://{}/notify", addr);
+        let res = http.post(&url).body("{}").send().await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = http.get(&url).send().await?;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let plain = r#"{"stock_creator_mchid":"1900000001","stock_id":"9856000","coupon_id":"98674556","status":"USED"}"#;
+        let body = mock_notification("EV-1", "COUPON.USE", "coupon", plain);
+        let headers = mock.signature_headers(&body)?;
+        let res = http
+            .post(&url)
+            .headers(headers.clone())
+            .body(body.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(*handler.0.lock().unwrap(), ["9856000"]);
+        // 重复的通知应答成功，不再交由 handler 处理
+        let res = http.post(&url).headers(headers).body(body).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await?, r#"{"code":"SUCCESS"}"#);
+        assert_eq!(handler.0.lock().unwrap().len(), 1);
+
+        tx.send(()).ok();
+        server.await??;
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+//! 分页接口的统一抽象。
+//! 微信支付的列表接口大多使用 offset/limit 分页，`Paginated` 负责翻页，
+//! 通过 `into_stream` 将所有分页的数据依次产出。
+
+use crate::error::Result;
+use futures::future::BoxFuture;
+use futures::stream::{self, Stream};
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+
+/// 一页数据
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    /// 本页的数据
+    pub items: Vec<T>,
+    /// 数据总数。接口未返回总数时为 None，此时以本页数据不足 limit 条作为结束条件。
+    pub total_count: Option<u32>,
+}
+
+type FetchPage<T> = Box<dyn FnMut(u32, u32) -> BoxFuture<'static, Result<Page<T>>> + Send>;
+
+/// offset/limit 分页的列表。
+pub struct Paginated<T> {
+    offset: u32,
+    limit: u32,
+    fetch: FetchPage<T>,
+}
+
+impl<T> fmt::Debug for Paginated<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Paginated")
+            .field("offset", &self.offset)
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+impl<T: Send + 'static> Paginated<T> {
+    /// fetch 接收 (offset, limit) 两个参数，返回对应的一页数据。
+    pub fn new<F, Fut>(limit: u32, mut fetch: F) -> Paginated<T>
+    where
+        F: FnMut(u32, u32) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Page<T>>> + Send + 'static,
+    {
+        Paginated {
+            offset: 0,
+            limit: limit.max(1),
+            fetch: Box::new(move |offset, limit| Box::pin(fetch(offset, limit))),
+        }
+    }
+
+    /// 从指定的位置开始翻页，默认为 0。
+    pub fn offset(mut self, offset: u32) -> Paginated<T> {
+        self.offset = offset;
+        self
+    }
+
+    /// 自动翻页，依次产出每条数据。
+    /// 获取某一页失败时，产出该错误后结束。
+    pub fn into_stream(self) -> impl Stream<Item = Result<T>> + Send {
+        struct State<T> {
+            paginated: Paginated<T>,
+            buffer: VecDeque<T>,
+            done: bool,
+        }
+
+        let state = State {
+            paginated: self,
+            buffer: VecDeque::new(),
+            done: false,
+        };
+
+        stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(item) = state.buffer.pop_front() {
+                    return Some((Ok(item), state));
+                }
+                if state.done {
+                    return None;
+                }
+
+                let Paginated {
+                    offset,
+                    limit,
+                    fetch,
+                } = &mut state.paginated;
+                match fetch(*offset, *limit).await {
+                    Ok(page) => {
+                        let n = page.items.len() as u32;
+                        *offset += n;
+                        state.done = match page.total_count {
+                            Some(total_count) => n == 0 || *offset >= total_count,
+                            None => n < *limit,
+                        };
+                        state.buffer.extend(page.items);
+                    }
+                    Err(e) => {
+                        state.done = true;
+                        return Some((Err(e), state));
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WechatPayError;
+    use futures::StreamExt;
+    use futures::TryStreamExt;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_paginated() -> anyhow::Result<()> {
+        let data: Vec<u32> = (0..7).collect();
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let paginated = {
+            let data = data.clone();
+            let requests = requests.clone();
+            Paginated::new(3, move |offset, limit| {
+                requests.lock().unwrap().push((offset, limit));
+                let items = data
+                    .iter()
+                    .skip(offset as usize)
+                    .take(limit as usize)
+                    .copied()
+                    .collect();
+                async move {
+                    Ok(Page {
+                        items,
+                        total_count: Some(7),
+                    })
+                }
+            })
+        };
+        let items: Vec<u32> = paginated.into_stream().try_collect().await?;
+        assert_eq!(items, data);
+        assert_eq!(*requests.lock().unwrap(), [(0, 3), (3, 3), (6, 3)]);
+
+        // 无总数时，以不足一页作为结束
+        let paginated = Paginated::new(3, move |offset, _| async move {
+            Ok(Page {
+                items: (offset..6.min(offset + 3)).collect(),
+                total_count: None,
+            })
+        });
+        let items: Vec<u32> = paginated.offset(2).into_stream().try_collect().await?;
+        assert_eq!(items, [2, 3, 4, 5]);
+
+        // 出错后结束
+        let paginated = Paginated::new(2, move |offset, _| async move {
+            if offset == 0 {
+                Ok(Page {
+                    items: vec![0, 1],
+                    total_count: Some(4),
+                })
+            } else {
+                Err(WechatPayError::Other("boom".to_string()))
+            }
+        });
+        let items: Vec<Result<u32>> = paginated.into_stream().collect().await;
+        assert_eq!(items.len(), 3);
+        assert!(items[2].is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+//! 委托代扣(周期扣款)。
+//! 用户与商户签约后，商户可按扣费计划(plan)定期从用户账户扣款，适用于会员订阅自动续费等场景。
+//! 签约有两种方式：纯签约(`papay_pre_entrust_sign`)及支付中签约(`papay_pay_and_sign`)。
+//! 签约、解约结果通过 `ContractNotification` 通知，扣款结果通过 `PapayTradeNotification` 通知。
+
+use crate::client::WechatPayClient;
+use crate::error::{Result, WechatPayError};
+use crate::ids::OutTradeNo;
+use crate::notify::{FromNotification, WechatPayNotification};
+use crate::trade::{Amount, CreateTradeSceneInfo, Payer, TradeQueryResponse};
+use crate::util::{datetime_fmt, option_datetime_fmt};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+impl WechatPayClient {
+    /// 纯签约：获取签约跳转参数。用户在微信内确认签约后，微信支付发送签约通知。
+    /// APP 签约返回 pre_entrustweb_id，其他渠道返回跳转的 redirect_url。
+    pub async fn papay_pre_entrust_sign(
+        &self,
+        channel: PapayChannel,
+        params: &PapayPreEntrustSignParams,
+    ) -> Result<PapayPreEntrustSignResponse> {
+        if channel == PapayChannel::Native {
+            return Err(WechatPayError::InvalidParams(
+                "papay pre-entrust sign does not support NATIVE".to_string(),
+            ));
+        }
+        let url = format!(
+            "{}/papay/sign/contracts/pre-entrust-sign/{}",
+            self.base_url,
+            channel.path()
+        );
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 支付中签约：下单的同时发起签约，用户支付成功后即完成签约。
+    /// 返回结果同普通下单，按渠道分别带有 prepay_id、code_url 或 h5_url。
+    pub async fn papay_pay_and_sign(
+        &self,
+        channel: PapayChannel,
+        params: &PapayPayAndSignParams,
+    ) -> Result<PapayPayAndSignResponse> {
+        let url = format!(
+            "{}/papay/pay-and-sign/transactions/{}",
+            self.base_url,
+            channel.path()
+        );
+        let req = self.client.post(url).json(params).build()?;
+        let res = self.execute_idempotent(req).await?;
+        Ok(res.json().await?)
+    }
+
+    /// 通过委托代扣协议 ID 查询签约关系。
+    pub async fn query_contract_by_id(&self, app_id: &str, contract_id: &str) -> Result<Contract> {
+        let url = format!(
+            "{}/papay/sign/contracts/contract-id/{}?appid={}",
+            self.base_url, contract_id, app_id
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 通过扣费计划 ID 及商户签约协议号查询签约关系。
+    pub async fn query_contract_by_out_contract_code(
+        &self,
+        app_id: &str,
+        plan_id: i64,
+        out_contract_code: &str,
+    ) -> Result<Contract> {
+        let url = format!(
+            "{}/papay/sign/contracts/plan-id/{}/out-contract-code/{}?appid={}",
+            self.base_url, plan_id, out_contract_code, app_id
+        );
+        let req = self.client.get(url).build()?;
+        self.execute_json(req).await
+    }
+
+    /// 解约。解约成功后，微信支付同样会发送解约通知。
+    pub async fn terminate_contract(
+        &self,
+        contract_id: &str,
+        params: &TerminateContractParams,
+    ) -> Result<()> {
+        let url = format!(
+            "{}/papay/sign/contracts/contract-id/{}/terminate",
+            self.base_url, contract_id
+        );
+        let req = self.client.post(url).json(params).build()?;
+        self.execute_idempotent(req).await?;
+        Ok(())
+    }
+
+    /// 申请扣款。扣款为异步受理，结果通过 `PapayTradeNotification` 通知，
+    /// 也可通过 `query_trade_by_out_trade_no` 查询。
+    pub async fn papay_apply(&self, params: &PapayApplyParams) -> Result<()> {
+        let url = format!("{}/papay/pay/transactions/apply", self.base_url);
+        let req = self.client.post(url).json(params).build()?;
+        self.execute_idempotent(req).await?;
+        Ok(())
+    }
+}
+
+/// 签约渠道
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PapayChannel {
+    /// 公众号
+    JsApi,
+    /// 小程序
+    MiniProgram,
+    App,
+    H5,
+    /// 扫码，仅支持支付中签约
+    Native,
+}
+
+impl PapayChannel {
+    fn path(&self) -> &'static str {
+        match self {
+            PapayChannel::JsApi => "jsapi",
+            PapayChannel::MiniProgram => "mini-program",
+            PapayChannel::App => "app",
+            PapayChannel::H5 => "h5",
+            PapayChannel::Native => "native",
+        }
+    }
+}
+
+/// 纯签约的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PapayPreEntrustSignParams {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 扣费计划 ID，在商户平台配置
+    pub plan_id: i64,
+    /// 商户签约协议号，商户侧唯一
+    pub out_contract_code: String,
+    /// 签约用户的名称，用于页面展示，如会员账号
+    pub contract_display_account: String,
+    /// 签约、解约结果通知地址
+    pub notify_url: String,
+    /// 用户标识。公众号、小程序签约时可传入，以校验签约用户。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub openid: Option<String>,
+    /// 签约完成后的跳转地址，H5、公众号签约时有效
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub return_url: Option<String>,
+}
+
+/// 纯签约的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct PapayPreEntrustSignResponse {
+    /// 预签约 ID，APP 签约时返回，用于拉起签约页面
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub pre_entrustweb_id: Option<String>,
+    /// 签约跳转地址，公众号、小程序、H5 签约时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub redirect_url: Option<String>,
+}
+
+/// 支付中签约的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PapayPayAndSignParams {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 商户号
+    #[serde(rename = "mchid")]
+    pub mch_id: String,
+    /// 商品描述
+    pub description: String,
+    /// 商户订单号
+    pub out_trade_no: OutTradeNo,
+    /// 附加数据
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub attach: Option<String>,
+    /// 支付结果通知地址
+    pub notify_url: String,
+    /// 订单金额
+    pub amount: Amount,
+    /// 支付者。公众号、小程序签约时必填。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub payer: Option<Payer>,
+    /// 场景信息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scene_info: Option<CreateTradeSceneInfo>,
+    /// 签约信息
+    pub contract_info: ContractInfo,
+}
+
+/// 支付中签约的签约信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractInfo {
+    /// 扣费计划 ID
+    pub plan_id: i64,
+    /// 商户签约协议号
+    pub out_contract_code: String,
+    /// 签约用户的名称
+    pub contract_display_account: String,
+    /// 签约、解约结果通知地址
+    pub contract_notify_url: String,
+}
+
+/// 支付中签约的结果。仅与签约渠道对应的字段有值。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct PapayPayAndSignResponse {
+    /// 公众号、小程序、APP 签约时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub prepay_id: Option<String>,
+    /// 扫码签约时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub code_url: Option<String>,
+    /// H5 签约时返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub h5_url: Option<String>,
+}
+
+/// 签约关系
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Contract {
+    /// 委托代扣协议 ID
+    pub contract_id: String,
+    /// 商户号
+    pub mchid: String,
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 扣费计划 ID
+    pub plan_id: i64,
+    /// 商户签约协议号
+    pub out_contract_code: String,
+    /// 签约用户标识
+    pub openid: String,
+    /// 签约用户的名称
+    pub contract_display_account: String,
+    /// 协议状态
+    /// * SIGNED：已签约
+    /// * TERMINATED：已解约
+    pub contract_state: String,
+    /// 签约时间
+    #[serde(with = "datetime_fmt")]
+    pub contract_signed_time: DateTime<Local>,
+    /// 协议到期时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub contract_expired_time: Option<DateTime<Local>>,
+    /// 解约时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub contract_terminated_time: Option<DateTime<Local>>,
+    /// 解约方式
+    /// * USER：用户解约
+    /// * MCH：商户解约
+    /// * PLATFORM：平台解约
+    /// * EXPIRE：协议到期
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub contract_termination_mode: Option<String>,
+    /// 解约备注
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub contract_termination_remark: Option<String>,
+}
+
+/// 解约的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminateContractParams {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 扣费计划 ID
+    pub plan_id: i64,
+    /// 解约备注，不超过 256 字符
+    pub contract_termination_remark: String,
+}
+
+/// 申请扣款的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PapayApplyParams {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 商户号
+    #[serde(rename = "mchid")]
+    pub mch_id: String,
+    /// 商品描述
+    pub description: String,
+    /// 商户订单号
+    pub out_trade_no: OutTradeNo,
+    /// 附加数据
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub attach: Option<String>,
+    /// 扣款结果通知地址
+    pub notify_url: String,
+    /// 订单优惠标记
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub goods_tag: Option<String>,
+    /// 委托代扣协议 ID
+    pub contract_id: String,
+    /// 扣款金额
+    pub amount: Amount,
+}
+
+/// 签约、解约结果通知解密后的资源数据。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ContractNotification {
+    /// 商户号
+    pub mchid: String,
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 委托代扣协议 ID
+    pub contract_id: String,
+    /// 扣费计划 ID
+    pub plan_id: i64,
+    /// 商户签约协议号
+    pub out_contract_code: String,
+    /// 签约用户标识
+    pub openid: String,
+    /// 签约用户的名称
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub contract_display_account: Option<String>,
+    /// 变更类型
+    /// * ADD：签约
+    /// * DELETE：解约
+    pub change_type: String,
+    /// 操作时间
+    #[serde(with = "datetime_fmt")]
+    pub operate_time: DateTime<Local>,
+    /// 协议到期时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub contract_expired_time: Option<DateTime<Local>>,
+    /// 解约方式，解约时返回。取值同 `Contract::contract_termination_mode`。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub contract_termination_mode: Option<String>,
+}
+
+/// 扣款结果通知解密后的资源数据。在普通支付通知的基础上，带有委托代扣协议 ID。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct PapayTradeNotification {
+    /// 委托代扣协议 ID
+    pub contract_id: String,
+    #[serde(flatten)]
+    pub trade: TradeQueryResponse,
+}
+
+impl FromNotification for ContractNotification {
+    fn from_notification(client: &WechatPayClient, noti: &WechatPayNotification) -> Result<Self> {
+        client.decrypt_notification_resource(noti)
+    }
+}
+
+impl FromNotification for PapayTradeNotification {
+    fn from_notification(client: &WechatPayClient, noti: &WechatPayNotification) -> Result<Self> {
+        client.decrypt_notification_resource(noti)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trade::TradeState;
+
+    #[test]
+    fn test_papay_trade_notification() -> anyhow::Result<()> {
+        let noti: PapayTradeNotification = serde_json::from_str(
+            r#"{
+                "appid": "wxd678efh567hg6787",
+                "mchid": "1230000109",
+                "out_trade_no": "1217752501201407033233368018",
+                "transaction_id": "1217752501201407033233368018",
+                "trade_type": "PAP",
+                "trade_state": "SUCCESS",
+                "trade_state_desc": "支付成功",
+                "success_time": "2018-06-08T10:34:56+08:00",
+                "contract_id": "Wx15463511252015071056489715",
+                "amount": {"total": 1000, "currency": "CNY"}
+            }"#,
+        )?;
+        assert_eq!(noti.contract_id, "Wx15463511252015071056489715");
+        assert_eq!(noti.trade.trade_state, TradeState::Success);
+        assert_eq!(noti.trade.trade_type.unwrap().as_str(), "PAP");
+
+        let noti: ContractNotification = serde_json::from_str(
+            r#"{
+                "mchid": "1230000109",
+                "appid": "wxd678efh567hg6787",
+                "contract_id": "Wx15463511252015071056489715",
+                "plan_id": 12535,
+                "out_contract_code": "100001256",
+                "openid": "oUpF8uMuAJO_M2pxb1Q9zNjWeS6o",
+                "change_type": "DELETE",
+                "operate_time": "2018-06-08T10:34:56+08:00",
+                "contract_termination_mode": "USER"
+            }"#,
+        )?;
+        assert_eq!(noti.change_type, "DELETE");
+        assert_eq!(noti.contract_termination_mode.as_deref(), Some("USER"));
+        Ok(())
+    }
+}
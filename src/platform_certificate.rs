@@ -1,24 +1,38 @@
-//! 微信支付平台证书。
+//! 微信支付平台证书及微信支付公钥。
+//! 平台证书的解析、获取及持久化需启用 `x509` feature；仅使用微信支付公钥验签时无需启用。
 
-use crate::client::{BASE_URL, USER_AGENT};
-use crate::credential::MchCredential;
-use crate::util::datetime_fmt;
-use anyhow::Result;
+use crate::error::{Result, WechatPayError};
+use crate::sensitive::rsa_oaep_encrypt;
+use crate::transport::BufferedResponse;
 use base64::prelude::*;
 use bytes::{BufMut, BytesMut};
-use chrono::{DateTime, Local};
-use reqwest::{Client, Response};
-use rsa::pkcs1::DecodeRsaPublicKey;
+use reqwest::header::HeaderMap;
+use reqwest::Response;
 use rsa::pkcs1v15::{Signature, VerifyingKey};
 use rsa::sha2::Sha256;
 use rsa::signature::Verifier;
 use rsa::RsaPublicKey;
-use serde::Deserialize;
-use std::cmp::Reverse;
-use x509_cert::der::DecodePem;
-use x509_cert::Certificate;
+#[cfg(feature = "x509")]
+use {
+    crate::client::{BASE_URL, USER_AGENT},
+    crate::clock::{Clock, SystemClock},
+    crate::credential::{MchCredential, RandomNonce, RequestSigner},
+    crate::transport::Transport,
+    crate::util::datetime_fmt,
+    chrono::{DateTime, Local},
+    reqwest::Client,
+    rsa::pkcs1::DecodeRsaPublicKey,
+    serde::{Deserialize, Serialize},
+    std::cmp::Reverse,
+    std::path::Path,
+    std::time::Instant,
+    x509_cert::der::pem::LineEnding,
+    x509_cert::der::{DecodePem, EncodePem},
+    x509_cert::Certificate,
+};
 
 /// 微信支付平台证书。
+#[cfg(feature = "x509")]
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PlatformCertificate {
     pub serial_no: String,
@@ -27,6 +41,7 @@ pub struct PlatformCertificate {
     pub certificate: Certificate,
 }
 
+#[cfg(feature = "x509")]
 impl PlatformCertificate {
     pub fn public_key(&self) -> Result<RsaPublicKey> {
         let bytes = self
@@ -36,21 +51,162 @@ impl PlatformCertificate {
             .subject_public_key
             .raw_bytes();
 
-        RsaPublicKey::from_pkcs1_der(bytes)
-            .map_err(|e| anyhow::format_err!("failed to get public key from ca, err: {}", e))
+        RsaPublicKey::from_pkcs1_der(bytes).map_err(|e| {
+            WechatPayError::Certificate(format!("failed to get public key from ca, err: {}", e))
+        })
+    }
+
+    /// 对响应的 header 及原始 body 进行验签，参见 `verify_response_parts`。
+    pub fn verify_response_parts(&self, headers: &HeaderMap, body: &[u8]) -> Result<()> {
+        verify_response_parts(&self.public_key()?, headers, body)
+    }
+
+    /// 验证签名，参见 `verify_signature`。
+    pub fn verify_signature(
+        &self,
+        timestamp: &str,
+        nonce: &str,
+        body: &[u8],
+        signature: &str,
+    ) -> Result<()> {
+        verify_signature(&self.public_key()?, timestamp, nonce, body, signature)
+    }
+
+    /// 使用 RSAES-OAEP 加密敏感信息，返回 base64 编码的密文。
+    /// 请求中包含加密字段时，须将 Wechatpay-Serial header 设置为此证书的序列号。
+    pub fn encrypt_sensitive(&self, plaintext: &str) -> Result<String> {
+        rsa_oaep_encrypt(&self.public_key()?, plaintext)
+    }
+
+    /// 将证书编码为 PEM 格式。
+    pub fn certificate_pem(&self) -> Result<String> {
+        self.certificate
+            .to_pem(LineEnding::LF)
+            .map_err(|e| WechatPayError::Certificate(e.to_string()))
+    }
+}
+
+/// 平台证书的持久化格式：证书的 PEM 及元数据。
+#[cfg(feature = "x509")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersistedPlatformCertificate {
+    /// 证书序列号
+    pub serial_no: String,
+    /// 证书启用时间
+    #[serde(with = "datetime_fmt")]
+    pub effective_time: DateTime<Local>,
+    /// 证书弃用时间
+    #[serde(with = "datetime_fmt")]
+    pub expire_time: DateTime<Local>,
+    /// PEM 格式的证书
+    pub certificate: String,
+}
+
+#[cfg(feature = "x509")]
+impl TryFrom<&PlatformCertificate> for PersistedPlatformCertificate {
+    type Error = WechatPayError;
+
+    fn try_from(c: &PlatformCertificate) -> Result<Self> {
+        Ok(PersistedPlatformCertificate {
+            serial_no: c.serial_no.clone(),
+            effective_time: c.effective_time,
+            expire_time: c.expire_time,
+            certificate: c.certificate_pem()?,
+        })
     }
+}
+
+#[cfg(feature = "x509")]
+impl TryFrom<PersistedPlatformCertificate> for PlatformCertificate {
+    type Error = WechatPayError;
 
-    /// 对响应进行数字签名验证。
-    pub(crate) async fn verify_response(&self, res: Response) -> Result<Response> {
-        let public_key = self.public_key()?;
-        let res = verify_response(&public_key, res).await?;
-        Ok(res)
+    fn try_from(c: PersistedPlatformCertificate) -> Result<Self> {
+        Ok(PlatformCertificate {
+            serial_no: c.serial_no,
+            effective_time: c.effective_time,
+            expire_time: c.expire_time,
+            certificate: Certificate::from_pem(c.certificate.as_bytes())
+                .map_err(|e| WechatPayError::Certificate(e.to_string()))?,
+        })
     }
+}
+
+/// 将平台证书列表保存到文件(JSON 格式)。
+/// 进程重启时可通过 `load_platform_certificates` 加载，不必每次都调用证书接口。
+#[cfg(feature = "x509")]
+pub fn save_platform_certificates<P: AsRef<Path>>(
+    path: P,
+    certificates: &[PlatformCertificate],
+) -> Result<()> {
+    let certificates = certificates
+        .iter()
+        .map(PersistedPlatformCertificate::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    let content = serde_json::to_vec_pretty(&certificates)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// 从文件(JSON 格式)加载平台证书列表。文件由 `save_platform_certificates` 生成。
+#[cfg(feature = "x509")]
+pub fn load_platform_certificates<P: AsRef<Path>>(path: P) -> Result<Vec<PlatformCertificate>> {
+    let content = std::fs::read(path)?;
+    let certificates: Vec<PersistedPlatformCertificate> = serde_json::from_slice(&content)?;
+    certificates
+        .into_iter()
+        .map(PlatformCertificate::try_from)
+        .collect()
+}
+
+/// 微信支付公钥 ID 的前缀。
+/// 使用微信支付公钥验签时，响应的 Wechatpay-Serial 为公钥 ID，形如 `PUB_KEY_ID_0114232134912410000000000000`。
+pub const WECHATPAY_PUBLIC_KEY_ID_PREFIX: &str = "PUB_KEY_ID_";
 
-    // TODO: 定义一个 RSA 加密方法，用于对敏感信息进行加密
+/// 微信支付公钥。
+/// 新申请的商户使用微信支付公钥(而非平台证书)进行验签与敏感信息加密。
+/// 公钥及公钥 ID 可在商户平台下载，公钥文件一般为 `pub_key.pem`，可通过
+/// `rsa::pkcs8::DecodePublicKey::from_public_key_pem` 解析。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WechatPayPublicKey {
+    /// 公钥 ID，形如 `PUB_KEY_ID_xxx`
+    pub public_key_id: String,
+    /// 公钥
+    pub public_key: RsaPublicKey,
+}
+
+impl WechatPayPublicKey {
+    pub fn new(public_key_id: String, public_key: RsaPublicKey) -> WechatPayPublicKey {
+        WechatPayPublicKey {
+            public_key_id,
+            public_key,
+        }
+    }
+
+    /// 对响应的 header 及原始 body 进行验签，参见 `verify_response_parts`。
+    pub fn verify_response_parts(&self, headers: &HeaderMap, body: &[u8]) -> Result<()> {
+        verify_response_parts(&self.public_key, headers, body)
+    }
+
+    /// 验证签名，参见 `verify_signature`。
+    pub fn verify_signature(
+        &self,
+        timestamp: &str,
+        nonce: &str,
+        body: &[u8],
+        signature: &str,
+    ) -> Result<()> {
+        verify_signature(&self.public_key, timestamp, nonce, body, signature)
+    }
+
+    /// 使用 RSAES-OAEP 加密敏感信息，返回 base64 编码的密文。
+    /// 请求中包含加密字段时，须将 Wechatpay-Serial header 设置为公钥 ID。
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        rsa_oaep_encrypt(&self.public_key, plaintext)
+    }
 }
 
 /// 微信支付平台证书状态。
+#[cfg(feature = "x509")]
 #[derive(Debug, Clone)]
 pub struct PlatformCertificateState {
     /// 证书列表
@@ -58,8 +214,11 @@ pub struct PlatformCertificateState {
     /// 最新的证书索引
     #[allow(unused)]
     newest_certificate_idx: usize,
+    /// 证书列表的更新时间
+    updated_at: Instant,
 }
 
+#[cfg(feature = "x509")]
 impl PlatformCertificateState {
     pub fn new(certificates: Vec<PlatformCertificate>) -> Result<Self> {
         let now = Local::now();
@@ -68,15 +227,27 @@ impl PlatformCertificateState {
             .filter(|c| now < c.expire_time)
             .collect();
         if certificates.is_empty() {
-            return Err(anyhow::format_err!("no available certificates found"));
+            return Err(WechatPayError::Certificate(
+                "no available certificates found".to_string(),
+            ));
         }
         certificates.sort_by_key(|c| Reverse(c.effective_time));
         Ok(PlatformCertificateState {
             certificates,
             newest_certificate_idx: 0,
+            updated_at: Instant::now(),
         })
     }
 
+    /// 空的证书列表。仅使用微信支付公钥验签时使用。
+    pub(crate) fn empty() -> Self {
+        PlatformCertificateState {
+            certificates: vec![],
+            newest_certificate_idx: 0,
+            updated_at: Instant::now(),
+        }
+    }
+
     /// 根据 serial_no 获取平台证书。
     pub fn get_platform_certificate(&self, serial_no: &str) -> Result<PlatformCertificate> {
         let certificate = self
@@ -84,69 +255,136 @@ impl PlatformCertificateState {
             .iter()
             .find(|c| c.serial_no == serial_no)
             .ok_or_else(|| {
-                anyhow::format_err!("no certificate found for serial_no: {}", serial_no)
+                WechatPayError::Certificate(format!(
+                    "no certificate found for serial_no: {}",
+                    serial_no
+                ))
             })?
             .clone();
         Ok(certificate)
     }
 
+    /// 已启用的证书中最新的一个。没有已启用的证书时，返回最新的证书。
+    /// 敏感信息加密时使用。
+    pub fn newest_certificate(&self) -> Option<&PlatformCertificate> {
+        let now = Local::now();
+        self.certificates
+            .iter()
+            .find(|c| c.effective_time <= now)
+            .or_else(|| self.certificates.first())
+    }
+
     /// 平台证书列表
     pub fn certificates(&self) -> &Vec<PlatformCertificate> {
         &self.certificates
     }
+
+    /// 证书列表的更新时间
+    pub fn updated_at(&self) -> Instant {
+        self.updated_at
+    }
+
+    /// 将证书列表保存到文件。参见 `save_platform_certificates`。
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        save_platform_certificates(path, &self.certificates)
+    }
+
+    /// 从文件加载证书列表，已过期的证书会被忽略。参见 `load_platform_certificates`。
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        PlatformCertificateState::new(load_platform_certificates(path)?)
+    }
 }
 
 /// 响应签名验证器: 对响应进行数字签名验证。
 /// 验证响应的签名。
 /// <https://pay.weixin.qq.com/wiki/doc/apiv3/wechatpay/wechatpay4_1.shtml>
+/// 返回的 Response 的 body 已读入内存。
 pub async fn verify_response(public_key: &RsaPublicKey, res: Response) -> Result<Response> {
-    // 需要这个 builder 重新构建一个 Response 并返回。
-    let mut builder = http::Response::builder()
-        .status(res.status())
-        .version(res.version());
-    for (key, value) in res.headers() {
-        builder = builder.header(key, value);
-    }
-
-    let signature = res
-        .headers()
-        .get("Wechatpay-Signature")
-        .ok_or_else(|| anyhow::format_err!("missing `Wechatpay-Signature` header"))?
-        .to_str()?;
-    let signature = BASE64_STANDARD.decode(signature.as_bytes())?;
-
-    let timestamp = res
-        .headers()
-        .get("Wechatpay-Timestamp")
-        .ok_or_else(|| anyhow::format_err!("missing `Wechatpay-Timestamp` header"))?
-        .to_str()?;
-    let nonce_str = res
-        .headers()
-        .get("Wechatpay-Nonce")
-        .ok_or_else(|| anyhow::format_err!("missing `Wechatpay-Nonce` header"))?
-        .to_str()?;
+    let res = BufferedResponse::read(res, None).await?;
+    verify_response_parts(public_key, &res.headers, &res.body)?;
+    Ok(res.into())
+}
+
+/// 对响应的 header 及原始 body 进行验签。
+/// 验签所需的信息取自 Wechatpay-Timestamp、Wechatpay-Nonce、Wechatpay-Signature header。
+/// 不依赖 reqwest，使用 hyper、isahc 等其他 HTTP 客户端时，也可以此验证微信支付的响应或通知。
+pub fn verify_response_parts(
+    public_key: &RsaPublicKey,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<()> {
+    let header = |key: &str| -> Result<&str> {
+        headers
+            .get(key)
+            .ok_or_else(|| WechatPayError::Verify(format!("missing `{}` header", key)))?
+            .to_str()
+            .map_err(|e| WechatPayError::Verify(e.to_string()))
+    };
+    verify_signature(
+        public_key,
+        header("Wechatpay-Timestamp")?,
+        header("Wechatpay-Nonce")?,
+        body,
+        header("Wechatpay-Signature")?,
+    )
+}
+
+/// 验证签名。signature 为 base64 编码的签名(即 Wechatpay-Signature header)。
+/// 验签串为 `{timestamp}\n{nonce}\n{body}\n`。同步函数，可在任意上下文中使用。
+pub fn verify_signature(
+    public_key: &RsaPublicKey,
+    timestamp: &str,
+    nonce: &str,
+    body: &[u8],
+    signature: &str,
+) -> Result<()> {
+    let signature = BASE64_STANDARD
+        .decode(signature.as_bytes())
+        .map_err(|e| WechatPayError::Verify(e.to_string()))?;
 
     let mut msg = BytesMut::new();
     msg.put_slice(timestamp.as_bytes());
     msg.put_u8(b'\n');
-    msg.put_slice(nonce_str.as_bytes());
+    msg.put_slice(nonce.as_bytes());
     msg.put_u8(b'\n');
-    let body = res.text().await?;
-    msg.put_slice(body.as_bytes());
+    msg.put_slice(body);
     msg.put_u8(b'\n');
 
     let verifying_key = VerifyingKey::<Sha256>::new(public_key.clone());
-    let signature = Signature::try_from(signature.as_slice())?;
-    verifying_key.verify(&msg, &signature)?;
-
-    let new_res = builder.body(body)?;
-    Ok(new_res.into())
+    let signature = Signature::try_from(signature.as_slice())
+        .map_err(|e| WechatPayError::Verify(e.to_string()))?;
+    verifying_key
+        .verify(&msg, &signature)
+        .map_err(|e| WechatPayError::Verify(e.to_string()))
 }
 
 /// 获取微信支付平台证书。
 /// 此接口与其他接口不同。收到响应时，需要先处理响应，后进行验签。因此单独实现。
+#[cfg(feature = "x509")]
 pub async fn get_platform_certificates(
     mch_credential: &MchCredential,
+) -> Result<Vec<PlatformCertificate>> {
+    let client = Client::builder().user_agent(USER_AGENT).build()?;
+    get_platform_certificates_with_transport(
+        &client,
+        &client,
+        BASE_URL,
+        mch_credential,
+        None,
+        &SystemClock,
+    )
+    .await
+}
+
+/// 使用指定的传输层获取微信支付平台证书。client 仅用于构建请求。
+#[cfg(feature = "x509")]
+pub(crate) async fn get_platform_certificates_with_transport(
+    client: &Client,
+    transport: &dyn Transport,
+    base_url: &str,
+    mch_credential: &MchCredential,
+    signer: Option<&dyn RequestSigner>,
+    clock: &dyn Clock,
 ) -> Result<Vec<PlatformCertificate>> {
     #[derive(Deserialize)]
     struct EncryptedCertificate {
@@ -172,37 +410,31 @@ pub async fn get_platform_certificates(
         data: Vec<PlatformCertificateItem>,
     }
 
-    let client = Client::new();
-    let url = format!("{}/certificates", BASE_URL);
+    let url = format!("{}/certificates", base_url);
     let mut req = client.get(&url).build()?;
     req.headers_mut()
         .append("Accept", "application/json".parse().unwrap());
-    req.headers_mut()
-        .append("User-Agent", USER_AGENT.parse().unwrap());
 
-    let req = mch_credential.sign_request(req)?;
-    let res = client.execute(req).await?;
+    let signer = signer.unwrap_or(mch_credential);
+    let req = mch_credential
+        .sign_request_with_signer(req, None, signer, clock, &RandomNonce)
+        .await?;
+    let res = BufferedResponse::read(transport.send(req).await?, None).await?;
 
     // 用于验签的 serial_no
     let serial_no = res
-        .headers()
+        .headers
         .get("Wechatpay-Serial")
-        .ok_or_else(|| anyhow::format_err!("missing `Wechatpay-Serial` header"))?
-        .to_str()?
+        .ok_or_else(|| WechatPayError::Verify("missing `Wechatpay-Serial` header".to_string()))?
+        .to_str()
+        .map_err(|e| WechatPayError::Verify(e.to_string()))?
         .to_string();
 
-    // 需要这个 builder 重新构建一个 Response 以便最后进行验签。
-    let mut builder = http::Response::builder()
-        .status(res.status())
-        .version(res.version());
-    for (key, value) in res.headers() {
-        builder = builder.header(key, value);
-    }
-    let body_txt = res.text().await?;
-
     let mut platform_certificates = vec![];
-    for item in serde_json::from_str::<GetPlatformCertificatesRes>(&body_txt)?.data {
-        let ciphertext = BASE64_STANDARD.decode(&item.encrypt_certificate.ciphertext)?;
+    for item in serde_json::from_slice::<GetPlatformCertificatesRes>(&res.body)?.data {
+        let ciphertext = BASE64_STANDARD
+            .decode(&item.encrypt_certificate.ciphertext)
+            .map_err(|e| WechatPayError::Decrypt(e.to_string()))?;
         let plain = mch_credential.aes_decrypt(
             &ciphertext,
             item.encrypt_certificate.associated_data.as_bytes(),
@@ -212,7 +444,8 @@ pub async fn get_platform_certificates(
             serial_no: item.serial_no,
             effective_time: item.effective_time,
             expire_time: item.expire_time,
-            certificate: Certificate::from_pem(&plain)?,
+            certificate: Certificate::from_pem(&plain)
+                .map_err(|e| WechatPayError::Certificate(e.to_string()))?,
         };
         platform_certificates.push(certificate);
     }
@@ -220,10 +453,56 @@ pub async fn get_platform_certificates(
     let public_key = platform_certificates
         .iter()
         .find(|c| c.serial_no == serial_no)
-        .ok_or_else(|| anyhow::format_err!("no certificate found for serial_no: {}", serial_no))?
+        .ok_or_else(|| {
+            WechatPayError::Certificate(format!(
+                "no certificate found for serial_no: {}",
+                serial_no
+            ))
+        })?
         .public_key()?;
 
-    let res_clone = Response::from(builder.body(body_txt)?);
-    verify_response(&public_key, res_clone).await?;
+    verify_response_parts(&public_key, &res.headers, &res.body)?;
     Ok(platform_certificates)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::pkcs1v15::SigningKey;
+    use rsa::signature::{RandomizedSigner, SignatureEncoding};
+    use rsa::RsaPrivateKey;
+
+    #[test]
+    fn test_verify_signature() -> anyhow::Result<()> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let public_key = private_key.to_public_key();
+        let signing_key = SigningKey::<Sha256>::new(private_key);
+        let signature = signing_key
+            .sign_with_rng(&mut rand::thread_rng(), b"1554208460\nnonce\n{}\n")
+            .to_bytes();
+        let signature = BASE64_STANDARD.encode(signature);
+
+        verify_signature(&public_key, "1554208460", "nonce", b"{}", &signature)?;
+        assert!(verify_signature(&public_key, "1554208460", "nonce", b"{ }", &signature).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_response_parts() -> anyhow::Result<()> {
+        use crate::transport::MockTransport;
+
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let mock = MockTransport::new(private_key, "PUB_KEY_ID_0000000000000001");
+        let public_key = mock.wechatpay_public_key();
+        let mut headers = mock.signature_headers(r#"{"code_url":"weixin://"}"#)?;
+
+        public_key.verify_response_parts(&headers, br#"{"code_url":"weixin://"}"#)?;
+        let err = public_key.verify_response_parts(&headers, br#"{"code_url":"weixin://x"}"#);
+        assert!(matches!(err, Err(WechatPayError::Verify(_))));
+
+        headers.remove("Wechatpay-Nonce");
+        let err = public_key.verify_response_parts(&headers, br#"{"code_url":"weixin://"}"#);
+        assert!(matches!(err, Err(WechatPayError::Verify(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! 多商户客户端注册表。
+//! SaaS 等多租户场景下，按 mch_id 管理多个商户的 client。各商户的凭证、平台证书状态相互独立，
+//! 底层共享同一个 `reqwest::Client`(即共享连接池)。
+
+use crate::client::{WechatPayClient, WechatPayClientBuilder, USER_AGENT};
+use crate::error::Result;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// 多商户客户端注册表
+#[derive(Debug, Clone)]
+pub struct WechatPayClientPool {
+    http_client: Client,
+    clients: Arc<RwLock<HashMap<String, WechatPayClient>>>,
+}
+
+impl WechatPayClientPool {
+    pub fn new() -> Result<WechatPayClientPool> {
+        let http_client = Client::builder().user_agent(USER_AGENT).build()?;
+        Ok(WechatPayClientPool::with_http_client(http_client))
+    }
+
+    /// 使用指定的 `reqwest::Client`，所有商户的 client 共享之。
+    pub fn with_http_client(http_client: Client) -> WechatPayClientPool {
+        WechatPayClientPool {
+            http_client,
+            clients: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// 构建并注册商户的 client，返回之。已存在相同 mch_id 的 client 时，将被替换。
+    /// builder 的 http_client 会被设置为共享的 `reqwest::Client`。
+    pub async fn register(&self, builder: &mut WechatPayClientBuilder) -> Result<WechatPayClient> {
+        let client = builder
+            .http_client(self.http_client.clone())
+            .build()
+            .await?;
+        self.insert(client.clone());
+        Ok(client)
+    }
+
+    /// 注册已构建的 client。已存在相同 mch_id 的 client 时，将被替换。
+    pub fn insert(&self, client: WechatPayClient) {
+        let mch_id = client.mch_credential().mch_id.clone();
+        self.clients.write().unwrap().insert(mch_id, client);
+    }
+
+    /// 获取商户的 client
+    pub fn get(&self, mch_id: &str) -> Option<WechatPayClient> {
+        self.clients.read().unwrap().get(mch_id).cloned()
+    }
+
+    /// 移除商户的 client
+    pub fn remove(&self, mch_id: &str) -> Option<WechatPayClient> {
+        self.clients.write().unwrap().remove(mch_id)
+    }
+
+    /// 已注册的商户号
+    pub fn mch_ids(&self) -> Vec<String> {
+        self.clients.read().unwrap().keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MchCredential, WechatPayPublicKey};
+    use rsa::RsaPrivateKey;
+
+    #[tokio::test]
+    async fn test_client_pool() -> anyhow::Result<()> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let pool = WechatPayClientPool::new()?;
+        for mch_id in ["1900000001", "1900000002"] {
+            pool.register(
+                WechatPayClient::builder()
+                    .mch_credential(MchCredential::new(
+                        mch_id.to_string(),
+                        "serial".to_string(),
+                        private_key.clone(),
+                        "0".repeat(32),
+                    ))
+                    .wechatpay_public_key(WechatPayPublicKey::new(
+                        "PUB_KEY_ID_0000000000000001".to_string(),
+                        private_key.to_public_key(),
+                    )),
+            )
+            .await?;
+        }
+
+        let mut mch_ids = pool.mch_ids();
+        mch_ids.sort();
+        assert_eq!(mch_ids, vec!["1900000001", "1900000002"]);
+        assert_eq!(
+            pool.get("1900000002").unwrap().mch_credential().mch_id,
+            "1900000002"
+        );
+        assert!(pool.get("1900000003").is_none());
+        assert!(pool.remove("1900000001").is_some());
+        assert!(pool.get("1900000001").is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+//! Native 支付二维码。
+//! 将 Native 下单返回的 code_url 渲染为 PNG 或 SVG 格式的二维码图片。
+
+use crate::client::WechatPayClient;
+use crate::error::{Result, WechatPayError};
+use crate::trade::NativeCreateTradeParams;
+use ::qrcode::render::svg;
+use ::qrcode::{Color, EcLevel, QrCode};
+
+/// 二维码图片格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QrCodeFormat {
+    /// 灰度 PNG 图片
+    #[default]
+    Png,
+    /// SVG 图片
+    Svg,
+}
+
+/// 二维码的纠错级别，级别越高，二维码被遮挡时越容易识别，但图案也越密集。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QrCodeEcLevel {
+    /// 约 7% 的纠错能力
+    L,
+    /// 约 15% 的纠错能力
+    #[default]
+    M,
+    /// 约 25% 的纠错能力
+    Q,
+    /// 约 30% 的纠错能力
+    H,
+}
+
+impl From<QrCodeEcLevel> for EcLevel {
+    fn from(level: QrCodeEcLevel) -> EcLevel {
+        match level {
+            QrCodeEcLevel::L => EcLevel::L,
+            QrCodeEcLevel::M => EcLevel::M,
+            QrCodeEcLevel::Q => EcLevel::Q,
+            QrCodeEcLevel::H => EcLevel::H,
+        }
+    }
+}
+
+/// 二维码的渲染选项
+#[derive(Debug, Clone)]
+pub struct QrCodeOptions {
+    /// 图片格式，默认为 PNG
+    pub format: QrCodeFormat,
+    /// 纠错级别，默认为 M
+    pub ec_level: QrCodeEcLevel,
+    /// 每个模块(即二维码中的一个小方块)的边长，单位为像素，默认为 8
+    pub module_size: u32,
+    /// 是否保留四周的空白区(宽度为 4 个模块)，默认为 true。扫码设备通常依赖空白区识别二维码。
+    pub quiet_zone: bool,
+}
+
+impl Default for QrCodeOptions {
+    fn default() -> Self {
+        QrCodeOptions {
+            format: QrCodeFormat::Png,
+            ec_level: QrCodeEcLevel::M,
+            module_size: 8,
+            quiet_zone: true,
+        }
+    }
+}
+
+const QUIET_ZONE_MODULES: usize = 4;
+
+/// 将内容渲染为二维码图片，返回图片的字节。
+pub fn render_qr_code(content: &str, options: &QrCodeOptions) -> Result<Vec<u8>> {
+    let code = QrCode::with_error_correction_level(content, options.ec_level.into())
+        .map_err(|e| WechatPayError::InvalidParams(format!("failed to encode qr code: {}", e)))?;
+    let module_size = options.module_size.max(1);
+    match options.format {
+        QrCodeFormat::Png => render_png(&code, module_size as usize, options.quiet_zone),
+        QrCodeFormat::Svg => Ok(code
+            .render::<svg::Color>()
+            .module_dimensions(module_size, module_size)
+            .quiet_zone(options.quiet_zone)
+            .build()
+            .into_bytes()),
+    }
+}
+
+fn render_png(code: &QrCode, module_size: usize, quiet_zone: bool) -> Result<Vec<u8>> {
+    let quiet_zone = if quiet_zone { QUIET_ZONE_MODULES } else { 0 };
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + quiet_zone * 2) * module_size;
+
+    let mut pixels = vec![255u8; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color == Color::Light {
+            continue;
+        }
+        let x0 = (i % modules + quiet_zone) * module_size;
+        let y0 = (i / modules + quiet_zone) * module_size;
+        for y in y0..y0 + module_size {
+            pixels[y * size + x0..y * size + x0 + module_size].fill(0);
+        }
+    }
+
+    let mut buf = vec![];
+    let mut encoder = png::Encoder::new(&mut buf, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let to_err = |e: png::EncodingError| WechatPayError::Other(e.to_string());
+    let mut writer = encoder.write_header().map_err(to_err)?;
+    writer.write_image_data(&pixels).map_err(to_err)?;
+    writer.finish().map_err(to_err)?;
+    Ok(buf)
+}
+
+impl WechatPayClient {
+    /// Native 下单，并将返回的 code_url 渲染为二维码图片，返回图片的字节。
+    pub async fn native_create_trade_qr(
+        &self,
+        params: &NativeCreateTradeParams,
+        options: &QrCodeOptions,
+    ) -> Result<Vec<u8>> {
+        let code_url = self.native_create_trade(params).await?;
+        render_qr_code(&code_url, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_qr_code() -> anyhow::Result<()> {
+        let code_url = "weixin://wxpay/bizpayurl/up?pr=NwY5Mz9&groupid=00";
+
+        let png = render_qr_code(code_url, &QrCodeOptions::default())?;
+        let decoder = png::Decoder::new(png.as_slice());
+        let reader = decoder.read_info()?;
+        let info = reader.info();
+        let modules = QrCode::with_error_correction_level(code_url, EcLevel::M)?.width();
+        assert_eq!(info.width as usize, (modules + 8) * 8);
+        assert_eq!(info.width, info.height);
+        assert_eq!(info.color_type, png::ColorType::Grayscale);
+
+        let svg = render_qr_code(
+            code_url,
+            &QrCodeOptions {
+                format: QrCodeFormat::Svg,
+                ec_level: QrCodeEcLevel::H,
+                module_size: 4,
+                quiet_zone: false,
+            },
+        )?;
+        let svg = String::from_utf8(svg)?;
+        let modules = QrCode::with_error_correction_level(code_url, EcLevel::H)?.width();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains(&format!(r#"width="{}""#, modules * 4)));
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+//! 客户端限流。
+//! 微信支付对部分接口有频率限制，超限时返回 FREQUENCY_LIMITED。可在客户端按接口路径配置令牌桶限流，
+//! 超限时排队等待，或快速失败返回 `WechatPayError::RateLimited`。
+
+use crate::error::{Result, WechatPayError};
+use std::cmp::Reverse;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 超限时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitMode {
+    /// 排队等待，直到获得令牌
+    #[default]
+    Wait,
+    /// 立即返回 `WechatPayError::RateLimited`
+    FailFast,
+}
+
+/// 令牌桶限流配置
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    /// 每秒补充的令牌数，即稳定状态下每秒允许的请求数
+    pub per_second: f64,
+    /// 令牌桶容量，即允许的突发请求数
+    pub burst: u32,
+    /// 超限时的处理方式
+    pub mode: RateLimitMode,
+}
+
+impl RateLimit {
+    /// 每秒 per_second 个请求，突发容量与之相同，超限时排队等待。
+    pub fn per_second(per_second: u32) -> RateLimit {
+        RateLimit {
+            per_second: per_second as f64,
+            burst: per_second.max(1),
+            mode: RateLimitMode::Wait,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> TokenBucket {
+        TokenBucket {
+            tokens: limit.burst as f64,
+            limit,
+            updated_at: Instant::now(),
+        }
+    }
+
+    /// 尝试获取一个令牌。获取失败时，返回需要等待的时间。
+    fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.updated_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else if self.limit.per_second > 0.0 {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.limit.per_second,
+            ))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+}
+
+/// 按接口路径前缀限流。路径匹配多个前缀时，使用最长的前缀。
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    buckets: Vec<(String, Mutex<TokenBucket>)>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limits: Vec<(String, RateLimit)>) -> RateLimiter {
+        let mut buckets: Vec<_> = limits
+            .into_iter()
+            .map(|(prefix, limit)| (prefix, Mutex::new(TokenBucket::new(limit))))
+            .collect();
+        buckets.sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+        RateLimiter { buckets }
+    }
+
+    /// 为请求路径获取一个令牌。未配置限流的路径直接返回。
+    pub(crate) async fn acquire(&self, path: &str) -> Result<()> {
+        let (prefix, bucket) = match self.buckets.iter().find(|(p, _)| path.starts_with(p)) {
+            Some((prefix, bucket)) => (prefix, bucket),
+            None => return Ok(()),
+        };
+        loop {
+            let (wait, mode) = {
+                let mut bucket = bucket.lock().unwrap();
+                match bucket.try_acquire(Instant::now()) {
+                    Ok(()) => return Ok(()),
+                    Err(wait) => (wait, bucket.limit.mode),
+                }
+            };
+            if mode == RateLimitMode::FailFast || wait == Duration::MAX {
+                return Err(WechatPayError::RateLimited(format!(
+                    "rate limit exceeded for {}",
+                    prefix
+                )));
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(RateLimit {
+            per_second: 2.0,
+            burst: 2,
+            mode: RateLimitMode::FailFast,
+        });
+        let now = bucket.updated_at;
+        assert!(bucket.try_acquire(now).is_ok());
+        assert!(bucket.try_acquire(now).is_ok());
+        assert_eq!(bucket.try_acquire(now), Err(Duration::from_millis(500)));
+
+        let now = now + Duration::from_millis(500);
+        assert!(bucket.try_acquire(now).is_ok());
+        assert!(bucket.try_acquire(now).is_err());
+
+        // 令牌数不超过容量
+        let now = now + Duration::from_secs(10);
+        assert!(bucket.try_acquire(now).is_ok());
+        assert!(bucket.try_acquire(now).is_ok());
+        assert!(bucket.try_acquire(now).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_longest_prefix() {
+        let limiter = RateLimiter::new(vec![
+            ("/v3".to_string(), RateLimit::per_second(100)),
+            (
+                "/v3/pay/transactions".to_string(),
+                RateLimit {
+                    per_second: 1.0,
+                    burst: 1,
+                    mode: RateLimitMode::FailFast,
+                },
+            ),
+        ]);
+        assert!(limiter.acquire("/v3/pay/transactions/jsapi").await.is_ok());
+        assert!(matches!(
+            limiter.acquire("/v3/pay/transactions/jsapi").await,
+            Err(WechatPayError::RateLimited(_))
+        ));
+        assert!(limiter.acquire("/v3/refund/domestic/refunds").await.is_ok());
+        assert!(limiter.acquire("/v2/unlimited").await.is_ok());
+    }
+}
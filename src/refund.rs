@@ -1,21 +1,29 @@
 //! 退款相关接口。
 
-use crate::client::WechatPayClient;
-use crate::client::BASE_URL;
+use crate::client::{PollOptions, WechatPayClient};
+use crate::error::{Result, WechatPayError};
+use crate::ids::{OutRefundNo, OutTradeNo};
+use crate::money::Fen;
+use crate::sensitive::{Encrypted, EncryptedString};
 use crate::util::datetime_fmt;
 use crate::util::option_datetime_fmt;
-use anyhow::Result;
 use chrono::{DateTime, Local};
+use reqwest::Method;
 use serde::Deserializer;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Instant;
 
 impl WechatPayClient {
     /// 申请退款。
     /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_9.shtml>
     pub async fn apply_refund(&self, params: &RefundParams) -> Result<RefundQueryResponse> {
-        let url = format!("{}/refund/domestic/refunds", BASE_URL);
+        let url = format!("{}/refund/domestic/refunds", self.base_url);
         let req = self.client.post(&url).json(params).build()?;
-        let res = self.execute(req).await?;
+        let res = self.execute_idempotent(req).await?;
         let res: RefundQueryResponse = res.json().await?;
         Ok(res)
     }
@@ -23,12 +31,110 @@ impl WechatPayClient {
     /// 查询退款。
     /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_10.shtml>
     pub async fn query_refund(&self, out_refund_no: &str) -> Result<RefundQueryResponse> {
-        let url = format!("{}/refund/domestic/refunds/{}", BASE_URL, out_refund_no);
+        let url = format!(
+            "{}/refund/domestic/refunds/{}",
+            self.base_url, out_refund_no
+        );
         let req = self.client.get(url).build()?;
         let res = self.execute(req).await?;
         let res: RefundQueryResponse = res.json().await?;
         Ok(res)
     }
+
+    /// 发起异常退款。退款状态为 ABNORMAL 时，可退款至用户或商户的银行卡。
+    /// 参数中的银行卡号、姓名会被自动加密。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_14.shtml>
+    pub async fn apply_abnormal_refund(
+        &self,
+        refund_id: &str,
+        params: &AbnormalRefundParams,
+    ) -> Result<RefundQueryResponse> {
+        let url = format!(
+            "{}/refund/domestic/refunds/{}/apply-abnormal-refund",
+            self.base_url, refund_id
+        );
+        let req = self.json_request(Method::POST, &url, params)?;
+        let res = self.execute_idempotent(req).await?;
+        self.json_response(res).await
+    }
+
+    /// 轮询查询退款，直到退款状态不再是 PROCESSING(如 SUCCESS、CLOSED、ABNORMAL)，返回最后一次查询的结果。
+    /// 轮询间隔按 `options` 指数退避；超过 `options.timeout` 仍在处理中时，返回 `WechatPayError::Timeout`。
+    pub async fn wait_for_refund_final_state(
+        &self,
+        out_refund_no: &str,
+        options: PollOptions,
+    ) -> Result<RefundQueryResponse> {
+        let deadline = Instant::now() + options.timeout;
+        let mut attempt = 0;
+        loop {
+            let res = self.query_refund(out_refund_no).await?;
+            if res.status != RefundStatus::Processing {
+                return Ok(res);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(WechatPayError::Timeout(format!(
+                    "refund {} is still processing after {:?}",
+                    out_refund_no, options.timeout
+                )));
+            }
+            let interval = options.interval(attempt).min(deadline - now);
+            tokio::time::sleep(interval).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// 发起异常退款的参数。
+#[derive(Debug, Clone, Serialize)]
+pub struct AbnormalRefundParams {
+    /// 商户退款单号
+    pub out_refund_no: OutRefundNo,
+    /// 异常退款处理方式。
+    /// * USER_BANK_CARD：退款到用户银行卡
+    /// * MERCHANT_BANK_CARD：退款至交易商户银行账户
+    #[serde(rename = "type")]
+    pub refund_type: String,
+    /// 开户银行。处理方式为 USER_BANK_CARD 时必填，取值参见银行类型对照表。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bank_type: Option<String>,
+    /// 收款银行卡号。处理方式为 USER_BANK_CARD 时必填。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bank_account: Option<EncryptedString>,
+    /// 收款用户姓名。处理方式为 USER_BANK_CARD 时必填。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub real_name: Option<EncryptedString>,
+}
+
+impl AbnormalRefundParams {
+    /// 退款到用户银行卡
+    pub fn user_bank_card(
+        out_refund_no: impl Into<OutRefundNo>,
+        bank_type: &str,
+        bank_account: &str,
+        real_name: &str,
+    ) -> AbnormalRefundParams {
+        AbnormalRefundParams {
+            out_refund_no: out_refund_no.into(),
+            refund_type: "USER_BANK_CARD".to_string(),
+            bank_type: Some(bank_type.to_string()),
+            bank_account: Some(Encrypted(bank_account.to_string())),
+            real_name: Some(Encrypted(real_name.to_string())),
+        }
+    }
+
+    /// 退款至交易商户银行账户
+    pub fn merchant_bank_card(out_refund_no: impl Into<OutRefundNo>) -> AbnormalRefundParams {
+        AbnormalRefundParams {
+            out_refund_no: out_refund_no.into(),
+            refund_type: "MERCHANT_BANK_CARD".to_string(),
+            bank_type: None,
+            bank_account: None,
+            real_name: None,
+        }
+    }
 }
 
 /// 申请退款的参数。
@@ -38,7 +144,7 @@ pub struct RefundParams {
     trade_id: TradeId,
     /// 商户退款单号，不超过 64 字符。
     /// 商户系统内部的退款单号，商户系统内部唯一，只能是数字、大小写字母_-|*@
-    pub out_refund_no: String,
+    pub out_refund_no: OutRefundNo,
     /// 退款原因，不超过 80 字符。
     /// 若传入，会在下发给用户的退款消息中体现退款原因。
     #[serde(skip_serializing_if = "Option::is_none", default)]
@@ -62,6 +168,165 @@ pub struct RefundParams {
     pub goods_detail: Vec<RefundGoodsDetail>,
 }
 
+impl RefundParams {
+    pub fn builder() -> RefundParamsBuilder {
+        RefundParamsBuilder::new()
+    }
+}
+
+/// builder for `RefundParams`.
+/// build 时校验退款金额、商户退款单号、出资账户等是否满足微信支付的限制。
+#[derive(Debug, Default)]
+pub struct RefundParamsBuilder {
+    trade_id: Option<TradeId>,
+    out_refund_no: Option<OutRefundNo>,
+    reason: Option<String>,
+    notify_url: Option<String>,
+    funds_account: Option<String>,
+    total: Option<Fen>,
+    refund: Option<Fen>,
+    currency: Option<String>,
+    from: Vec<RefundFromAccount>,
+    goods_detail: Vec<RefundGoodsDetail>,
+}
+
+impl RefundParamsBuilder {
+    fn new() -> RefundParamsBuilder {
+        RefundParamsBuilder {
+            ..Default::default()
+        }
+    }
+
+    /// 微信支付订单号。与 out_trade_no 二选一。
+    pub fn transaction_id(&mut self, transaction_id: String) -> &mut Self {
+        self.trade_id = Some(TradeId::TransactionId(transaction_id));
+        self
+    }
+
+    /// 商户订单号。与 transaction_id 二选一。
+    pub fn out_trade_no(&mut self, out_trade_no: impl Into<OutTradeNo>) -> &mut Self {
+        self.trade_id = Some(TradeId::OutTradeNo(out_trade_no.into()));
+        self
+    }
+
+    pub fn out_refund_no(&mut self, out_refund_no: impl Into<OutRefundNo>) -> &mut Self {
+        self.out_refund_no = Some(out_refund_no.into());
+        self
+    }
+
+    pub fn reason(&mut self, reason: String) -> &mut Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn notify_url(&mut self, notify_url: String) -> &mut Self {
+        self.notify_url = Some(notify_url);
+        self
+    }
+
+    pub fn funds_account(&mut self, funds_account: String) -> &mut Self {
+        self.funds_account = Some(funds_account);
+        self
+    }
+
+    /// 原订单金额及退款金额，单位为分。
+    pub fn amount(&mut self, total: impl Into<Fen>, refund: impl Into<Fen>) -> &mut Self {
+        self.total = Some(total.into());
+        self.refund = Some(refund.into());
+        self
+    }
+
+    /// 退款币种，默认为 CNY。
+    pub fn currency(&mut self, currency: String) -> &mut Self {
+        self.currency = Some(currency);
+        self
+    }
+
+    /// 添加一个退款出资账户。
+    pub fn from(&mut self, account: String, amount: impl Into<Fen>) -> &mut Self {
+        self.from.push(RefundFromAccount {
+            account,
+            amount: amount.into(),
+        });
+        self
+    }
+
+    /// 添加一个退款商品。
+    pub fn goods_detail(&mut self, detail: RefundGoodsDetail) -> &mut Self {
+        self.goods_detail.push(detail);
+        self
+    }
+
+    pub fn build(&mut self) -> Result<RefundParams> {
+        let trade_id = self.trade_id.take().ok_or_else(|| {
+            WechatPayError::InvalidParams("missing `transaction_id` or `out_trade_no`".to_string())
+        })?;
+        let out_refund_no = self
+            .out_refund_no
+            .take()
+            .ok_or_else(|| WechatPayError::InvalidParams("missing `out_refund_no`".to_string()))?;
+        let (total, refund) = self
+            .total
+            .zip(self.refund)
+            .ok_or_else(|| WechatPayError::InvalidParams("missing `amount`".to_string()))?;
+
+        if let Some(reason) = &self.reason {
+            if reason.chars().count() > 80 {
+                return Err(WechatPayError::InvalidParams(
+                    "`reason` is too long, at most 80 characters".to_string(),
+                ));
+            }
+        }
+        if total <= Fen::ZERO || refund <= Fen::ZERO || refund > total {
+            return Err(WechatPayError::InvalidParams(format!(
+                "invalid amount, total: {}, refund: {}",
+                total, refund
+            )));
+        }
+
+        let from = std::mem::take(&mut self.from);
+        if !from.is_empty() {
+            let mut accounts = HashSet::new();
+            for f in &from {
+                if f.amount <= Fen::ZERO {
+                    return Err(WechatPayError::InvalidParams(format!(
+                        "invalid amount {} for account: {}",
+                        f.amount, f.account
+                    )));
+                }
+                if !accounts.insert(f.account.as_str()) {
+                    return Err(WechatPayError::InvalidParams(format!(
+                        "duplicated from account: {}",
+                        f.account
+                    )));
+                }
+            }
+            let sum: i64 = from.iter().map(|f| f.amount.value()).sum();
+            if sum != refund.value() {
+                return Err(WechatPayError::InvalidParams(format!(
+                    "sum of from amounts {} is not equal to refund {}",
+                    sum, refund
+                )));
+            }
+        }
+
+        Ok(RefundParams {
+            trade_id,
+            out_refund_no,
+            reason: self.reason.take(),
+            notify_url: self.notify_url.take(),
+            funds_account: self.funds_account.take(),
+            amount: RefundApplyingAmount {
+                total,
+                refund,
+                currency: self.currency.take().unwrap_or_else(|| "CNY".to_string()),
+                from,
+            },
+            goods_detail: std::mem::take(&mut self.goods_detail),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TradeId {
     /// 微信支付订单号
@@ -69,16 +334,16 @@ pub enum TradeId {
     TransactionId(String),
     /// 商户订单号
     #[serde(rename = "out_trade_no")]
-    OutTradeNo(String),
+    OutTradeNo(OutTradeNo),
 }
 
 /// 申请退款的金额信息。
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RefundApplyingAmount {
     /// 原支付交易的订单总金额，单位为分，只能为整数。
-    pub total: i32,
+    pub total: Fen,
     /// 退款金额，单位为分，只能为整数，不能超过原订单支付金额。
-    pub refund: i32,
+    pub refund: Fen,
     /// 退款币种。符合ISO 4217标准的三位字母代码，目前只支持人民币：CNY。
     pub currency: String,
     /// 退款出资账户及金额。
@@ -86,10 +351,10 @@ pub struct RefundApplyingAmount {
     /// 同时指定多个账户出资退款的使用场景需要满足以下条件：
     /// 1. 未开通退款支出分离产品功能；
     /// 2. 订单属于分账订单，且分账处于待分账或分账中状态。
-    /// 参数传递需要满足条件：
+    ///    参数传递需要满足条件：
     /// 1. 基本账户可用余额出资金额与基本账户不可用余额出资金额之和等于退款金额；
     /// 2. 账户类型不能重复。
-    /// 上述任一条件不满足将返回错误
+    ///    上述任一条件不满足将返回错误
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub from: Vec<RefundFromAccount>,
 }
@@ -102,7 +367,7 @@ pub struct RefundFromAccount {
     /// * UNAVAILABLE : 不可用余额
     pub account: String,
     /// 对应账户出资金额。
-    pub amount: i32,
+    pub amount: Fen,
 }
 
 /// 退款商品
@@ -118,15 +383,16 @@ pub struct RefundGoodsDetail {
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub goods_name: Option<String>,
     /// 商品单价，单位为分。如果商户有优惠，需传输商户优惠后的单价。
-    pub unit_price: i32,
+    pub unit_price: Fen,
     /// 商品退款金额。单位为分。
-    pub refund_amount: i32,
+    pub refund_amount: Fen,
     /// 商品退货数量。
     pub refund_quantity: i32,
 }
 
 /// 退款查询响应。
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct RefundQueryResponse {
     /// 微信支付退款单号。不超过 32 字符。
     pub refund_id: String,
@@ -178,9 +444,15 @@ pub struct RefundQueryResponse {
     /// 优惠退款信息
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub promotion_detail: Vec<RefundPromotionDetail>,
+
+    /// 未在本结构中定义的字段。微信支付新增字段时，可从中取得原始数据。
+    /// 启用 `extra-fields` feature 时可用。
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RefundStatus {
     /// 退款成功
     Success,
@@ -190,39 +462,58 @@ pub enum RefundStatus {
     Processing,
     /// 退款异常
     Abnormal,
+    /// 其他状态。微信支付新增的枚举值，保留原文。
+    Other(String),
 }
 
-impl<'de> Deserialize<'de> for RefundStatus {
-    fn deserialize<D>(deserializer: D) -> Result<RefundStatus, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        match s.as_str() {
+impl RefundStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RefundStatus::Success => "SUCCESS",
+            RefundStatus::Closed => "CLOSED",
+            RefundStatus::Processing => "PROCESSING",
+            RefundStatus::Abnormal => "ABNORMAL",
+            RefundStatus::Other(s) => s,
+        }
+    }
+}
+
+impl FromStr for RefundStatus {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
             "SUCCESS" => Ok(RefundStatus::Success),
             "CLOSED" => Ok(RefundStatus::Closed),
             "PROCESSING" => Ok(RefundStatus::Processing),
             "ABNORMAL" => Ok(RefundStatus::Abnormal),
-            _ => Err(serde::de::Error::custom(format!(
-                "unknown refund status: {}",
-                s
-            ))),
+            _ => Ok(RefundStatus::Other(s.to_string())),
         }
     }
 }
 
+impl fmt::Display for RefundStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RefundStatus {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+}
+
 impl Serialize for RefundStatus {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let s = match self {
-            RefundStatus::Success => "SUCCESS",
-            RefundStatus::Closed => "CLOSED",
-            RefundStatus::Processing => "PROCESSING",
-            RefundStatus::Abnormal => "ABNORMAL",
-        };
-        serializer.serialize_str(s)
+        serializer.serialize_str(self.as_str())
     }
 }
 
@@ -233,37 +524,39 @@ impl Serialize for RefundStatus {
 /// 二者的差异是因为，由于用户使用了优惠券，订单金额与用户实际支付金额本就不一致。
 /// 看来，如果如果使用微信支付的优惠券功能，计算将会比较复杂。
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct RefundActualAmount {
     /// 原支付交易的订单总金额，单位为分，只能为整数。
-    pub total: i32,
+    pub total: Fen,
     /// 退款标价金额，单位为分，可以做部分退款。
-    pub refund: i32,
+    pub refund: Fen,
     /// 现金支付金额，单位为分。
-    pub payer_total: i32,
+    pub payer_total: Fen,
     /// 退款给用户的金额，不包含所有优惠券金额。
-    pub payer_refund: i32,
+    pub payer_refund: Fen,
 
     /// 退款出资账户及金额。
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub from: Vec<RefundFromAccount>,
 
     /// 应结订单金额=订单金额-免充值代金券金额，应结订单金额<=订单金额，单位为分
-    pub settlement_total: i32,
+    pub settlement_total: Fen,
     /// 应结退款金额。去掉非充值代金券退款金额后的退款金额，单位为分。
     /// 退款金额=申请退款金额-非充值代金券退款金额，退款金额<=申请退款金额
-    pub settlement_refund: i32,
+    pub settlement_refund: Fen,
 
     /// 优惠退款金额<=退款金额，退款金额-代金券或立减优惠退款金额为现金。
-    pub discount_refund: i32,
+    pub discount_refund: Fen,
 
     /// 退款币种。符合ISO 4217标准的三位字母代码，目前只支持人民币：CNY。
     pub currency: String,
 
     /// 手续费退款金额
-    pub refund_fee: i32,
+    pub refund_fee: Fen,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct RefundPromotionDetail {
     /// 券ID
     pub coupon_id: String,
@@ -277,11 +570,231 @@ pub struct RefundPromotionDetail {
     #[serde(rename = "type")]
     pub promotion_type: Option<String>,
     /// 优惠券面额
-    pub amount: i32,
+    pub amount: Fen,
     /// 优惠退款金额。
     /// 优惠退款金额<=退款金额，退款金额-代金券或立减优惠退款金额为用户支付的现金。
-    pub refund_amount: i32,
+    pub refund_amount: Fen,
     /// 商品列表
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub goods_detail: Vec<RefundGoodsDetail>,
 }
+
+/// 退款结果通知解密后的资源数据。
+/// 对应 event_type: REFUND.SUCCESS、REFUND.ABNORMAL、REFUND.CLOSED。
+/// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_11.shtml>
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RefundNotification {
+    /// 直连商户号
+    pub mchid: String,
+    /// 商户订单号。不超过 32 字符。
+    pub out_trade_no: String,
+    /// 微信支付订单号。不超过 32 字符。
+    pub transaction_id: String,
+    /// 商户退款单号，不超过 64 字符。
+    pub out_refund_no: String,
+    /// 微信支付退款单号。不超过 32 字符。
+    pub refund_id: String,
+    /// 退款状态。
+    pub refund_status: RefundStatus,
+    /// 退款成功时间，当退款状态为退款成功时有返回。
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub success_time: Option<DateTime<Local>>,
+    /// 退款入账账户。不超过 64 字符。取值同 `RefundQueryResponse::user_received_account`。
+    pub user_received_account: String,
+    /// 金额信息
+    pub amount: RefundNotificationAmount,
+
+    /// 未在本结构中定义的字段。微信支付新增字段时，可从中取得原始数据。
+    /// 启用 `extra-fields` feature 时可用。
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// 退款结果通知中的金额信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RefundNotificationAmount {
+    /// 原支付交易的订单总金额，单位为分。
+    pub total: Fen,
+    /// 退款金额，单位为分。
+    pub refund: Fen,
+    /// 用户实际支付金额，单位为分。
+    pub payer_total: Fen,
+    /// 退款给用户的金额，单位为分，不包含所有优惠券金额。
+    pub payer_refund: Fen,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refund_params_builder() -> anyhow::Result<()> {
+        let out_trade_no = OutTradeNo::new("1217752501201407033233368018")?;
+        let params = RefundParams::builder()
+            .out_trade_no(&out_trade_no)
+            .out_refund_no(OutRefundNo::new("refund_1|2@3")?)
+            .amount(100, 60)
+            .from("AVAILABLE".to_string(), 40)
+            .from("UNAVAILABLE".to_string(), 20)
+            .build()?;
+        assert_eq!(params.amount.currency, "CNY");
+        let v = serde_json::to_value(&params)?;
+        assert_eq!(v["out_trade_no"], "1217752501201407033233368018");
+
+        let err = RefundParams::builder()
+            .out_trade_no(&out_trade_no)
+            .out_refund_no(OutRefundNo::new("refund-1")?)
+            .amount(100, 101)
+            .build();
+        assert!(matches!(err, Err(WechatPayError::InvalidParams(_))));
+
+        let err = OutRefundNo::new("退款单号");
+        assert!(matches!(err, Err(WechatPayError::InvalidParams(_))));
+
+        let err = RefundParams::builder()
+            .out_trade_no(&out_trade_no)
+            .out_refund_no(OutRefundNo::new("refund-1")?)
+            .amount(100, 60)
+            .from("AVAILABLE".to_string(), 50)
+            .build();
+        assert!(matches!(err, Err(WechatPayError::InvalidParams(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_abnormal_refund_encrypts_bank_account() -> anyhow::Result<()> {
+        use crate::transport::mock_client;
+
+        let (client, mock) = mock_client().await?;
+
+        let params = AbnormalRefundParams::user_bank_card(
+            OutRefundNo::new("refund-1")?,
+            "ICBC_DEBIT",
+            "6222000011112222",
+            "张三",
+        );
+        // 未设置 mock 响应，返回 NOT_FOUND
+        assert!(client
+            .apply_abnormal_refund("50000000382019052709732678859", &params)
+            .await
+            .is_err());
+
+        let requests = mock.requests();
+        let body = String::from_utf8(requests[0].body.clone())?;
+        assert!(requests[0].path.ends_with("/apply-abnormal-refund"));
+        assert!(body.contains("USER_BANK_CARD"));
+        assert!(!body.contains("6222000011112222"));
+        assert!(!body.contains("张三"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_refund_notification_deserialize() -> anyhow::Result<()> {
+        let s = r#"{
+            "mchid": "1900000100",
+            "transaction_id": "1008450740201411110005820873",
+            "out_trade_no": "20150806125346",
+            "refund_id": "50200207182018070300011301001",
+            "out_refund_no": "7752501201407033233368018",
+            "refund_status": "SUCCESS",
+            "success_time": "2018-06-08T10:34:56+08:00",
+            "user_received_account": "招商银行信用卡0403",
+            "amount": {
+                "total": 999,
+                "refund": 999,
+                "payer_total": 999,
+                "payer_refund": 999
+            }
+        }"#;
+        let noti: RefundNotification = serde_json::from_str(s)?;
+        assert_eq!(noti.refund_status, RefundStatus::Success);
+        assert!(noti.success_time.is_some());
+        assert_eq!(noti.amount.payer_refund, Fen(999));
+        #[cfg(feature = "extra-fields")]
+        assert!(noti.extra.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_refund_final_state() -> anyhow::Result<()> {
+        use crate::transport::mock_client;
+        use reqwest::StatusCode;
+        use std::time::Duration;
+
+        let (client, mock) = mock_client().await?;
+
+        let response = |status: &str| {
+            serde_json::json!({
+                "refund_id": "50000000382019052709732678859",
+                "out_refund_no": "refund-1",
+                "transaction_id": "1217752501201407033233368018",
+                "out_trade_no": "1217752501201407033233368018",
+                "channel": "ORIGINAL",
+                "user_received_account": "招商银行信用卡0403",
+                "create_time": "2020-12-01T16:18:12+08:00",
+                "status": status,
+                "amount": {
+                    "total": 100, "refund": 100, "payer_total": 100, "payer_refund": 100,
+                    "settlement_total": 100, "settlement_refund": 100, "discount_refund": 0,
+                    "currency": "CNY", "refund_fee": 0
+                }
+            })
+            .to_string()
+        };
+        let path = "/v3/refund/domestic/refunds/refund-1";
+        mock.on(Method::GET, path, StatusCode::OK, &response("PROCESSING"));
+        let options = PollOptions {
+            initial_interval: Duration::from_millis(10),
+            max_interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(60),
+        };
+
+        let err = client
+            .wait_for_refund_final_state("refund-1", options.clone())
+            .await;
+        assert!(matches!(err, Err(WechatPayError::Timeout(_))));
+
+        let update = async {
+            tokio::time::sleep(Duration::from_millis(15)).await;
+            mock.on(Method::GET, path, StatusCode::OK, &response("SUCCESS"));
+        };
+        let options = PollOptions {
+            timeout: Duration::from_secs(5),
+            ..options
+        };
+        let (res, _) = tokio::join!(
+            client.wait_for_refund_final_state("refund-1", options),
+            update
+        );
+        assert_eq!(res?.status, RefundStatus::Success);
+        Ok(())
+    }
+
+    #[cfg(feature = "extra-fields")]
+    #[test]
+    fn test_extra_fields() -> anyhow::Result<()> {
+        let mut v = serde_json::json!({
+            "mchid": "1900000100",
+            "transaction_id": "1008450740201411110005820873",
+            "out_trade_no": "20150806125346",
+            "refund_id": "50200207182018070300011301001",
+            "out_refund_no": "7752501201407033233368018",
+            "refund_status": "SUCCESS",
+            "user_received_account": "招商银行信用卡0403",
+            "amount": { "total": 999, "refund": 999, "payer_total": 999, "payer_refund": 999 },
+            "new_field": "new_value"
+        });
+        let noti: RefundNotification = serde_json::from_value(v.clone())?;
+        assert_eq!(noti.extra["new_field"], "new_value");
+        v.as_object_mut().unwrap().remove("success_time");
+        assert_eq!(serde_json::to_value(&noti)?, v);
+        Ok(())
+    }
+}
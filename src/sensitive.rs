@@ -0,0 +1,358 @@
+//! 敏感信息加密。
+//! 上送的敏感信息字段(如姓名、银行卡号、手机号等)，须使用微信支付平台证书或微信支付公钥进行 RSAES-OAEP 加密，
+//! 并在请求的 Wechatpay-Serial header 中指明所用证书的序列号或公钥 ID。
+//! 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/wechatpay/wechatpay4_3.shtml>
+//!
+//! 结构体中的敏感字段可声明为 `EncryptedString`：通过 `WechatPayClient::json_request` 构建请求时自动加密并设置
+//! Wechatpay-Serial header；通过 `WechatPayClient::json_response` 解析响应时自动使用商户私钥解密。
+
+use crate::client::WechatPayClient;
+use crate::credential::MchCredential;
+use crate::error::{Result, WechatPayError};
+use base64::prelude::*;
+use reqwest::{Method, Request, Response};
+use rsa::{Oaep, RsaPublicKey};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha1::Sha1;
+use std::cell::RefCell;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// 使用 RSAES-OAEP 加密敏感信息，返回 base64 编码的密文。
+pub(crate) fn rsa_oaep_encrypt(public_key: &RsaPublicKey, plaintext: &str) -> Result<String> {
+    let mut rng = rand::thread_rng();
+    let ciphertext = public_key
+        .encrypt(&mut rng, Oaep::new::<Sha1>(), plaintext.as_bytes())
+        .map_err(|e| WechatPayError::Other(format!("failed to encrypt: {}", e)))?;
+    Ok(BASE64_STANDARD.encode(ciphertext))
+}
+
+/// 敏感信息加密器。
+/// 同一请求中的所有敏感字段须使用同一个加密器加密，并通过 `apply` 设置 Wechatpay-Serial header。
+#[derive(Debug, Clone)]
+pub struct SensitiveEncryptor {
+    serial_no: String,
+    public_key: RsaPublicKey,
+}
+
+impl SensitiveEncryptor {
+    /// serial_no 为平台证书序列号或微信支付公钥 ID。
+    pub fn new(serial_no: String, public_key: RsaPublicKey) -> SensitiveEncryptor {
+        SensitiveEncryptor {
+            serial_no,
+            public_key,
+        }
+    }
+
+    /// 平台证书序列号或微信支付公钥 ID
+    pub fn serial_no(&self) -> &str {
+        &self.serial_no
+    }
+
+    /// 加密敏感信息，返回 base64 编码的密文。
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        rsa_oaep_encrypt(&self.public_key, plaintext)
+    }
+
+    /// 设置请求的 Wechatpay-Serial header。
+    pub fn apply(&self, req: &mut Request) -> Result<()> {
+        let value = self
+            .serial_no
+            .parse()
+            .map_err(|e: reqwest::header::InvalidHeaderValue| {
+                WechatPayError::InvalidParams(e.to_string())
+            })?;
+        req.headers_mut().insert("Wechatpay-Serial", value);
+        Ok(())
+    }
+}
+
+impl WechatPayClient {
+    /// 获取敏感信息加密器。
+    /// 配置了微信支付公钥时使用公钥；否则使用已启用的平台证书中最新的一个。
+    pub fn sensitive_encryptor(&self) -> Result<SensitiveEncryptor> {
+        if let Some(public_key) = &self.wechatpay_public_key {
+            return Ok(SensitiveEncryptor::new(
+                public_key.public_key_id.clone(),
+                public_key.public_key.clone(),
+            ));
+        }
+        self.platform_certificate_encryptor()
+    }
+
+    /// 使用已启用的平台证书中最新的一个构建加密器。
+    #[cfg(feature = "x509")]
+    fn platform_certificate_encryptor(&self) -> Result<SensitiveEncryptor> {
+        let state = self.platform_certificate_state.load();
+        let certificate = state.newest_certificate().ok_or_else(|| {
+            WechatPayError::Certificate("no platform certificate for encryption".to_string())
+        })?;
+        Ok(SensitiveEncryptor::new(
+            certificate.serial_no.clone(),
+            certificate.public_key()?,
+        ))
+    }
+
+    /// 未启用 `x509` feature 时不支持平台证书，仅能使用微信支付公钥加密。
+    #[cfg(not(feature = "x509"))]
+    fn platform_certificate_encryptor(&self) -> Result<SensitiveEncryptor> {
+        Err(WechatPayError::Certificate(
+            "no wechatpay public key for encryption".to_string(),
+        ))
+    }
+
+    /// 构建 body 为 JSON 的请求。params 中的 `Encrypted` 字段会被自动加密，并设置对应的 Wechatpay-Serial header。
+    /// 无法获取加密器且 params 中含有 `Encrypted` 字段时，返回 `sensitive_encryptor` 的错误。
+    pub fn json_request<T: Serialize>(
+        &self,
+        method: Method,
+        url: &str,
+        params: &T,
+    ) -> Result<Request> {
+        let encryptor = self.sensitive_encryptor();
+        let (body, used) = ENCRYPTOR.with(|c| {
+            let _guard = ContextGuard::set(c, Some((encryptor.as_ref().ok().cloned(), false)));
+            let body = serde_json::to_vec(params);
+            let used = c.borrow().as_ref().is_some_and(|(_, used)| *used);
+            (body, used)
+        });
+        let encryptor = if used { Some(encryptor?) } else { None };
+
+        let mut req = self
+            .client
+            .request(method, url)
+            .header("Content-Type", "application/json")
+            .body(body?)
+            .build()?;
+        if let Some(encryptor) = encryptor {
+            encryptor.apply(&mut req)?;
+        }
+        Ok(req)
+    }
+
+    /// 将 JSON 响应解析为 T。T 中的 `Encrypted` 字段会被自动使用商户私钥解密。
+    pub async fn json_response<T: DeserializeOwned>(&self, res: Response) -> Result<T> {
+        let body = res.bytes().await?;
+        with_decryptor(&self.mch_credential(), || {
+            Ok(serde_json::from_slice(&body)?)
+        })
+    }
+
+    /// 加密敏感信息，返回 base64 编码的密文。使用的证书或公钥同 `sensitive_encryptor`。
+    /// 本 crate 中带有敏感字段的接口(如转账的 user_name)，会自动设置对应的 Wechatpay-Serial header。
+    pub fn encrypt_sensitive(&self, plaintext: &str) -> Result<String> {
+        self.sensitive_encryptor()?.encrypt(plaintext)
+    }
+}
+
+thread_local! {
+    /// 当前线程的加密器，以及是否已被使用。加密器为 None 表示 `json_request` 未能获取加密器
+    static ENCRYPTOR: RefCell<Option<(Option<SensitiveEncryptor>, bool)>> = const { RefCell::new(None) };
+    /// 当前线程的解密凭证
+    static DECRYPTOR: RefCell<Option<MchCredential>> = const { RefCell::new(None) };
+}
+
+/// 设置线程局部的上下文，离开作用域时恢复原值。
+struct ContextGuard<'a, T> {
+    cell: &'a RefCell<Option<T>>,
+    prev: Option<T>,
+}
+
+impl<'a, T> ContextGuard<'a, T> {
+    fn set(cell: &'a RefCell<Option<T>>, value: Option<T>) -> ContextGuard<'a, T> {
+        let prev = cell.replace(value);
+        ContextGuard { cell, prev }
+    }
+}
+
+impl<T> Drop for ContextGuard<'_, T> {
+    fn drop(&mut self) {
+        self.cell.replace(self.prev.take());
+    }
+}
+
+/// 在 f 中序列化 `Encrypted` 字段时，使用 encryptor 加密。
+/// 请求需通过 `SensitiveEncryptor::apply` 设置 Wechatpay-Serial header。
+pub fn with_encryptor<R>(encryptor: &SensitiveEncryptor, f: impl FnOnce() -> R) -> R {
+    ENCRYPTOR.with(|c| {
+        let _guard = ContextGuard::set(c, Some((Some(encryptor.clone()), false)));
+        f()
+    })
+}
+
+/// 在 f 中反序列化 `Encrypted` 字段时，使用 mch_credential 的商户私钥解密。
+pub fn with_decryptor<R>(mch_credential: &MchCredential, f: impl FnOnce() -> R) -> R {
+    DECRYPTOR.with(|c| {
+        let _guard = ContextGuard::set(c, Some(mch_credential.clone()));
+        f()
+    })
+}
+
+/// 敏感字段。
+/// 序列化时使用 `with_encryptor` 指定的加密器加密，反序列化时使用 `with_decryptor` 指定的商户私钥解密；
+/// 未指定时返回错误，避免明文被意外上送或密文被当作明文使用。
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Encrypted<T>(pub T);
+
+/// 字符串类型的敏感字段，如姓名、手机号等。
+pub type EncryptedString = Encrypted<String>;
+
+impl<T> Encrypted<T> {
+    pub fn new(value: T) -> Encrypted<T> {
+        Encrypted(value)
+    }
+
+    /// 明文
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for Encrypted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Encrypted(***)")
+    }
+}
+
+impl From<&str> for EncryptedString {
+    fn from(s: &str) -> Self {
+        Encrypted(s.to_string())
+    }
+}
+
+impl<T: Display> Serialize for Encrypted<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let ciphertext = ENCRYPTOR.with(|c| match c.borrow_mut().as_mut() {
+            Some((Some(encryptor), used)) => {
+                *used = true;
+                encryptor.encrypt(&self.0.to_string())
+            }
+            Some((None, used)) => {
+                *used = true;
+                Err(WechatPayError::Other(
+                    "no encryptor for sensitive field".to_string(),
+                ))
+            }
+            None => Err(WechatPayError::Other(
+                "no encryptor for sensitive field, use `with_encryptor`".to_string(),
+            )),
+        });
+        let ciphertext = ciphertext.map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&ciphertext)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Encrypted<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ciphertext = String::deserialize(deserializer)?;
+        let plaintext = DECRYPTOR.with(|c| match c.borrow().as_ref() {
+            Some(mch_credential) => mch_credential.decrypt_sensitive(&ciphertext),
+            None => Err(WechatPayError::Other(
+                "no decryptor for sensitive field, use `with_decryptor`".to_string(),
+            )),
+        });
+        let plaintext = plaintext.map_err(serde::de::Error::custom)?;
+        let value = plaintext.parse().map_err(serde::de::Error::custom)?;
+        Ok(Encrypted(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::RsaPrivateKey;
+
+    #[test]
+    fn test_sensitive_encryptor() -> anyhow::Result<()> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let encryptor = SensitiveEncryptor::new(
+            "PUB_KEY_ID_0000000000000001".to_string(),
+            private_key.to_public_key(),
+        );
+
+        let ciphertext = encryptor.encrypt("张三")?;
+        let plaintext =
+            private_key.decrypt(Oaep::new::<Sha1>(), &BASE64_STANDARD.decode(ciphertext)?)?;
+        assert_eq!(String::from_utf8(plaintext)?, "张三");
+
+        let mut req = reqwest::Client::new()
+            .post("https://api.mch.weixin.qq.com/v3/transfer/batches")
+            .build()?;
+        encryptor.apply(&mut req)?;
+        assert_eq!(
+            req.headers().get("Wechatpay-Serial").unwrap(),
+            "PUB_KEY_ID_0000000000000001"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypted_string() -> anyhow::Result<()> {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Params {
+            openid: String,
+            user_name: EncryptedString,
+        }
+
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let encryptor = SensitiveEncryptor::new("serial".to_string(), private_key.to_public_key());
+        let mch_credential = MchCredential::new(
+            "1900000001".to_string(),
+            "serial".to_string(),
+            private_key,
+            "0".repeat(32),
+        );
+
+        let params = Params {
+            openid: "o-1".to_string(),
+            user_name: "张三".into(),
+        };
+        assert!(serde_json::to_string(&params).is_err());
+        let json = with_encryptor(&encryptor, || serde_json::to_string(&params))?;
+        assert!(!json.contains("张三"));
+
+        assert!(serde_json::from_str::<Params>(&json).is_err());
+        let params: Params = with_decryptor(&mch_credential, || serde_json::from_str(&json))?;
+        assert_eq!(params.user_name.into_inner(), "张三");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_json_request_without_encryptor() -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct Params {
+            openid: String,
+            user_name: Option<EncryptedString>,
+        }
+
+        let (mut client, _) = crate::transport::mock_client().await?;
+        client.wechatpay_public_key = None;
+        let url = "https://api.mch.weixin.qq.com/v3/transfer/batches";
+
+        let params = Params {
+            openid: "o-1".to_string(),
+            user_name: None,
+        };
+        let req = client.json_request(Method::POST, url, &params)?;
+        assert!(req.headers().get("Wechatpay-Serial").is_none());
+
+        let params = Params {
+            openid: "o-1".to_string(),
+            user_name: Some("张三".into()),
+        };
+        let err = client.json_request(Method::POST, url, &params).unwrap_err();
+        assert!(matches!(err, WechatPayError::Certificate(_)), "{err}");
+        Ok(())
+    }
+}
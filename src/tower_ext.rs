@@ -0,0 +1,196 @@
+//! 微信支付通知的 tower 中间件。
+//!
+//! `WechatPayNotifyLayer` 对通知请求进行验签、解密及反序列化，内层 service 收到的请求 body
+//! 为解密后的 T(默认为 `NotificationEvent`)。基于 tower/hyper 的框架(如 warp、原生 hyper)均可直接使用：
+//! ```ignore
+//! let svc = ServiceBuilder::new()
+//!     .layer(WechatPayNotifyLayer::<NotificationEvent>::new(client))
+//!     .service_fn(|req: http::Request<NotificationEvent>| async move {
+//!         // ...
+//!         Ok::<_, Infallible>(http::Response::new(hyper::Body::empty()))
+//!     });
+//! ```
+//! 验签失败时直接应答 401，解析或解密失败时应答 400，body 为 `{"code":"FAIL","message":"..."}`。
+//! 中间件不使用通知去重存储，重复的通知同样会转发给内层 service。需要去重时可使用
+//! `WechatPayClient::process_notification` 或 `NotificationDispatcher`。
+
+use crate::client::WechatPayClient;
+use crate::error::WechatPayError;
+use crate::notify::{FromNotification, NotificationEvent, NotifyResponse, WechatPayNotification};
+use futures::future::BoxFuture;
+use http::{header, Request, Response};
+use http_body::Body as HttpBody;
+use std::fmt;
+use std::marker::PhantomData;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// 微信支付通知中间件的 Layer
+pub struct WechatPayNotifyLayer<T = NotificationEvent> {
+    client: WechatPayClient,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> WechatPayNotifyLayer<T> {
+    pub fn new(client: WechatPayClient) -> Self {
+        WechatPayNotifyLayer {
+            client,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for WechatPayNotifyLayer<T> {
+    fn clone(&self) -> Self {
+        WechatPayNotifyLayer::new(self.client.clone())
+    }
+}
+
+impl<T> fmt::Debug for WechatPayNotifyLayer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WechatPayNotifyLayer").finish()
+    }
+}
+
+impl<S, T> Layer<S> for WechatPayNotifyLayer<T> {
+    type Service = WechatPayNotifyService<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        WechatPayNotifyService {
+            client: self.client.clone(),
+            inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// 微信支付通知中间件。参见 `WechatPayNotifyLayer`。
+pub struct WechatPayNotifyService<S, T = NotificationEvent> {
+    client: WechatPayClient,
+    inner: S,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<S: Clone, T> Clone for WechatPayNotifyService<S, T> {
+    fn clone(&self) -> Self {
+        WechatPayNotifyService {
+            client: self.client.clone(),
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<S: fmt::Debug, T> fmt::Debug for WechatPayNotifyService<S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WechatPayNotifyService")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<S, T, B, ResBody> Service<Request<B>> for WechatPayNotifyService<S, T>
+where
+    S: Service<Request<T>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    T: FromNotification + Send + 'static,
+    B: HttpBody + Unpin + Send + 'static,
+    B::Data: Send,
+    B::Error: fmt::Display,
+    ResBody: From<String>,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let client = self.client.clone();
+        // 使用已 poll_ready 的 inner，并留下一个克隆供下次使用
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            match decode::<T, B>(&client, req).await {
+                Ok(req) => inner.call(req).await,
+                Err(e) => Ok(rejection(e)),
+            }
+        })
+    }
+}
+
+async fn decode<T, B>(client: &WechatPayClient, req: Request<B>) -> crate::error::Result<Request<T>>
+where
+    T: FromNotification,
+    B: HttpBody + Unpin,
+    B::Error: fmt::Display,
+{
+    let req = client.verify_notification(req).await?;
+    let (parts, body) = req.into_parts();
+    let noti: WechatPayNotification = serde_json::from_slice(&body)?;
+    let event = T::from_notification(client, &noti)?;
+    Ok(Request::from_parts(parts, event))
+}
+
+fn rejection<ResBody: From<String>>(e: WechatPayError) -> Response<ResBody> {
+    let (status, body) = NotifyResponse::from_error(&e);
+    let mut res = Response::new(ResBody::from(body.to_json()));
+    *res.status_mut() = status;
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{mock_client, mock_notification};
+    use http::StatusCode;
+    use std::convert::Infallible;
+    use tower::{service_fn, ServiceExt};
+
+    #[tokio::test]
+    async fn test_forward_signed_notification() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        let plain = r#"{"stock_creator_mchid":"1900000001","stock_id":"9856000","coupon_id":"98674556","status":"USED"}"#;
+        let body = mock_notification("EV-1", "COUPON.USE", "coupon", plain);
+
+        let svc = WechatPayNotifyLayer::<NotificationEvent>::new(client).layer(service_fn(
+            |req: Request<NotificationEvent>| async move {
+                let stock_id = match req.into_body() {
+                    NotificationEvent::Coupon(coupon) => coupon.stock_id,
+                    event => panic!("unexpected event: {:?}", event),
+                };
+                Ok::<_, Infallible>(Response::new(stock_id))
+            },
+        ));
+        let mut req =
+            Request::post("/notify").body(http_body::Full::<bytes::Bytes>::from(body.clone()))?;
+        *req.headers_mut() = mock.signature_headers(&body)?;
+        let res = svc.oneshot(req).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.body(), "9856000");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_unsigned_notification() -> anyhow::Result<()> {
+        let (client, _) = mock_client().await?;
+
+        let svc = WechatPayNotifyLayer::<NotificationEvent>::new(client).layer(service_fn(
+            |_req: Request<NotificationEvent>| async {
+                Ok::<_, Infallible>(Response::new(String::new()))
+            },
+        ));
+        let req = Request::post("/notify").body(http_body::Full::<bytes::Bytes>::from("{}"))?;
+        let res = svc.oneshot(req).await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(res.body().contains("FAIL"));
+        Ok(())
+    }
+}
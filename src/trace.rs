@@ -0,0 +1,102 @@
+//! tracing 埋点，启用 `tracing` feature 时可用。
+//! 每次 API 调用创建一个 span，记录路径、商户号、耗时、状态码及错误码。
+//! 记录请求 header 时，Authorization 等敏感信息会被脱敏。
+
+use crate::error::Result;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, Request, Response};
+use std::fmt;
+use std::future::Future;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+/// 需要脱敏的 header
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "cookie", "proxy-authorization"];
+
+/// 为一次 API 调用创建 span。
+pub(crate) fn request_span(mch_id: &str, method: &Method, path: &str) -> Span {
+    tracing::info_span!(
+        "wechatpay_request",
+        method = %method,
+        path = %path,
+        mchid = %mch_id,
+        status = Empty,
+        error_code = Empty,
+        elapsed_ms = Empty,
+    )
+}
+
+/// 在 span 中执行 API 调用，并记录耗时、状态码及错误码。
+pub(crate) async fn instrument<F>(span: Span, fut: F) -> Result<Response>
+where
+    F: Future<Output = Result<Response>>,
+{
+    let start = Instant::now();
+    let res = fut.instrument(span.clone()).await;
+    span.record("elapsed_ms", start.elapsed().as_millis() as u64);
+    match &res {
+        Ok(res) => {
+            span.record("status", res.status().as_u16());
+        }
+        Err(e) => {
+            if let Some(api_error) = e.api_error() {
+                span.record("error_code", api_error.code());
+                if let Some(status) = api_error.status() {
+                    span.record("status", status.as_u16());
+                }
+            }
+            span.in_scope(|| tracing::warn!(error = %e, "wechatpay request failed"));
+        }
+    }
+    res
+}
+
+/// 记录实际发送的请求，header 已脱敏。
+pub(crate) fn log_request(req: &Request) {
+    tracing::debug!(
+        method = %req.method(),
+        url = %req.url(),
+        headers = ?RedactedHeaders(req.headers()),
+        "sending request"
+    );
+}
+
+/// 脱敏后的 header，用于输出日志。
+struct RedactedHeaders<'a>(&'a HeaderMap);
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, value) in self.0 {
+            if SENSITIVE_HEADERS.contains(&name.as_str()) {
+                map.entry(&name.as_str(), &"***");
+            } else {
+                map.entry(&name.as_str(), &value);
+            }
+        }
+        map.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::AUTHORIZATION;
+
+    #[test]
+    fn test_redacted_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            "WECHATPAY2-SHA256-RSA2048 mchid=\"1900000001\",signature=\"secret\""
+                .parse()
+                .unwrap(),
+        );
+        headers.insert("Accept", "application/json".parse().unwrap());
+        let s = format!("{:?}", RedactedHeaders(&headers));
+        assert!(!s.contains("secret"));
+        assert!(s.contains("\"authorization\": \"***\""));
+        assert!(s.contains("application/json"));
+    }
+}
@@ -1,24 +1,28 @@
 //! 交易相关接口的实现
 
-use crate::client::{WechatPayClient, BASE_URL};
-use crate::credential::generate_none_str;
-use crate::util::option_datetime_fmt;
-use anyhow::Result;
+use crate::client::WechatPayClient;
+use crate::error::{Result, WechatPayError};
+use crate::ids::OutTradeNo;
+use crate::money::Fen;
+use crate::util::{datetime_fmt, option_datetime_fmt};
 use base64::prelude::*;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local, TimeZone};
+use futures::future::join_all;
 use rand::Rng;
-use rsa::pkcs1v15::SigningKey;
-use rsa::sha2::Sha256;
-use rsa::signature::{RandomizedSigner, SignatureEncoding};
+use reqwest::Url;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use tokio::sync::Semaphore;
 
 impl WechatPayClient {
     /// JSAPI 下单，返回 prepay_id。
     /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_1.shtml>
     pub async fn jsapi_create_trade(&self, params: &JsApiCreateTradeParams) -> Result<String> {
-        let url = format!("{}/pay/transactions/jsapi", BASE_URL);
+        let url = format!("{}/pay/transactions/jsapi", self.base_url);
         let req = self.client.post(url).json(params).build()?;
-        let res = self.execute(req).await?;
+        let res = self.execute_idempotent(req).await?;
         let res: JsApiCreateTradeResponse = res.json().await?;
         Ok(res.prepay_id)
     }
@@ -26,9 +30,9 @@ impl WechatPayClient {
     /// APP 下单，返回 `prepay_id`。
     /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_2_1.shtml>
     pub async fn app_create_trade(&self, params: &AppCreateTradeParams) -> Result<String> {
-        let url = format!("{}/pay/transactions/app", BASE_URL);
+        let url = format!("{}/pay/transactions/app", self.base_url);
         let req = self.client.post(url).json(params).build()?;
-        let res = self.execute(req).await?;
+        let res = self.execute_idempotent(req).await?;
         let res: AppCreateTradeResponse = res.json().await?;
         Ok(res.prepay_id)
     }
@@ -36,20 +40,30 @@ impl WechatPayClient {
     /// H5 下单，返回 h5_url。
     /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_3_1.shtml>
     pub async fn h5_create_trade(&self, params: &H5CreateTradeParams) -> Result<String> {
-        let url = format!("{}/pay/transactions/h5", BASE_URL);
+        let url = format!("{}/pay/transactions/h5", self.base_url);
         let req = self.client.post(url).json(params).build()?;
-        let res = self.execute(req).await?;
+        let res = self.execute_idempotent(req).await?;
         let res: H5CreateTradeResponse = res.json().await?;
         Ok(res.h5_url)
     }
 
+    /// H5 下单，返回的 `H5PayUrl` 中带有生成时间，便于判断是否已过期。
+    pub async fn h5_create_trade_url(&self, params: &H5CreateTradeParams) -> Result<H5PayUrl> {
+        let h5_url = self.h5_create_trade(params).await?;
+        let created_at = Local
+            .timestamp_opt(self.clock.timestamp(), 0)
+            .single()
+            .unwrap_or_else(Local::now);
+        Ok(H5PayUrl { h5_url, created_at })
+    }
+
     /// Native 下单，返回二维码 url (code_url)。
     /// code_url 用于生成支付二维码，然后提供给用户扫码支付。
     /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_4_1.shtml>
     pub async fn native_create_trade(&self, params: &NativeCreateTradeParams) -> Result<String> {
-        let url = format!("{}/pay/transactions/native", BASE_URL);
+        let url = format!("{}/pay/transactions/native", self.base_url);
         let req = self.client.post(url).json(params).build()?;
-        let res = self.execute(req).await?;
+        let res = self.execute_idempotent(req).await?;
         let res: NativeCreateTradeResponse = res.json().await?;
         Ok(res.code_url)
     }
@@ -62,7 +76,9 @@ impl WechatPayClient {
     ) -> Result<TradeQueryResponse> {
         let url = format!(
             "{}/pay/transactions/id/{}?mchid={}",
-            BASE_URL, transaction_id, &self.mch_credential.mch_id
+            self.base_url,
+            transaction_id,
+            &self.mch_credential().mch_id
         );
         let req = self.client.get(url).build()?;
         let res = self.execute(req).await?;
@@ -78,7 +94,9 @@ impl WechatPayClient {
     ) -> Result<TradeQueryResponse> {
         let url = format!(
             "{}/pay/transactions/out-trade-no/{}?mchid={}",
-            BASE_URL, out_trade_no, &self.mch_credential.mch_id
+            self.base_url,
+            out_trade_no,
+            &self.mch_credential().mch_id
         );
         let req = self.client.get(url).build()?;
         let res = self.execute(req).await?;
@@ -97,46 +115,230 @@ impl WechatPayClient {
 
         let url = format!(
             "{}/pay/transactions/out-trade-no/{}/close",
-            BASE_URL, out_trade_no
+            self.base_url, out_trade_no
         );
         let req = CloseTradeRequest {
-            mch_id: self.mch_credential.mch_id.clone(),
+            mch_id: self.mch_credential().mch_id.clone(),
         };
         let req = self.client.post(url).json(&req).build()?;
-        let _res = self.execute(req).await?;
+        let _res = self.execute_idempotent(req).await?;
         Ok(())
     }
+
+    /// 通过商户订单号并发查询多笔订单，同时进行的查询不超过 concurrency 个。
+    /// 单笔查询失败不影响其他订单，成功的订单及失败的原因分别汇总在结果中。适用于对账、补偿等任务。
+    pub async fn query_trades<S: AsRef<str>>(
+        &self,
+        out_trade_nos: &[S],
+        concurrency: usize,
+    ) -> QueryTradesResult {
+        let semaphore = Semaphore::new(concurrency.max(1));
+        let queries = out_trade_nos.iter().map(|out_trade_no| async {
+            let out_trade_no = out_trade_no.as_ref();
+            let res = match semaphore.acquire().await {
+                Ok(_permit) => self.query_trade_by_out_trade_no(out_trade_no).await,
+                Err(e) => Err(WechatPayError::Other(e.to_string())),
+            };
+            (out_trade_no.to_string(), res)
+        });
+
+        let mut result = QueryTradesResult::default();
+        for (out_trade_no, res) in join_all(queries).await {
+            match res {
+                Ok(trade) => result.trades.push(trade),
+                Err(e) => result.errors.push((out_trade_no, e)),
+            }
+        }
+        result
+    }
 }
 
 impl WechatPayClient {
+    /// 简化的 JSAPI 下单。
+    /// 使用 builder 中设置的默认 app_id、notify_url 及凭证中的 mch_id，自动生成 out_trade_no，
+    /// 并对返回的 prepay_id 进行签名。amount 单位为分。
+    pub async fn jsapi_pay(
+        &self,
+        openid: &str,
+        amount: impl Into<Fen>,
+        description: &str,
+    ) -> Result<JsApiPayResponse> {
+        let app_id = self.default_app_id()?;
+        let params = JsApiCreateTradeParams::new(
+            app_id.clone(),
+            self.mch_credential().mch_id.clone(),
+            description.to_string(),
+            OutTradeNo::generate(),
+            None,
+            None,
+            self.default_notify_url()?,
+            Amount::new_with_cny(amount),
+            openid.to_string(),
+        );
+        let prepay_id = self.jsapi_create_trade(&params).await?;
+        let signature = self.sign_jsapi_trade(&prepay_id, &app_id).await?;
+        Ok(JsApiPayResponse {
+            out_trade_no: params.out_trade_no,
+            prepay_id,
+            signature,
+        })
+    }
+
+    /// 简化的 Native 下单，返回商户订单号及二维码 url (code_url)。
+    /// 默认值的使用同 `jsapi_pay`。amount 单位为分。
+    pub async fn native_pay(
+        &self,
+        amount: impl Into<Fen>,
+        description: &str,
+    ) -> Result<NativePayResponse> {
+        let params = NativeCreateTradeParams {
+            app_id: self.default_app_id()?,
+            mch_id: self.mch_credential().mch_id.clone(),
+            description: description.to_string(),
+            out_trade_no: OutTradeNo::generate(),
+            time_expire: None,
+            attach: None,
+            notify_url: self.default_notify_url()?,
+            goods_tag: None,
+            support_fapiao: None,
+            amount: Amount::new_with_cny(amount),
+            detail: None,
+            scene_info: None,
+            settle_info: None,
+        };
+        let code_url = self.native_create_trade(&params).await?;
+        Ok(NativePayResponse {
+            out_trade_no: params.out_trade_no,
+            code_url,
+        })
+    }
+
+    fn default_app_id(&self) -> Result<String> {
+        self.default_app_id
+            .clone()
+            .ok_or_else(|| WechatPayError::InvalidParams("missing default `app_id`".to_string()))
+    }
+
+    fn default_notify_url(&self) -> Result<String> {
+        self.default_notify_url.clone().ok_or_else(|| {
+            WechatPayError::InvalidParams("missing default `notify_url`".to_string())
+        })
+    }
+
     /// 对 JSAPI 下单返回的 prepay_id 进行签名。
     /// 前端在调起微信支付时，需要这些参数。
     /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter3_1_4.shtml>
-    pub fn sign_jsapi_trade(&self, prepay_id: &str, app_id: &str) -> JsApiTradeSignature {
-        let timestamp = Local::now().timestamp();
-        let nonce_str = generate_none_str(32);
+    /// 配置了 `request_signer` 时由其签名，否则使用商户私钥签名。
+    pub async fn sign_jsapi_trade(
+        &self,
+        prepay_id: &str,
+        app_id: &str,
+    ) -> Result<JsApiTradeSignature> {
+        let timestamp = self.clock.timestamp();
+        let nonce_str = self.nonce_provider.nonce();
         let package = format!("prepay_id={}", prepay_id);
         let msg = format!("{}\n{}\n{}\n{}\n", app_id, timestamp, nonce_str, package);
 
-        let mut rng = rand::thread_rng();
-        let signing_key =
-            SigningKey::<Sha256>::new(self.mch_credential.mch_rsa_private_key.clone());
-        let signature = signing_key
-            .sign_with_rng(&mut rng, msg.as_bytes())
-            .to_bytes();
-        let signature = BASE64_STANDARD.encode(&signature);
+        let signature = match &self.request_signer {
+            Some(signer) => signer.sign(msg.as_bytes()).await?,
+            None => self
+                .mch_credential
+                .load()
+                .signer()
+                .sign_message(msg.as_bytes()),
+        };
+        let signature = BASE64_STANDARD.encode(signature);
 
-        JsApiTradeSignature {
+        Ok(JsApiTradeSignature {
             app_id: app_id.to_string(),
             timestamp: timestamp.to_string(),
             nonce_str,
             package,
             sign_type: "RSA".to_string(),
             pay_sign: signature,
-        }
+        })
     }
 }
 
+/// 简化的 JSAPI 下单的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct JsApiPayResponse {
+    /// 自动生成的商户订单号
+    pub out_trade_no: OutTradeNo,
+    pub prepay_id: String,
+    /// 前端调起支付所需的参数
+    pub signature: JsApiTradeSignature,
+}
+
+/// 简化的 Native 下单的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct NativePayResponse {
+    /// 自动生成的商户订单号
+    pub out_trade_no: OutTradeNo,
+    /// 二维码 url
+    pub code_url: String,
+}
+
+/// H5 支付跳转链接。h5_url 的有效期为 5 分钟，过期后需重新下单获取。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct H5PayUrl {
+    pub h5_url: String,
+    /// 生成时间
+    #[serde(with = "datetime_fmt")]
+    pub created_at: DateTime<Local>,
+}
+
+impl H5PayUrl {
+    /// h5_url 的有效期
+    pub const VALIDITY_SECS: i64 = 5 * 60;
+
+    /// 过期时间
+    pub fn expire_time(&self) -> DateTime<Local> {
+        self.created_at + Duration::seconds(Self::VALIDITY_SECS)
+    }
+
+    /// 是否已过期
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Local::now())
+    }
+
+    /// 在 now 时是否已过期
+    pub fn is_expired_at(&self, now: DateTime<Local>) -> bool {
+        now >= self.expire_time()
+    }
+
+    /// 在 h5_url 后追加 redirect_url，用户支付完成后将跳转到该地址。
+    /// 参见 `append_redirect_url`。
+    pub fn with_redirect_url(&self, redirect_url: &str) -> Result<String> {
+        append_redirect_url(&self.h5_url, redirect_url)
+    }
+}
+
+/// 在 h5_url 后追加经过 urlencode 的 redirect_url 参数。
+/// 注意 redirect_url 的域名须与商户在微信支付后台配置的 H5 支付域名一致，
+/// 且跳转后并不代表支付成功，仍应通过查单或支付通知确认支付结果。
+pub fn append_redirect_url(h5_url: &str, redirect_url: &str) -> Result<String> {
+    let mut url = Url::parse(h5_url)
+        .map_err(|e| WechatPayError::InvalidParams(format!("invalid h5_url: {}", e)))?;
+    Url::parse(redirect_url)
+        .map_err(|e| WechatPayError::InvalidParams(format!("invalid redirect_url: {}", e)))?;
+    url.query_pairs_mut()
+        .append_pair("redirect_url", redirect_url);
+    Ok(url.into())
+}
+
+/// 批量查询订单的结果，见 `WechatPayClient::query_trades`。
+#[derive(Debug, Default)]
+pub struct QueryTradesResult {
+    /// 查询成功的订单，顺序与传入的商户订单号一致
+    pub trades: Vec<TradeQueryResponse>,
+    /// 查询失败的商户订单号及失败原因
+    pub errors: Vec<(String, WechatPayError)>,
+}
+
 /// JSAPI 下单时，针对返回的 prepay_id 生成的签名，
 /// 前端在调起微信支付时，需要这些参数。
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -151,6 +353,34 @@ pub struct JsApiTradeSignature {
     pub pay_sign: String,
 }
 
+impl JsApiTradeSignature {
+    /// 小程序 `wx.requestPayment` 及 JSAPI `WeixinJSBridge.invoke('getBrandWCPayRequest', ...)` 所需的参数。
+    /// 字段名为 appId、timeStamp、nonceStr、package、signType、paySign。
+    pub fn to_wx_request_payment_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "appId": self.app_id,
+            "timeStamp": self.timestamp,
+            "nonceStr": self.nonce_str,
+            "package": self.package,
+            "signType": self.sign_type,
+            "paySign": self.pay_sign,
+        })
+    }
+
+    /// JS-SDK `wx.chooseWXPay` 所需的参数。
+    /// 字段名为 timestamp、nonceStr、package、signType、paySign，注意 timestamp 全小写，
+    /// 且不含 appId(已在 `wx.config` 中指定)。
+    pub fn to_choose_wx_pay_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "timestamp": self.timestamp,
+            "nonceStr": self.nonce_str,
+            "package": self.package,
+            "signType": self.sign_type,
+            "paySign": self.pay_sign,
+        })
+    }
+}
+
 /// JSAPI 下单参数
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsApiCreateTradeParams {
@@ -164,7 +394,7 @@ pub struct JsApiCreateTradeParams {
     pub description: String,
     /// 商户订单号。商户系统内部订单号，需在同一个商户号下唯一。只能是数字、大小写字母_-*组成
     /// 长度应在 [6, 32] 字符之间
-    pub out_trade_no: String,
+    pub out_trade_no: OutTradeNo,
     /// 订单失效时间
     #[serde(with = "option_datetime_fmt", skip_serializing_if = "Option::is_none")]
     pub time_expire: Option<DateTime<Local>>,
@@ -224,7 +454,7 @@ struct NativeCreateTradeResponse {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Amount {
     /// 订单总金额，单位为分。
-    pub total: i32,
+    pub total: Fen,
     /// 货币类型。CNY：人民币，境内商户号仅支持人民币。
     pub currency: String,
 }
@@ -233,17 +463,18 @@ pub struct Amount {
 /// total, currency 为下单时的金额信息；
 /// payer_total, payer_currency 为用户实际支付的金额信息。
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct PaidAmount {
     /// 订单总金额，单位为分。
     #[serde(skip_serializing_if = "Option::is_none", default)]
-    pub total: Option<i32>,
+    pub total: Option<Fen>,
     /// 货币类型。CNY：人民币，境内商户号仅支持人民币。
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub currency: Option<String>,
 
     /// 用户支付金额，单位为分。
     #[serde(skip_serializing_if = "Option::is_none", default)]
-    pub payer_total: Option<i32>,
+    pub payer_total: Option<Fen>,
     /// 用户支付币种
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub payer_currency: Option<String>,
@@ -251,9 +482,9 @@ pub struct PaidAmount {
 
 impl Amount {
     /// 以人民币为单位的订单金额(单位: 分)
-    pub fn new_with_cny(total: i32) -> Amount {
+    pub fn new_with_cny(total: impl Into<Fen>) -> Amount {
         Amount {
-            total,
+            total: total.into(),
             currency: "CNY".to_string(),
         }
     }
@@ -283,7 +514,7 @@ pub struct CreateTradePromotionDetail {
     /// 1、商户侧一张小票订单可能被分多次支付，订单原价用于记录整张小票的交易金额。
     /// 2、当订单原价与支付金额不相等，则不享受优惠。
     /// 3、该字段主要用于防止同一张小票分多次支付，以享受多次优惠的情况，正常支付订单不必上传此参数。
-    pub cost_price: Option<i32>,
+    pub cost_price: Option<Fen>,
     /// 商品小票ID
     pub invoice_id: Option<String>,
     /// 单品列表
@@ -292,6 +523,7 @@ pub struct CreateTradePromotionDetail {
 
 /// 优惠功能
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct TradePromotionDetail {
     /// 券ID
     pub coupon_id: String,
@@ -307,18 +539,19 @@ pub struct TradePromotionDetail {
     #[serde(rename = "type")]
     pub promotion_type: Option<String>,
     /// 优惠券面额
-    pub amount: i32,
+    pub amount: Fen,
     /// 活动ID
     pub stock_id: Option<String>,
     /// 微信出资，单位为分
-    pub wechatpay_contribute: Option<i32>,
+    pub wechatpay_contribute: Option<Fen>,
     /// 商户出资，单位为分
-    pub merchant_contribute: Option<i32>,
+    pub merchant_contribute: Option<Fen>,
     /// 其他出资，单位为分
-    pub other_contribute: Option<i32>,
+    pub other_contribute: Option<Fen>,
     /// 优惠币种。CNY：人民币，境内商户号仅支持人民币。
     pub currency: Option<String>,
     /// 商品列表
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub goods_detail: Vec<TradeGoodsDetail>,
 }
 
@@ -337,20 +570,21 @@ pub struct CreateTradeGoodsDetail {
     /// 商品数量
     pub quantity: i32,
     /// 商品单价，单位为分。如果商户有优惠，需传输商户优惠后的单价。
-    pub unit_price: i32,
+    pub unit_price: Fen,
 }
 
 /// 单品信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct TradeGoodsDetail {
     /// 商品编码
     pub goods_id: String,
     /// 用户购买的商品数量
     pub quantity: i32,
     /// 商品单价，单位为分
-    pub unit_price: i32,
+    pub unit_price: Fen,
     /// 商品优惠金额
-    pub discount_amount: i32,
+    pub discount_amount: Fen,
     /// 商品备注信息
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub goods_remark: Option<String>,
@@ -369,6 +603,7 @@ pub struct CreateTradeSceneInfo {
 
 /// 场景信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct TradeSceneInfo {
     /// 商户端设备号（发起扣款请求的商户服务器设备号）。
     #[serde(skip_serializing_if = "Option::is_none", default)]
@@ -401,7 +636,7 @@ impl JsApiCreateTradeParams {
         app_id: String,
         mch_id: String,
         description: String,
-        out_trade_no: String,
+        out_trade_no: impl Into<OutTradeNo>,
         time_expire: Option<DateTime<Local>>,
         attach: Option<String>,
         notify_url: String,
@@ -412,7 +647,7 @@ impl JsApiCreateTradeParams {
             app_id,
             mch_id,
             description,
-            out_trade_no,
+            out_trade_no: out_trade_no.into(),
             time_expire,
             attach,
             notify_url,
@@ -441,7 +676,7 @@ pub struct AppCreateTradeParams {
     pub description: String,
     /// 商户订单号。商户系统内部订单号，需在同一个商户号下唯一。只能是数字、大小写字母_-*组成
     /// 长度应在 [6, 32] 字符之间
-    pub out_trade_no: String,
+    pub out_trade_no: OutTradeNo,
     /// 订单失效时间
     #[serde(with = "option_datetime_fmt", skip_serializing_if = "Option::is_none")]
     pub time_expire: Option<DateTime<Local>>,
@@ -484,7 +719,7 @@ pub struct H5CreateTradeParams {
     pub description: String,
     /// 商户订单号。商户系统内部订单号，需在同一个商户号下唯一。只能是数字、大小写字母_-*组成
     /// 长度应在 [6, 32] 字符之间
-    pub out_trade_no: String,
+    pub out_trade_no: OutTradeNo,
     /// 订单失效时间
     #[serde(with = "option_datetime_fmt", skip_serializing_if = "Option::is_none")]
     pub time_expire: Option<DateTime<Local>>,
@@ -526,7 +761,7 @@ pub struct NativeCreateTradeParams {
     pub description: String,
     /// 商户订单号。商户系统内部订单号，需在同一个商户号下唯一。只能是数字、大小写字母_-*组成
     /// 长度应在 [6, 32] 字符之间
-    pub out_trade_no: String,
+    pub out_trade_no: OutTradeNo,
     /// 订单失效时间
     #[serde(with = "option_datetime_fmt", skip_serializing_if = "Option::is_none")]
     pub time_expire: Option<DateTime<Local>>,
@@ -557,6 +792,7 @@ pub struct NativeCreateTradeParams {
 
 /// 订单查询响应
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct TradeQueryResponse {
     /// 应用 ID
     #[serde(rename = "appid")]
@@ -603,10 +839,16 @@ pub struct TradeQueryResponse {
     /// 优惠功能，享受优惠时返回该字段
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub promotion_detail: Vec<TradePromotionDetail>,
+
+    /// 未在本结构中定义的字段。微信支付新增字段时，可从中取得原始数据。
+    /// 启用 `extra-fields` feature 时可用。
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// 交易类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TradeType {
     JsApi,
     Native,
@@ -617,9 +859,11 @@ pub enum TradeType {
     Mweb,
     /// 刷脸支付
     Facepay,
+    /// 其他类型。微信支付新增的枚举值，保留原文。
+    Other(String),
 }
 impl TradeType {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             TradeType::JsApi => "JSAPI",
             TradeType::Native => "NATIVE",
@@ -627,16 +871,16 @@ impl TradeType {
             TradeType::Micropay => "MICROPAY",
             TradeType::Mweb => "MWEB",
             TradeType::Facepay => "FACEPAY",
+            TradeType::Other(s) => s,
         }
     }
 }
 
-impl<'de> Deserialize<'de> for TradeType {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?.to_ascii_uppercase();
+impl FromStr for TradeType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_uppercase();
         match s.as_str() {
             "JSAPI" => Ok(TradeType::JsApi),
             "NATIVE" => Ok(TradeType::Native),
@@ -644,14 +888,27 @@ impl<'de> Deserialize<'de> for TradeType {
             "MICROPAY" => Ok(TradeType::Micropay),
             "MWEB" => Ok(TradeType::Mweb),
             "FACEPAY" => Ok(TradeType::Facepay),
-            _ => Err(serde::de::Error::custom(format!(
-                "unknown trade type: {}",
-                s
-            ))),
+            _ => Ok(TradeType::Other(s)),
         }
     }
 }
 
+impl fmt::Display for TradeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TradeType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+}
+
 impl Serialize for TradeType {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -663,7 +920,7 @@ impl Serialize for TradeType {
 }
 
 /// 交易状态
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TradeState {
     /// 支付成功
     Success,
@@ -679,10 +936,12 @@ pub enum TradeState {
     UserPaying,
     /// 支付失败（仅付款码支付会返回）
     PayError,
+    /// 其他状态。微信支付新增的枚举值，保留原文。
+    Other(String),
 }
 
 impl TradeState {
-    pub fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &str {
         match self {
             TradeState::Success => "SUCCESS",
             TradeState::Refund => "REFUND",
@@ -691,16 +950,16 @@ impl TradeState {
             TradeState::Revoked => "REVOKED",
             TradeState::UserPaying => "USERPAYING",
             TradeState::PayError => "PAYERROR",
+            TradeState::Other(s) => s,
         }
     }
 }
 
-impl<'de> Deserialize<'de> for TradeState {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?.to_ascii_uppercase();
+impl FromStr for TradeState {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_uppercase();
         match s.as_str() {
             "SUCCESS" => Ok(TradeState::Success),
             "REFUND" => Ok(TradeState::Refund),
@@ -709,14 +968,27 @@ impl<'de> Deserialize<'de> for TradeState {
             "REVOKED" => Ok(TradeState::Revoked),
             "USERPAYING" => Ok(TradeState::UserPaying),
             "PAYERROR" => Ok(TradeState::PayError),
-            _ => Err(serde::de::Error::custom(format!(
-                "unknown trade state: {}",
-                s
-            ))),
+            _ => Ok(TradeState::Other(s)),
         }
     }
 }
 
+impl fmt::Display for TradeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TradeState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+}
+
 impl Serialize for TradeState {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -766,6 +1038,18 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_display_from_str() {
+        assert_eq!(TradeType::JsApi.to_string(), "JSAPI");
+        assert_eq!("native".parse::<TradeType>(), Ok(TradeType::Native));
+        assert_eq!(TradeState::UserPaying.to_string(), "USERPAYING");
+        assert_eq!("NOTPAY".parse::<TradeState>(), Ok(TradeState::NotPay));
+        assert_eq!(
+            "NEW_STATE".parse::<TradeState>(),
+            Ok(TradeState::Other("NEW_STATE".to_string()))
+        );
+    }
+
     #[test]
     fn test_trade_state_serde() -> anyhow::Result<()> {
         #[derive(Debug, Serialize, Deserialize)]
@@ -781,6 +1065,164 @@ mod tests {
 
         let w2: Wrapper = serde_json::from_str(r#"{"ts":"NOTPAY"}"#)?;
         assert_eq!(w2.ts, TradeState::NotPay);
+
+        let w3: Wrapper = serde_json::from_str(r#"{"ts":"NEW_STATE"}"#)?;
+        assert_eq!(w3.ts, TradeState::Other("NEW_STATE".to_string()));
+        assert_eq!(serde_json::to_string(&w3)?, r#"{"ts":"NEW_STATE"}"#);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trade_query_response_forward_compatible() -> anyhow::Result<()> {
+        // 未知字段被忽略，条件返回的字段缺失时为空
+        let res: TradeQueryResponse = serde_json::from_str(
+            r#"{
+                "appid": "wxd678efh567hg6787",
+                "mchid": "1230000109",
+                "out_trade_no": "1217752501201407033233368018",
+                "trade_state": "NEW_STATE",
+                "trade_state_desc": "新的状态",
+                "new_field": {"value": 1},
+                "promotion_detail": [{"coupon_id": "109519", "amount": 100}]
+            }"#,
+        )?;
+        assert_eq!(res.trade_state, TradeState::Other("NEW_STATE".to_string()));
+        assert!(res.transaction_id.is_none());
+        assert!(res.promotion_detail[0].goods_detail.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_trades() -> anyhow::Result<()> {
+        use crate::transport::mock_client;
+        use reqwest::{Method, StatusCode};
+
+        let (client, mock) = mock_client().await?;
+
+        for out_trade_no in ["order-1", "order-3"] {
+            let body = serde_json::json!({
+                "appid": "wxd678efh567hg6787",
+                "mchid": "1900000001",
+                "out_trade_no": out_trade_no,
+                "trade_state": "NOTPAY",
+                "trade_state_desc": "订单未支付",
+            });
+            mock.on(
+                Method::GET,
+                &format!("/v3/pay/transactions/out-trade-no/{}", out_trade_no),
+                StatusCode::OK,
+                &body.to_string(),
+            );
+        }
+
+        let result = client
+            .query_trades(&["order-1", "order-2", "order-3"], 2)
+            .await;
+        let out_trade_nos: Vec<_> = result
+            .trades
+            .iter()
+            .map(|t| t.out_trade_no.as_str())
+            .collect();
+        assert_eq!(out_trade_nos, ["order-1", "order-3"]);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].0, "order-2");
+        assert_eq!(mock.requests().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sign_jsapi_trade_with_request_signer() -> anyhow::Result<()> {
+        use crate::credential::RequestSigner;
+        use crate::transport::mock_client_with;
+        use rsa::pkcs1v15::{Signature, SigningKey, VerifyingKey};
+        use rsa::sha2::Sha256;
+        use rsa::signature::{RandomizedSigner, SignatureEncoding, Verifier};
+        use rsa::RsaPrivateKey;
+
+        // 私钥不在凭证中，由外部签名器(如 KMS)签名
+        struct RemoteSigner(SigningKey<Sha256>);
+
+        #[async_trait::async_trait]
+        impl RequestSigner for RemoteSigner {
+            async fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+                Ok(self
+                    .0
+                    .sign_with_rng(&mut rand::thread_rng(), message)
+                    .to_vec())
+            }
+        }
+
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)?;
+        let signer = RemoteSigner(SigningKey::new(private_key.clone()));
+        let (client, _) = mock_client_with(|builder| {
+            builder.request_signer(signer);
+        })
+        .await?;
+
+        let signature = client
+            .sign_jsapi_trade("wx201410272009395522657a690389285100", "wx8888888888888888")
+            .await?;
+        let msg = format!(
+            "{}\n{}\n{}\n{}\n",
+            signature.app_id, signature.timestamp, signature.nonce_str, signature.package
+        );
+        let pay_sign =
+            Signature::try_from(BASE64_STANDARD.decode(&signature.pay_sign)?.as_slice())?;
+        VerifyingKey::<Sha256>::new(private_key.to_public_key())
+            .verify(msg.as_bytes(), &pay_sign)?;
+        assert!(VerifyingKey::<Sha256>::new(
+            client.mch_credential().mch_rsa_private_key.to_public_key()
+        )
+        .verify(msg.as_bytes(), &pay_sign)
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jsapi_trade_signature_json() {
+        let signature = JsApiTradeSignature {
+            app_id: "wxd678efh567hg6787".to_string(),
+            timestamp: "1414561699".to_string(),
+            nonce_str: "5K8264ILTKCH16CQ2502SI8ZNMTM67VS".to_string(),
+            package: "prepay_id=wx201410272009395522657a690389285100".to_string(),
+            sign_type: "RSA".to_string(),
+            pay_sign: "oR9d8PuhnIc+YZ8cBHFCwfgpaK9gd7vaRvkYD7rthRAZ".to_string(),
+        };
+
+        let v = signature.to_wx_request_payment_json();
+        assert_eq!(v["appId"], "wxd678efh567hg6787");
+        assert_eq!(v["timeStamp"], "1414561699");
+        assert_eq!(v["nonceStr"], "5K8264ILTKCH16CQ2502SI8ZNMTM67VS");
+        assert_eq!(
+            v["package"],
+            "prepay_id=wx201410272009395522657a690389285100"
+        );
+        assert_eq!(v["signType"], "RSA");
+        assert_eq!(v["paySign"], "oR9d8PuhnIc+YZ8cBHFCwfgpaK9gd7vaRvkYD7rthRAZ");
+
+        let v = signature.to_choose_wx_pay_json();
+        assert_eq!(v["timestamp"], "1414561699");
+        assert!(v.get("timeStamp").is_none());
+        assert!(v.get("appId").is_none());
+        assert_eq!(v["paySign"], "oR9d8PuhnIc+YZ8cBHFCwfgpaK9gd7vaRvkYD7rthRAZ");
+    }
+
+    #[test]
+    fn test_h5_pay_url() -> anyhow::Result<()> {
+        let h5_url = H5PayUrl {
+            h5_url: "https://wx.tenpay.com/cgi-bin/mmpayweb-bin/checkmweb?prepay_id=wx2016121516420242444321ca0631331346&package=1405458241".to_string(),
+            created_at: Local.timestamp_opt(1_600_000_000, 0).unwrap(),
+        };
+        assert_eq!(
+            h5_url.with_redirect_url("https://www.wechatpay.com.cn/pay?a=1&b=2")?,
+            "https://wx.tenpay.com/cgi-bin/mmpayweb-bin/checkmweb?prepay_id=wx2016121516420242444321ca0631331346&package=1405458241&redirect_url=https%3A%2F%2Fwww.wechatpay.com.cn%2Fpay%3Fa%3D1%26b%3D2"
+        );
+        assert!(h5_url.with_redirect_url("/pay").is_err());
+
+        let expire_time = Local.timestamp_opt(1_600_000_300, 0).unwrap();
+        assert_eq!(h5_url.expire_time(), expire_time);
+        assert!(!h5_url.is_expired_at(expire_time - Duration::seconds(1)));
+        assert!(h5_url.is_expired_at(expire_time));
         Ok(())
     }
 
@@ -0,0 +1,827 @@
+//! 商家转账相关接口。
+
+use crate::client::WechatPayClient;
+use crate::error::{Result, WechatPayError};
+use crate::util::{datetime_fmt, option_datetime_fmt};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+/// 单个批次内最多可包含的转账明细数。
+pub const MAX_TRANSFER_DETAILS: usize = 1000;
+
+/// 明细转账金额达到此值(单位: 分，即 2000 元)时，收款用户姓名必填。
+pub const USER_NAME_REQUIRED_AMOUNT: i32 = 200000;
+
+/// 明细转账金额低于此值(单位: 分，即 0.3 元)时，不允许填写收款用户姓名。
+pub const USER_NAME_FORBIDDEN_AMOUNT: i32 = 30;
+
+impl WechatPayClient {
+    /// 发起商家转账(批次)。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/apiv3/apis/chapter4_3_1.shtml>
+    pub async fn create_transfer_batch(
+        &self,
+        params: &TransferBatchParams,
+    ) -> Result<TransferBatchResponse> {
+        let url = format!("{}/transfer/batches", self.base_url);
+        let mut req = self.client.post(url).json(params).build()?;
+        if params
+            .transfer_detail_list
+            .iter()
+            .any(|d| d.user_name.is_some())
+        {
+            self.sensitive_encryptor()?.apply(&mut req)?;
+        }
+        let res = self.execute_idempotent(req).await?;
+        let res: TransferBatchResponse = res.json().await?;
+        Ok(res)
+    }
+
+    /// 发起转账(新版商家转账，单笔)。
+    /// 若返回的状态为 `WAIT_USER_CONFIRM`，需将 package_info 交给前端拉起用户确认收款页面。
+    /// 参见 <https://pay.weixin.qq.com/doc/v3/merchant/4012716434>
+    pub async fn create_transfer_bill(
+        &self,
+        params: &TransferBillParams,
+    ) -> Result<TransferBillResponse> {
+        let url = format!("{}/fund-app/mch-transfer/transfer-bills", self.base_url);
+        let mut req = self.client.post(url).json(params).build()?;
+        if params.user_name.is_some() {
+            self.sensitive_encryptor()?.apply(&mut req)?;
+        }
+        let res = self.execute_idempotent(req).await?;
+        let res: TransferBillResponse = res.json().await?;
+        Ok(res)
+    }
+
+    /// 撤销转账。
+    /// 仅在用户确认收款前(状态为 `WAIT_USER_CONFIRM`)可撤销。
+    /// 参见 <https://pay.weixin.qq.com/doc/v3/merchant/4012716458>
+    pub async fn cancel_transfer_bill(
+        &self,
+        out_bill_no: &str,
+    ) -> Result<CancelTransferBillResponse> {
+        let url = format!(
+            "{}/fund-app/mch-transfer/transfer-bills/out-bill-no/{}/cancel",
+            self.base_url, out_bill_no
+        );
+        let req = self.client.post(url).build()?;
+        let res = self.execute(req).await?;
+        let res: CancelTransferBillResponse = res.json().await?;
+        Ok(res)
+    }
+
+    /// 通过商户单号(out_bill_no)查询转账单。
+    /// 参见 <https://pay.weixin.qq.com/doc/v3/merchant/4012716437>
+    pub async fn query_transfer_bill_by_out_bill_no(
+        &self,
+        out_bill_no: &str,
+    ) -> Result<TransferBillQueryResponse> {
+        let url = format!(
+            "{}/fund-app/mch-transfer/transfer-bills/out-bill-no/{}",
+            self.base_url, out_bill_no
+        );
+        let req = self.client.get(url).build()?;
+        let res = self.execute(req).await?;
+        let res: TransferBillQueryResponse = res.json().await?;
+        Ok(res)
+    }
+
+    /// 通过微信转账单号(transfer_bill_no)查询转账单。
+    /// 参见 <https://pay.weixin.qq.com/doc/v3/merchant/4012716457>
+    pub async fn query_transfer_bill_by_transfer_bill_no(
+        &self,
+        transfer_bill_no: &str,
+    ) -> Result<TransferBillQueryResponse> {
+        let url = format!(
+            "{}/fund-app/mch-transfer/transfer-bills/transfer-bill-no/{}",
+            self.base_url, transfer_bill_no
+        );
+        let req = self.client.get(url).build()?;
+        let res = self.execute(req).await?;
+        let res: TransferBillQueryResponse = res.json().await?;
+        Ok(res)
+    }
+}
+
+/// 发起商家转账的参数。
+/// 建议通过 `TransferBatchParams::builder()` 构造，以便在本地完成金额汇总与校验。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferBatchParams {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 商家批次单号。商户系统内部的商家批次单号，要求此参数只能由数字、大小写字母组成，在商户系统内部唯一。
+    /// 长度应在 [5, 32] 字符之间。
+    pub out_batch_no: String,
+    /// 批次名称。不超过 32 字符。
+    pub batch_name: String,
+    /// 批次备注。不超过 32 字符。
+    pub batch_remark: String,
+    /// 转账总金额，单位为分。须与批次内所有明细转账金额之和保持一致。
+    pub total_amount: i64,
+    /// 转账总笔数。须与批次内所有明细之和保持一致。
+    pub total_num: i32,
+    /// 转账明细列表。
+    pub transfer_detail_list: Vec<TransferDetailInput>,
+    /// 转账场景 ID。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub transfer_scene_id: Option<String>,
+    /// 异步接收微信支付结果通知的回调地址，通知url必须为公网可访问的url，必须为https，不能携带参数。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub notify_url: Option<String>,
+}
+
+impl TransferBatchParams {
+    pub fn builder() -> TransferBatchBuilder {
+        TransferBatchBuilder::new()
+    }
+}
+
+/// 转账明细
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferDetailInput {
+    /// 商家明细单号。商户系统内部区分转账批次单下不同转账明细单的唯一标识，
+    /// 只能由数字、大小写字母组成，长度应在 [5, 32] 字符之间。
+    pub out_detail_no: String,
+    /// 转账金额，单位为分。
+    pub transfer_amount: i32,
+    /// 转账备注。不超过 32 字符。
+    pub transfer_remark: String,
+    /// 收款用户在商户 app_id 下的唯一标识。
+    pub openid: String,
+    /// 收款用户姓名。需使用微信支付平台公钥加密，可通过 `WechatPayClient::encrypt_sensitive` 加密。
+    /// 明细转账金额 >= 2000 元时必填；< 0.3 元时不允许填写。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub user_name: Option<String>,
+}
+
+/// 发起商家转账的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TransferBatchResponse {
+    /// 商家批次单号
+    pub out_batch_no: String,
+    /// 微信批次单号
+    pub batch_id: String,
+    /// 批次创建时间
+    #[serde(with = "datetime_fmt")]
+    pub create_time: DateTime<Local>,
+    /// 批次状态。
+    /// * ACCEPTED：已受理
+    /// * PROCESSING：转账中
+    /// * FINISHED：已完成
+    /// * CLOSED：已关闭
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub batch_status: Option<String>,
+}
+
+/// 发起转账(新版商家转账)的参数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferBillParams {
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 商户单号。商户系统内部的单号，只能由数字、大小写字母组成，在商户系统内部唯一。
+    /// 长度应在 [5, 32] 字符之间。
+    pub out_bill_no: String,
+    /// 转账场景 ID。需在商户平台开通对应场景后使用。
+    pub transfer_scene_id: TransferScene,
+    /// 收款用户在商户 app_id 下的唯一标识。
+    pub openid: String,
+    /// 收款用户姓名。需使用微信支付平台公钥加密，可通过 `WechatPayClient::encrypt_sensitive` 加密。
+    /// 转账金额 >= 2000 元时必填。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub user_name: Option<String>,
+    /// 转账金额，单位为分。
+    pub transfer_amount: i32,
+    /// 转账备注，用户收款时可见。不超过 32 字符。
+    pub transfer_remark: String,
+    /// 异步接收微信支付结果通知的回调地址，通知url必须为公网可访问的url，必须为https，不能携带参数。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub notify_url: Option<String>,
+    /// 用户收款感知。用户收款时展示的收款原因，如"现金奖励"。不传则使用场景的默认内容。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub user_recv_perception: Option<String>,
+    /// 转账场景报备信息。各场景需报备的信息类型不同，如佣金报酬需报备"岗位类型"与"报酬说明"。
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub transfer_scene_report_infos: Vec<TransferSceneReportInfo>,
+}
+
+/// 转账场景报备信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferSceneReportInfo {
+    /// 信息类型，如"活动名称"、"岗位类型"，须与商户平台上场景要求的类型一致。
+    pub info_type: String,
+    /// 信息内容
+    pub info_content: String,
+}
+
+impl TransferSceneReportInfo {
+    pub fn new(info_type: impl Into<String>, info_content: impl Into<String>) -> Self {
+        TransferSceneReportInfo {
+            info_type: info_type.into(),
+            info_content: info_content.into(),
+        }
+    }
+}
+
+/// 发起转账(新版商家转账)的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TransferBillResponse {
+    /// 商户单号
+    pub out_bill_no: String,
+    /// 微信转账单号
+    pub transfer_bill_no: String,
+    /// 单据创建时间
+    #[serde(with = "datetime_fmt")]
+    pub create_time: DateTime<Local>,
+    /// 单据状态
+    pub state: TransferBillState,
+    /// 失败原因。单据状态为 FAIL 时返回。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fail_reason: Option<String>,
+    /// 跳转领取页面的 package 信息。
+    /// 单据状态为 WAIT_USER_CONFIRM 时返回，前端调用 `wx.requestMerchantTransfer` 时需要。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub package_info: Option<String>,
+}
+
+/// 撤销转账的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CancelTransferBillResponse {
+    /// 商户单号
+    pub out_bill_no: String,
+    /// 微信转账单号
+    pub transfer_bill_no: String,
+    /// 单据状态。撤销受理后为 CANCELING，撤销完成后为 CANCELLED。
+    pub state: TransferBillState,
+    /// 最后一次状态变更时间
+    #[serde(with = "datetime_fmt")]
+    pub update_time: DateTime<Local>,
+}
+
+/// 转账单查询响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TransferBillQueryResponse {
+    /// 商户号
+    pub mch_id: String,
+    /// 商户单号
+    pub out_bill_no: String,
+    /// 微信转账单号
+    pub transfer_bill_no: String,
+    /// 应用 ID
+    #[serde(rename = "appid")]
+    pub app_id: String,
+    /// 单据状态
+    pub state: TransferBillState,
+    /// 转账金额，单位为分。
+    pub transfer_amount: i32,
+    /// 转账备注
+    pub transfer_remark: String,
+    /// 失败原因。单据状态为 FAIL 时返回。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fail_reason: Option<String>,
+    /// 收款用户在商户 app_id 下的唯一标识。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub openid: Option<String>,
+    /// 收款用户姓名。已使用商户公钥加密。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub user_name: Option<String>,
+    /// 单据创建时间
+    #[serde(with = "datetime_fmt")]
+    pub create_time: DateTime<Local>,
+    /// 最后一次状态变更时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub update_time: Option<DateTime<Local>>,
+
+    /// 未在本结构中定义的字段。微信支付新增字段时，可从中取得原始数据。
+    /// 启用 `extra-fields` feature 时可用。
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// 商家转账结果通知解密后的资源数据。
+/// 对应 event_type: MCHTRANSFER.BILL.FINISHED
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TransferBillNotification {
+    /// 商户号
+    pub mch_id: String,
+    /// 商户单号
+    pub out_bill_no: String,
+    /// 微信转账单号
+    pub transfer_bill_no: String,
+    /// 单据状态
+    pub state: TransferBillState,
+    /// 转账金额，单位为分。
+    pub transfer_amount: i32,
+    /// 收款用户在商户 app_id 下的唯一标识。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub openid: Option<String>,
+    /// 失败原因。单据状态为 FAIL 时返回。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fail_reason: Option<String>,
+    /// 单据创建时间
+    #[serde(with = "datetime_fmt")]
+    pub create_time: DateTime<Local>,
+    /// 最后一次状态变更时间
+    #[serde(
+        with = "option_datetime_fmt",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub update_time: Option<DateTime<Local>>,
+
+    /// 未在本结构中定义的字段。微信支付新增字段时，可从中取得原始数据。
+    /// 启用 `extra-fields` feature 时可用。
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// 转账单状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferBillState {
+    /// 转账已受理
+    Accepted,
+    /// 转账锁定资金中。如果一直停留在该状态，建议检查账户余额是否足够。
+    Processing,
+    /// 待收款用户确认，可拉起微信收款确认页面进行收款确认
+    WaitUserConfirm,
+    /// 转账中，可拉起微信收款确认页面再次重试确认收款
+    Transfering,
+    /// 转账成功
+    Success,
+    /// 转账失败
+    Fail,
+    /// 商户撤销请求受理成功，该笔转账正在撤销中
+    Canceling,
+    /// 转账撤销完成
+    Cancelled,
+    /// 其他状态。微信支付新增的枚举值，保留原文。
+    Other(String),
+}
+
+impl TransferBillState {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransferBillState::Accepted => "ACCEPTED",
+            TransferBillState::Processing => "PROCESSING",
+            TransferBillState::WaitUserConfirm => "WAIT_USER_CONFIRM",
+            TransferBillState::Transfering => "TRANSFERING",
+            TransferBillState::Success => "SUCCESS",
+            TransferBillState::Fail => "FAIL",
+            TransferBillState::Canceling => "CANCELING",
+            TransferBillState::Cancelled => "CANCELLED",
+            TransferBillState::Other(s) => s,
+        }
+    }
+
+    /// 是否为终态(SUCCESS/FAIL/CANCELLED)。
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            TransferBillState::Success | TransferBillState::Fail | TransferBillState::Cancelled
+        )
+    }
+
+    /// 是否需要用户确认收款。
+    /// 此时需将 package_info 交给前端，拉起微信收款确认页面。
+    pub fn needs_user_confirm(&self) -> bool {
+        matches!(
+            self,
+            TransferBillState::WaitUserConfirm | TransferBillState::Transfering
+        )
+    }
+}
+
+impl FromStr for TransferBillState {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ACCEPTED" => Ok(TransferBillState::Accepted),
+            "PROCESSING" => Ok(TransferBillState::Processing),
+            "WAIT_USER_CONFIRM" => Ok(TransferBillState::WaitUserConfirm),
+            "TRANSFERING" => Ok(TransferBillState::Transfering),
+            "SUCCESS" => Ok(TransferBillState::Success),
+            "FAIL" => Ok(TransferBillState::Fail),
+            "CANCELING" => Ok(TransferBillState::Canceling),
+            "CANCELLED" => Ok(TransferBillState::Cancelled),
+            _ => Ok(TransferBillState::Other(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TransferBillState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TransferBillState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+}
+
+impl Serialize for TransferBillState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// 转账场景
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferScene {
+    /// 1000：现金营销
+    CashMarketing,
+    /// 1005：佣金报酬
+    Commission,
+    /// 1006：企业报销
+    Reimbursement,
+    /// 其他场景，保留场景 ID 原文。
+    Other(String),
+}
+
+impl TransferScene {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransferScene::CashMarketing => "1000",
+            TransferScene::Commission => "1005",
+            TransferScene::Reimbursement => "1006",
+            TransferScene::Other(s) => s,
+        }
+    }
+}
+
+impl FromStr for TransferScene {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1000" => Ok(TransferScene::CashMarketing),
+            "1005" => Ok(TransferScene::Commission),
+            "1006" => Ok(TransferScene::Reimbursement),
+            _ => Ok(TransferScene::Other(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TransferScene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for TransferScene {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or_else(|e| match e {}))
+    }
+}
+
+impl Serialize for TransferScene {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// builder for `TransferBatchParams`.
+/// build 时自动汇总 total_amount 与 total_num，并校验批次与明细是否满足微信支付的限制。
+#[derive(Debug, Default)]
+pub struct TransferBatchBuilder {
+    app_id: Option<String>,
+    out_batch_no: Option<String>,
+    batch_name: Option<String>,
+    batch_remark: Option<String>,
+    transfer_scene_id: Option<String>,
+    notify_url: Option<String>,
+    details: Vec<TransferDetailInput>,
+
+    max_detail_amount: Option<i32>,
+    max_batch_amount: Option<i64>,
+}
+
+impl TransferBatchBuilder {
+    fn new() -> TransferBatchBuilder {
+        TransferBatchBuilder {
+            ..Default::default()
+        }
+    }
+
+    pub fn app_id(&mut self, app_id: String) -> &mut Self {
+        self.app_id = Some(app_id);
+        self
+    }
+
+    pub fn out_batch_no(&mut self, out_batch_no: String) -> &mut Self {
+        self.out_batch_no = Some(out_batch_no);
+        self
+    }
+
+    pub fn batch_name(&mut self, batch_name: String) -> &mut Self {
+        self.batch_name = Some(batch_name);
+        self
+    }
+
+    pub fn batch_remark(&mut self, batch_remark: String) -> &mut Self {
+        self.batch_remark = Some(batch_remark);
+        self
+    }
+
+    pub fn transfer_scene_id(&mut self, transfer_scene_id: String) -> &mut Self {
+        self.transfer_scene_id = Some(transfer_scene_id);
+        self
+    }
+
+    pub fn notify_url(&mut self, notify_url: String) -> &mut Self {
+        self.notify_url = Some(notify_url);
+        self
+    }
+
+    /// 添加一条转账明细。
+    pub fn detail(&mut self, detail: TransferDetailInput) -> &mut Self {
+        self.details.push(detail);
+        self
+    }
+
+    /// 单笔转账限额(单位: 分)。
+    /// 商户可在商户平台设置单笔转账额度，超出额度的明细会被微信支付拒绝。
+    pub fn max_detail_amount(&mut self, amount: i32) -> &mut Self {
+        self.max_detail_amount = Some(amount);
+        self
+    }
+
+    /// 单批次转账限额(单位: 分)。
+    pub fn max_batch_amount(&mut self, amount: i64) -> &mut Self {
+        self.max_batch_amount = Some(amount);
+        self
+    }
+
+    pub fn build(&mut self) -> Result<TransferBatchParams> {
+        let app_id = self
+            .app_id
+            .take()
+            .ok_or_else(|| WechatPayError::InvalidParams("missing `app_id`".to_string()))?;
+        let out_batch_no = self
+            .out_batch_no
+            .take()
+            .ok_or_else(|| WechatPayError::InvalidParams("missing `out_batch_no`".to_string()))?;
+        let batch_name = self
+            .batch_name
+            .take()
+            .ok_or_else(|| WechatPayError::InvalidParams("missing `batch_name`".to_string()))?;
+        let batch_remark = self
+            .batch_remark
+            .take()
+            .ok_or_else(|| WechatPayError::InvalidParams("missing `batch_remark`".to_string()))?;
+
+        check_transfer_no("out_batch_no", &out_batch_no)?;
+        check_max_chars("batch_name", &batch_name, 32)?;
+        check_max_chars("batch_remark", &batch_remark, 32)?;
+
+        let details = std::mem::take(&mut self.details);
+        if details.is_empty() {
+            return Err(WechatPayError::InvalidParams(
+                "empty `transfer_detail_list`".to_string(),
+            ));
+        }
+        if details.len() > MAX_TRANSFER_DETAILS {
+            return Err(WechatPayError::InvalidParams(format!(
+                "too many transfer details: {}, at most {}",
+                details.len(),
+                MAX_TRANSFER_DETAILS
+            )));
+        }
+
+        let mut out_detail_nos = HashSet::new();
+        let mut total_amount: i64 = 0;
+        for detail in &details {
+            check_transfer_no("out_detail_no", &detail.out_detail_no)?;
+            check_max_chars("transfer_remark", &detail.transfer_remark, 32)?;
+            if !out_detail_nos.insert(detail.out_detail_no.as_str()) {
+                return Err(WechatPayError::InvalidParams(format!(
+                    "duplicated out_detail_no: {}",
+                    detail.out_detail_no
+                )));
+            }
+
+            if detail.transfer_amount <= 0 {
+                return Err(WechatPayError::InvalidParams(format!(
+                    "invalid transfer_amount {} for out_detail_no: {}",
+                    detail.transfer_amount, detail.out_detail_no
+                )));
+            }
+            if let Some(max) = self.max_detail_amount {
+                if detail.transfer_amount > max {
+                    return Err(WechatPayError::InvalidParams(format!(
+                        "transfer_amount {} exceeds limit {} for out_detail_no: {}",
+                        detail.transfer_amount, max, detail.out_detail_no
+                    )));
+                }
+            }
+            if detail.transfer_amount >= USER_NAME_REQUIRED_AMOUNT && detail.user_name.is_none() {
+                return Err(WechatPayError::InvalidParams(format!(
+                    "`user_name` is required for out_detail_no: {}",
+                    detail.out_detail_no
+                )));
+            }
+            if detail.transfer_amount < USER_NAME_FORBIDDEN_AMOUNT && detail.user_name.is_some() {
+                return Err(WechatPayError::InvalidParams(format!(
+                    "`user_name` is not allowed for out_detail_no: {}",
+                    detail.out_detail_no
+                )));
+            }
+
+            total_amount += detail.transfer_amount as i64;
+        }
+
+        if let Some(max) = self.max_batch_amount {
+            if total_amount > max {
+                return Err(WechatPayError::InvalidParams(format!(
+                    "total_amount {} exceeds limit {}",
+                    total_amount, max
+                )));
+            }
+        }
+
+        Ok(TransferBatchParams {
+            app_id,
+            out_batch_no,
+            batch_name,
+            batch_remark,
+            total_amount,
+            total_num: details.len() as i32,
+            transfer_detail_list: details,
+            transfer_scene_id: self.transfer_scene_id.take(),
+            notify_url: self.notify_url.take(),
+        })
+    }
+}
+
+/// 校验商家批次单号/明细单号：只能由数字、大小写字母组成，长度应在 [5, 32] 字符之间。
+fn check_transfer_no(name: &str, no: &str) -> Result<()> {
+    if no.len() < 5 || no.len() > 32 || !no.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(WechatPayError::InvalidParams(format!(
+            "invalid {}: {}",
+            name, no
+        )));
+    }
+    Ok(())
+}
+
+fn check_max_chars(name: &str, s: &str, max: usize) -> Result<()> {
+    if s.chars().count() > max {
+        return Err(WechatPayError::InvalidParams(format!(
+            "`{}` is too long, at most {} characters",
+            name, max
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detail(out_detail_no: &str, transfer_amount: i32) -> TransferDetailInput {
+        TransferDetailInput {
+            out_detail_no: out_detail_no.to_string(),
+            transfer_amount,
+            transfer_remark: "佣金".to_string(),
+            openid: "o-MYE42l80oelYMDE34nYD456Xoy".to_string(),
+            user_name: None,
+        }
+    }
+
+    fn builder() -> TransferBatchBuilder {
+        let mut builder = TransferBatchParams::builder();
+        builder
+            .app_id("wxf636efh567hg4356".to_string())
+            .out_batch_no("plfk2020042013".to_string())
+            .batch_name("2019年1月深圳分部报销单".to_string())
+            .batch_remark("2019年1月深圳分部报销单".to_string());
+        builder
+    }
+
+    #[test]
+    fn test_transfer_batch_builder_sums() -> anyhow::Result<()> {
+        let params = builder()
+            .detail(detail("x23zy545Bd5436", 100))
+            .detail(detail("x23zy545Bd5437", 250))
+            .build()?;
+        assert_eq!(params.total_amount, 350);
+        assert_eq!(params.total_num, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transfer_batch_builder_rejects() {
+        fn assert_rejected(res: Result<TransferBatchParams>, reason: &str) {
+            assert!(
+                matches!(&res, Err(WechatPayError::InvalidParams(msg)) if msg.contains(reason)),
+                "expected `{}`, got {:?}",
+                reason,
+                res
+            );
+        }
+
+        assert_rejected(
+            builder()
+                .detail(detail("x23zy545Bd5436", 100))
+                .detail(detail("x23zy545Bd5436", 100))
+                .build(),
+            "duplicated out_detail_no",
+        );
+        assert_rejected(builder().build(), "empty `transfer_detail_list`");
+        assert_rejected(
+            builder()
+                .detail(detail("x23zy545Bd5436", USER_NAME_REQUIRED_AMOUNT))
+                .build(),
+            "`user_name` is required",
+        );
+        assert_rejected(
+            builder()
+                .max_detail_amount(100)
+                .detail(detail("x23zy545Bd5436", 101))
+                .build(),
+            "transfer_amount 101 exceeds limit 100",
+        );
+        assert_rejected(
+            builder()
+                .max_batch_amount(150)
+                .detail(detail("x23zy545Bd5436", 100))
+                .detail(detail("x23zy545Bd5437", 100))
+                .build(),
+            "total_amount 200 exceeds limit 150",
+        );
+    }
+
+    #[test]
+    fn test_transfer_bill_params_scene() -> anyhow::Result<()> {
+        let params = TransferBillParams {
+            app_id: "wxf636efh567hg4356".to_string(),
+            out_bill_no: "plfk2020042013".to_string(),
+            transfer_scene_id: TransferScene::Commission,
+            openid: "o-MYE42l80oelYMDE34nYD456Xoy".to_string(),
+            user_name: None,
+            transfer_amount: 400000,
+            transfer_remark: "2020年4月佣金".to_string(),
+            notify_url: None,
+            user_recv_perception: None,
+            transfer_scene_report_infos: vec![
+                TransferSceneReportInfo::new("岗位类型", "外卖员"),
+                TransferSceneReportInfo::new("报酬说明", "7月份配送费"),
+            ],
+        };
+        let json = serde_json::to_value(&params)?;
+        assert_eq!(json["transfer_scene_id"], "1005");
+        assert_eq!(
+            json["transfer_scene_report_infos"][1]["info_type"],
+            "报酬说明"
+        );
+
+        let scene: TransferScene = serde_json::from_str(r#""1009""#)?;
+        assert_eq!(scene, TransferScene::Other("1009".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_transfer_bill_state_other() -> anyhow::Result<()> {
+        let state: TransferBillState = serde_json::from_str(r#""WAIT_PAY""#)?;
+        assert_eq!(state, TransferBillState::Other("WAIT_PAY".to_string()));
+        assert!(!state.is_final());
+        assert_eq!(state.to_string(), "WAIT_PAY");
+        assert_eq!(serde_json::to_string(&state)?, r#""WAIT_PAY""#);
+
+        let state: TransferBillState = "CANCELLED".parse().unwrap_or_else(|e| match e {});
+        assert!(state.is_final());
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+//! HTTP 传输层。
+//! 默认使用 `reqwest::Client` 发送请求。测试时可通过 `WechatPayClientBuilder::transport` 注入
+//! `MockTransport`，返回固定的响应，从而在不联网的情况下对业务逻辑做单元测试。
+
+use crate::credential::generate_none_str;
+use crate::error::{Result, WechatPayError};
+use crate::platform_certificate::WechatPayPublicKey;
+use async_trait::async_trait;
+use base64::prelude::*;
+use bytes::{BufMut, Bytes, BytesMut};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Client, Method, Request, Response, StatusCode, Version};
+use rsa::pkcs1v15::SigningKey;
+use rsa::sha2::Sha256;
+use rsa::signature::{RandomizedSigner, SignatureEncoding};
+use rsa::RsaPrivateKey;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// HTTP 传输层，负责发送已签名的请求并返回响应。
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, req: Request) -> Result<Response>;
+}
+
+#[async_trait]
+impl Transport for Client {
+    async fn send(&self, req: Request) -> Result<Response> {
+        Ok(self.execute(req).await?)
+    }
+}
+
+impl fmt::Debug for dyn Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Transport")
+    }
+}
+
+/// 返回固定响应的传输层，用于测试。
+/// 响应使用指定的私钥签名，Wechatpay-Serial 为指定的公钥 ID。构建 client 时，
+/// 须通过 `WechatPayClientBuilder::wechatpay_public_key` 指定 `wechatpay_public_key()` 的返回值，才能通过验签。
+/// 未匹配到任何响应的请求，返回 404。
+#[derive(Clone)]
+pub struct MockTransport {
+    signing_key: SigningKey<Sha256>,
+    private_key: RsaPrivateKey,
+    public_key_id: String,
+    responses: Arc<Mutex<Vec<MockResponse>>>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+#[derive(Debug, Clone)]
+struct MockResponse {
+    method: Method,
+    path: String,
+    status: StatusCode,
+    body: String,
+}
+
+/// MockTransport 收到的请求
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: Method,
+    /// 请求路径及查询参数
+    pub path: String,
+    pub headers: HeaderMap,
+    /// 请求 body。multipart/form-data 等流式 body 为空。
+    pub body: Vec<u8>,
+}
+
+impl MockTransport {
+    /// public_key_id 须以 `PUB_KEY_ID_` 开头。
+    pub fn new(private_key: RsaPrivateKey, public_key_id: &str) -> MockTransport {
+        MockTransport {
+            signing_key: SigningKey::<Sha256>::new(private_key.clone()),
+            private_key,
+            public_key_id: public_key_id.to_string(),
+            responses: Arc::new(Mutex::new(vec![])),
+            requests: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// 与签名私钥对应的微信支付公钥，用于构建 client。
+    pub fn wechatpay_public_key(&self) -> WechatPayPublicKey {
+        WechatPayPublicKey::new(self.public_key_id.clone(), self.private_key.to_public_key())
+    }
+
+    /// 对 method 与 path(不含查询参数)匹配的请求，返回指定的状态码与 body。
+    /// path 中带有查询参数时，须与请求的路径及查询参数完全一致。
+    /// 多个响应匹配时，使用最后添加的。
+    pub fn on(&self, method: Method, path: &str, status: StatusCode, body: &str) -> &Self {
+        self.responses.lock().unwrap().push(MockResponse {
+            method,
+            path: path.to_string(),
+            status,
+            body: body.to_string(),
+        });
+        self
+    }
+
+    /// 已收到的请求
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// 对 body 签名，返回 Wechatpay-Serial、Wechatpay-Signature 等验签所需的 header。
+    /// 可用于模拟微信支付发送的通知。
+    pub fn signature_headers(&self, body: &str) -> Result<HeaderMap> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| WechatPayError::Other(e.to_string()))?
+            .as_secs();
+        let nonce_str = generate_none_str(32);
+        let signature = self.sign(timestamp, &nonce_str, body);
+
+        let mut headers = HeaderMap::new();
+        let mut insert = |key: &'static str, value: &str| -> Result<()> {
+            let value =
+                HeaderValue::from_str(value).map_err(|e| WechatPayError::Other(e.to_string()))?;
+            headers.insert(key, value);
+            Ok(())
+        };
+        insert("Wechatpay-Serial", &self.public_key_id)?;
+        insert("Wechatpay-Timestamp", &timestamp.to_string())?;
+        insert("Wechatpay-Nonce", &nonce_str)?;
+        insert("Wechatpay-Signature", &signature)?;
+        Ok(headers)
+    }
+
+    fn sign(&self, timestamp: u64, nonce_str: &str, body: &str) -> String {
+        let mut msg = BytesMut::new();
+        msg.put_slice(timestamp.to_string().as_bytes());
+        msg.put_u8(b'\n');
+        msg.put_slice(nonce_str.as_bytes());
+        msg.put_u8(b'\n');
+        msg.put_slice(body.as_bytes());
+        msg.put_u8(b'\n');
+
+        let mut rng = rand::thread_rng();
+        let signature = self.signing_key.sign_with_rng(&mut rng, &msg).to_bytes();
+        BASE64_STANDARD.encode(signature)
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn send(&self, req: Request) -> Result<Response> {
+        let path = match req.url().query() {
+            Some(query) => format!("{}?{}", req.url().path(), query),
+            None => req.url().path().to_string(),
+        };
+        let body = req
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|b| b.to_vec())
+            .unwrap_or_default();
+        self.requests.lock().unwrap().push(MockRequest {
+            method: req.method().clone(),
+            path: path.clone(),
+            headers: req.headers().clone(),
+            body,
+        });
+
+        let (status, body) = self
+            .responses
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|r| r.method == *req.method() && (r.path == req.url().path() || r.path == path))
+            .map(|r| (r.status, r.body.clone()))
+            .unwrap_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    r#"{"code":"NOT_FOUND","message":"no mock response"}"#.to_string(),
+                )
+            });
+
+        let mut builder = http::Response::builder()
+            .status(status)
+            .header("Content-Type", "application/json")
+            .header("Request-ID", generate_none_str(16));
+        for (key, value) in &self.signature_headers(&body)? {
+            builder = builder.header(key, value);
+        }
+        let res = builder
+            .body(body)
+            .map_err(|e| WechatPayError::Other(e.to_string()))?;
+        Ok(res.into())
+    }
+}
+
+/// 已读入内存的响应。header 从原响应中移出，body 为单个 chunk 时直接复用，均不做拷贝。
+/// 验签、错误解析及 JSON 反序列化都直接在 body 上进行，转回 `Response` 时也无需拷贝。
+pub(crate) struct BufferedResponse {
+    pub(crate) status: StatusCode,
+    pub(crate) version: Version,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Bytes,
+}
+
+impl BufferedResponse {
+    /// 读取响应体。指定 max 时，超过 max 字节即中断读取并返回 `WechatPayError::ResponseTooLarge`。
+    pub(crate) async fn read(res: Response, max: Option<usize>) -> Result<BufferedResponse> {
+        let max = max.unwrap_or(usize::MAX);
+        let too_large = || WechatPayError::ResponseTooLarge(format!("more than {} bytes", max));
+        if res.content_length().is_some_and(|len| len > max as u64) {
+            return Err(too_large());
+        }
+
+        let mut res = res;
+        let status = res.status();
+        let version = res.version();
+        let headers = std::mem::take(res.headers_mut());
+        let mut len = 0;
+        let mut chunks = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            len += chunk.len();
+            if len > max {
+                return Err(too_large());
+            }
+            chunks.push(chunk);
+        }
+        let body = match chunks.len() {
+            0 => Bytes::new(),
+            1 => chunks.pop().unwrap(),
+            _ => chunks.concat().into(),
+        };
+        Ok(BufferedResponse {
+            status,
+            version,
+            headers,
+            body,
+        })
+    }
+}
+
+/// `mock_client` 的 API v3 密钥
+#[cfg(test)]
+const MOCK_API_V3_KEY: &str = "00000000000000000000000000000000";
+
+/// 测试用的 client，使用 `MockTransport` 作为传输层，返回 client 和 mock。
+#[cfg(test)]
+pub(crate) async fn mock_client() -> Result<(crate::WechatPayClient, MockTransport)> {
+    mock_client_with(|_| {}).await
+}
+
+/// 同 `mock_client`，构建前可通过 `configure` 追加 builder 设置。
+#[cfg(test)]
+pub(crate) async fn mock_client_with<F>(
+    configure: F,
+) -> Result<(crate::WechatPayClient, MockTransport)>
+where
+    F: FnOnce(&mut crate::client::WechatPayClientBuilder),
+{
+    let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)
+        .map_err(|e| WechatPayError::Other(e.to_string()))?;
+    let mock = MockTransport::new(private_key.clone(), "PUB_KEY_ID_0000000000000001");
+    let mut builder = crate::WechatPayClient::builder();
+    builder
+        .mch_credential(crate::MchCredential::new(
+            "1900000001".to_string(),
+            "serial".to_string(),
+            private_key,
+            MOCK_API_V3_KEY.to_string(),
+        ))
+        .wechatpay_public_key(mock.wechatpay_public_key())
+        .transport(mock.clone());
+    configure(&mut builder);
+    Ok((builder.build().await?, mock))
+}
+
+/// 测试用的通知 body，资源数据使用 `mock_client` 的 API v3 密钥加密。
+/// 通知的 header 可由 `MockTransport::signature_headers` 生成。
+#[cfg(test)]
+pub(crate) fn mock_notification(
+    id: &str,
+    event_type: &str,
+    original_type: &str,
+    plain: &str,
+) -> String {
+    use aes_gcm::aead::{Aead, Payload};
+    use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+
+    let cipher = Aes256Gcm::new_from_slice(MOCK_API_V3_KEY.as_bytes()).unwrap();
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(b"fdasflkja484"),
+            Payload {
+                msg: plain.as_bytes(),
+                aad: original_type.as_bytes(),
+            },
+        )
+        .unwrap();
+    serde_json::json!({
+        "id": id,
+        "create_time": "2015-05-20T13:29:35+08:00",
+        "event_type": event_type,
+        "resource_type": "encrypt-resource",
+        "resource": {
+            "algorithm": "AEAD_AES_256_GCM",
+            "ciphertext": BASE64_STANDARD.encode(ciphertext),
+            "associated_data": original_type,
+            "original_type": original_type,
+            "nonce": "fdasflkja484",
+        },
+        "summary": "",
+    })
+    .to_string()
+}
+
+impl From<BufferedResponse> for Response {
+    fn from(res: BufferedResponse) -> Response {
+        let mut new_res = http::Response::new(res.body);
+        *new_res.status_mut() = res.status;
+        *new_res.version_mut() = res.version;
+        *new_res.headers_mut() = res.headers;
+        new_res.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_transport() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        mock.on(
+            Method::POST,
+            "/v3/pay/transactions/out-trade-no/order-1/close",
+            StatusCode::NO_CONTENT,
+            "",
+        );
+
+        client.close_trade("order-1").await?;
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::POST);
+        assert!(String::from_utf8(requests[0].body.clone())?.contains("1900000001"));
+
+        let err = client
+            .query_trade_by_out_trade_no("order-2")
+            .await
+            .unwrap_err();
+        assert_eq!(err.api_error().map(|e| e.code()), Some("NOT_FOUND"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_buffered_response() -> anyhow::Result<()> {
+        let chunked = || {
+            let chunks: Vec<std::io::Result<&str>> =
+                vec![Ok(r#"{"code_url":"#), Ok(r#""weixin://"}"#)];
+            let body = reqwest::Body::wrap_stream(futures::stream::iter(chunks));
+            let res = http::Response::builder()
+                .header("Request-ID", "08F78BB5AF0610D302")
+                .body(body)
+                .unwrap();
+            Response::from(res)
+        };
+
+        let res = BufferedResponse::read(chunked(), None).await?;
+        assert_eq!(&res.body[..], br#"{"code_url":"weixin://"}"#);
+        let res = Response::from(res);
+        assert_eq!(res.headers()["Request-ID"], "08F78BB5AF0610D302");
+        assert_eq!(res.text().await?, r#"{"code_url":"weixin://"}"#);
+
+        let err = BufferedResponse::read(chunked(), Some(16)).await;
+        assert!(matches!(err, Err(WechatPayError::ResponseTooLarge(_))));
+        Ok(())
+    }
+}
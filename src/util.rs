@@ -59,3 +59,8 @@ pub mod option_datetime_fmt {
         }
     }
 }
+
+/// 十六进制编码(小写)。
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,565 @@
+//! APIv2 兼容层，启用 `v2` feature 时可用。
+//! 部分接口(如现金红包、企业付款到零钱)仍只提供 v2 版本。v2 接口使用 XML 格式的请求与响应，
+//! 以 API 密钥(APIv2 密钥，非 APIv3 密钥)进行 MD5 或 HMAC-SHA256 签名，涉及资金的接口还需要商户证书(mTLS)。
+//! 参见 <https://pay.weixin.qq.com/wiki/doc/api/tools/cash_coupon.php?chapter=4_3>
+
+use crate::client::USER_AGENT;
+use crate::credential::generate_none_str;
+use crate::error::{Result, WechatPayApiError, WechatPayError};
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::Client;
+use rsa::sha2::Sha256;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+const V2_BASE_URL: &str = "https://api.mch.weixin.qq.com";
+/// 仿真测试系统的路径前缀
+const SANDBOX_PATH: &str = "/sandboxnew";
+
+/// 签名类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignType {
+    Md5,
+    HmacSha256,
+}
+
+impl SignType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignType::Md5 => "MD5",
+            SignType::HmacSha256 => "HMAC-SHA256",
+        }
+    }
+}
+
+/// 计算签名。
+/// 参数按参数名 ASCII 码从小到大排序，跳过空值及 sign 参数，拼接为 `k1=v1&k2=v2&key=API密钥` 后计算摘要，结果为大写的十六进制。
+/// 参见 <https://pay.weixin.qq.com/wiki/doc/api/jsapi.php?chapter=4_3>
+pub fn sign(params: &BTreeMap<String, String>, api_key: &str, sign_type: SignType) -> String {
+    let mut s = String::new();
+    for (k, v) in params {
+        if v.is_empty() || k == "sign" {
+            continue;
+        }
+        let _ = write!(s, "{}={}&", k, v);
+    }
+    let _ = write!(s, "key={}", api_key);
+
+    let digest = match sign_type {
+        SignType::Md5 => Md5::digest(s.as_bytes()).to_vec(),
+        SignType::HmacSha256 => {
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(api_key.as_bytes())
+                .expect("HMAC can take key of any size");
+            mac.update(s.as_bytes());
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+    digest.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{:02X}", b);
+        hex
+    })
+}
+
+/// 验证参数中的 sign。签名类型取自 sign_type 参数，缺省为 default_sign_type。
+/// 响应一般不返回 sign_type，其签名类型与请求一致。
+pub fn verify(
+    params: &BTreeMap<String, String>,
+    api_key: &str,
+    default_sign_type: SignType,
+) -> Result<()> {
+    let sign_type = match params.get("sign_type").map(|s| s.as_str()) {
+        None => default_sign_type,
+        Some("MD5") => SignType::Md5,
+        Some("HMAC-SHA256") => SignType::HmacSha256,
+        Some(other) => {
+            return Err(WechatPayError::Verify(format!(
+                "unsupported sign_type: {}",
+                other
+            )))
+        }
+    };
+    let expected = params
+        .get("sign")
+        .ok_or_else(|| WechatPayError::Verify("missing `sign`".to_string()))?;
+    if !expected.eq_ignore_ascii_case(&sign(params, api_key, sign_type)) {
+        return Err(WechatPayError::Verify("sign mismatch".to_string()));
+    }
+    Ok(())
+}
+
+/// 将参数序列化为 `<xml>` 根节点下的 XML，值均以 CDATA 包裹。
+pub fn to_xml(params: &BTreeMap<String, String>) -> String {
+    let mut xml = String::from("<xml>");
+    for (k, v) in params {
+        // CDATA 中不能出现 `]]>`，需拆分为两段
+        let v = v.replace("]]>", "]]]]><![CDATA[>");
+        let _ = write!(xml, "<{}><![CDATA[{}]]></{}>", k, v, k);
+    }
+    xml.push_str("</xml>");
+    xml
+}
+
+/// 解析 `<xml>` 根节点下的一层 XML 节点。
+pub fn from_xml(xml: &str) -> Result<BTreeMap<String, String>> {
+    let invalid = |e: &dyn std::fmt::Display| WechatPayError::Other(format!("invalid xml: {}", e));
+
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut params = BTreeMap::new();
+    let mut depth = 0;
+    let mut key: Option<String> = None;
+    let mut value = String::new();
+    loop {
+        match reader.read_event().map_err(|e| invalid(&e))? {
+            Event::Start(e) => {
+                depth += 1;
+                if depth == 2 {
+                    key = Some(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+                    value.clear();
+                }
+            }
+            Event::Empty(e) if depth == 1 => {
+                let key = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                params.insert(key, String::new());
+            }
+            Event::Text(e) if depth == 2 => {
+                value.push_str(&e.unescape().map_err(|e| invalid(&e))?);
+            }
+            Event::CData(e) if depth == 2 => {
+                value.push_str(&String::from_utf8_lossy(&e.into_inner()));
+            }
+            Event::End(_) => {
+                if depth == 2 {
+                    if let Some(key) = key.take() {
+                        params.insert(key, std::mem::take(&mut value));
+                    }
+                }
+                depth -= 1;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(params)
+}
+
+/// 将可序列化为 JSON 对象的参数转换为 v2 接口的参数。数字、布尔值转换为字符串，null 被忽略。
+pub fn to_params<T: Serialize>(params: &T) -> Result<BTreeMap<String, String>> {
+    let value = serde_json::to_value(params)?;
+    let object = match value {
+        serde_json::Value::Object(object) => object,
+        _ => {
+            return Err(WechatPayError::InvalidParams(
+                "params must be serialized as an object".to_string(),
+            ))
+        }
+    };
+
+    let mut params = BTreeMap::new();
+    for (k, v) in object {
+        let v = match v {
+            serde_json::Value::Null => continue,
+            serde_json::Value::String(s) => s,
+            v => v.to_string(),
+        };
+        params.insert(k, v);
+    }
+    Ok(params)
+}
+
+/// 小程序发券插件中待发放的批次
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SendCouponStock {
+    /// 批次号
+    pub stock_id: String,
+    /// 商户单据号，用于发券幂等
+    pub out_request_no: String,
+}
+
+/// 小程序发券插件的参数，原样传给 `<send-coupon>` 组件的同名属性。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SendCouponSign {
+    /// 发券参数
+    pub send_coupon_params: Vec<SendCouponStock>,
+    /// 发券商户号
+    pub send_coupon_merchant: String,
+    /// 签名
+    pub sign: String,
+}
+
+/// 计算小程序发券插件的签名。
+/// 第 i 个批次的参数名为 `stock_id{i}`、`out_request_no{i}`，与 send_coupon_merchant 一起以 APIv2 密钥进行 HMAC-SHA256 签名。
+pub fn sign_send_coupon(
+    send_coupon_merchant: &str,
+    stocks: Vec<SendCouponStock>,
+    api_key: &str,
+) -> SendCouponSign {
+    let mut params = BTreeMap::new();
+    params.insert(
+        "send_coupon_merchant".to_string(),
+        send_coupon_merchant.to_string(),
+    );
+    for (i, stock) in stocks.iter().enumerate() {
+        params.insert(format!("stock_id{}", i), stock.stock_id.clone());
+        params.insert(format!("out_request_no{}", i), stock.out_request_no.clone());
+    }
+    SendCouponSign {
+        sign: sign(&params, api_key, SignType::HmacSha256),
+        send_coupon_params: stocks,
+        send_coupon_merchant: send_coupon_merchant.to_string(),
+    }
+}
+
+/// APIv2 客户端。
+#[derive(Debug, Clone)]
+pub struct V2Client {
+    client: Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl V2Client {
+    pub fn builder() -> V2ClientBuilder {
+        V2ClientBuilder::new()
+    }
+
+    /// 发送 POST 请求。
+    /// 自动添加 nonce_str(若未设置)与 sign，签名类型为 HMAC-SHA256 时同时添加 sign_type 参数。
+    /// 响应的 return_code 或 result_code 不为 SUCCESS 时，返回 `WechatPayError::Api`；
+    /// 响应中带有 sign 时进行验签。
+    pub async fn post<T: Serialize>(
+        &self,
+        path: &str,
+        params: &T,
+        sign_type: SignType,
+    ) -> Result<BTreeMap<String, String>> {
+        let mut params = to_params(params)?;
+        params
+            .entry("nonce_str".to_string())
+            .or_insert_with(|| generate_none_str(32));
+        if sign_type == SignType::HmacSha256 {
+            params.insert("sign_type".to_string(), sign_type.as_str().to_string());
+        }
+        let signature = sign(&params, &self.api_key, sign_type);
+        params.insert("sign".to_string(), signature);
+
+        let body = self.send(path, &params).await?;
+        self.check_response(&body, sign_type)
+    }
+
+    /// 获取仿真测试系统的验签密钥(sandbox_signkey)。须在正式环境的 client 上调用。
+    /// 参见 <https://pay.weixin.qq.com/wiki/doc/api/tools/sp_coupon.php?chapter=23_1>
+    pub async fn get_sandbox_signkey(&self, mch_id: &str) -> Result<String> {
+        let mut params = BTreeMap::new();
+        params.insert("mch_id".to_string(), mch_id.to_string());
+        params.insert("nonce_str".to_string(), generate_none_str(32));
+        let signature = sign(&params, &self.api_key, SignType::Md5);
+        params.insert("sign".to_string(), signature);
+
+        let path = format!("{}/pay/getsignkey", SANDBOX_PATH);
+        let mut res = from_xml(&self.send(&path, &params).await?)?;
+        let get = |key: &str| res.get(key).map(|s| s.as_str()).unwrap_or_default();
+        if get("return_code") != "SUCCESS" {
+            return Err(WechatPayApiError::new(get("return_code"), get("return_msg")).into());
+        }
+        res.remove("sandbox_signkey")
+            .ok_or_else(|| WechatPayError::Other("missing `sandbox_signkey`".to_string()))
+    }
+
+    /// 返回使用仿真测试系统的 client，用于联调测试。须在正式环境的 client 上调用。
+    /// 会先获取 sandbox_signkey，之后的请求以其签名、验签，请求路径均加上 `/sandboxnew` 前缀。
+    pub async fn sandbox(&self, mch_id: &str) -> Result<V2Client> {
+        let api_key = self.get_sandbox_signkey(mch_id).await?;
+        Ok(V2Client {
+            client: self.client.clone(),
+            api_key,
+            base_url: format!("{}{}", self.base_url, SANDBOX_PATH),
+        })
+    }
+
+    async fn send(&self, path: &str, params: &BTreeMap<String, String>) -> Result<String> {
+        let url = format!("{}{}", self.base_url, path);
+        let res = self
+            .client
+            .post(url)
+            .header("Content-Type", "text/xml")
+            .body(to_xml(params))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(res.text().await?)
+    }
+
+    /// 验签时，响应未返回 sign_type 则使用请求的 sign_type。
+    fn check_response(&self, body: &str, sign_type: SignType) -> Result<BTreeMap<String, String>> {
+        let params = from_xml(body)?;
+        let get = |key: &str| params.get(key).map(|s| s.as_str()).unwrap_or_default();
+        if get("return_code") != "SUCCESS" {
+            return Err(WechatPayApiError::new(get("return_code"), get("return_msg")).into());
+        }
+        if params.contains_key("sign") {
+            verify(&params, &self.api_key, sign_type)?;
+        }
+        if params.contains_key("result_code") && get("result_code") != "SUCCESS" {
+            return Err(WechatPayApiError::new(get("err_code"), get("err_code_des")).into());
+        }
+        Ok(params)
+    }
+}
+
+/// builder for `V2Client`.
+#[derive(Debug, Default)]
+pub struct V2ClientBuilder {
+    api_key: Option<String>,
+    mch_certificate: Option<(Vec<u8>, Vec<u8>)>,
+    timeout: Option<Duration>,
+    base_url: Option<String>,
+}
+
+impl V2ClientBuilder {
+    fn new() -> V2ClientBuilder {
+        V2ClientBuilder {
+            ..Default::default()
+        }
+    }
+
+    /// APIv2 密钥
+    pub fn api_key(&mut self, api_key: String) -> &mut Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// 商户证书(apiclient_cert.pem)及私钥(apiclient_key.pem)，PEM 格式。
+    /// 红包、企业付款等涉及资金的接口需要以商户证书进行双向认证(mTLS)。
+    pub fn mch_certificate(&mut self, cert_pem: &[u8], key_pem: &[u8]) -> &mut Self {
+        self.mch_certificate = Some((cert_pem.to_vec(), key_pem.to_vec()));
+        self
+    }
+
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 默认为 `https://api.mch.weixin.qq.com`
+    pub fn base_url(&mut self, base_url: String) -> &mut Self {
+        self.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    pub fn build(&mut self) -> Result<V2Client> {
+        let api_key = self
+            .api_key
+            .take()
+            .ok_or_else(|| WechatPayError::InvalidParams("missing `api_key`".to_string()))?;
+
+        let mut client_builder = Client::builder().user_agent(USER_AGENT);
+        if let Some(timeout) = self.timeout {
+            client_builder = client_builder.timeout(timeout);
+        }
+        if let Some((cert_pem, key_pem)) = self.mch_certificate.take() {
+            client_builder = client_builder.identity(identity(&cert_pem, &key_pem)?);
+        }
+        Ok(V2Client {
+            client: client_builder.build()?,
+            api_key,
+            base_url: self
+                .base_url
+                .take()
+                .unwrap_or_else(|| V2_BASE_URL.to_string()),
+        })
+    }
+}
+
+/// 同时启用 `rustls` 与 `native-tls` 时，reqwest 默认使用 native-tls，证书须与之一致。
+#[cfg(feature = "native-tls")]
+fn identity(cert_pem: &[u8], key_pem: &[u8]) -> Result<reqwest::Identity> {
+    Ok(reqwest::Identity::from_pkcs8_pem(cert_pem, key_pem)?)
+}
+
+#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+fn identity(cert_pem: &[u8], key_pem: &[u8]) -> Result<reqwest::Identity> {
+    let pem = [cert_pem, b"\n", key_pem].concat();
+    Ok(reqwest::Identity::from_pem(&pem)?)
+}
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+fn identity(_cert_pem: &[u8], _key_pem: &[u8]) -> Result<reqwest::Identity> {
+    Err(WechatPayError::InvalidParams(
+        "mch certificate requires feature `rustls` or `native-tls`".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc_params() -> BTreeMap<String, String> {
+        // 参见签名算法文档中的示例
+        [
+            ("appid", "wxd930ea5d5a258f4f"),
+            ("mch_id", "10000100"),
+            ("device_info", "1000"),
+            ("body", "test"),
+            ("nonce_str", "ibuaiVcKdpRxkhJA"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_sign() {
+        let key = "192006250b4c09247ec02edce69f6a2d";
+        let mut params = doc_params();
+        assert_eq!(
+            sign(&params, key, SignType::Md5),
+            "9A0A8659F005D6984697E2CA0A9CF3B7"
+        );
+        assert_eq!(
+            sign(&params, key, SignType::HmacSha256),
+            "6A9AE1657590FD6257D693A078E1C3E4BB6BA4DC30B23E0EE2496E54170DACD6"
+        );
+
+        params.insert("attach".to_string(), "".to_string());
+        params.insert("sign".to_string(), sign(&params, key, SignType::Md5));
+        assert!(verify(&params, key, SignType::Md5).is_ok());
+        params.insert("body".to_string(), "test2".to_string());
+        assert!(verify(&params, key, SignType::Md5).is_err());
+    }
+
+    #[test]
+    fn test_sign_send_coupon() -> anyhow::Result<()> {
+        let key = "192006250b4c09247ec02edce69f6a2d";
+        let stocks = vec![SendCouponStock {
+            stock_id: "1234567".to_string(),
+            out_request_no: "89560002019101000121".to_string(),
+        }];
+        let res = sign_send_coupon("10016226", stocks, key);
+        assert_eq!(
+            res.sign,
+            "E1245E277617582BF4D2428D01F8968D19870B64159BF6C92F7FD24D1003FF27"
+        );
+        let json = serde_json::to_value(&res)?;
+        assert_eq!(json["send_coupon_params"][0]["stock_id"], "1234567");
+        assert_eq!(json["send_coupon_merchant"], "10016226");
+        Ok(())
+    }
+
+    #[test]
+    fn test_xml() -> anyhow::Result<()> {
+        let mut params = doc_params();
+        params.insert("detail".to_string(), "<a>&]]>b".to_string());
+        let xml = to_xml(&params);
+        assert_eq!(from_xml(&xml)?, params);
+
+        let xml = "<xml>\n  <return_code><![CDATA[SUCCESS]]></return_code>\n  <total_fee>100</total_fee>\n  <return_msg>a&amp;b</return_msg>\n  <empty/>\n</xml>";
+        let params = from_xml(xml)?;
+        assert_eq!(params["return_code"], "SUCCESS");
+        assert_eq!(params["total_fee"], "100");
+        assert_eq!(params["return_msg"], "a&b");
+        assert_eq!(params["empty"], "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_response() -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct Params {
+            mch_id: &'static str,
+            total_amount: i32,
+            remark: Option<String>,
+        }
+        let params = to_params(&Params {
+            mch_id: "10000100",
+            total_amount: 100,
+            remark: None,
+        })?;
+        assert_eq!(params["total_amount"], "100");
+        assert!(!params.contains_key("remark"));
+
+        let key = "192006250b4c09247ec02edce69f6a2d";
+        let client = V2Client::builder().api_key(key.to_string()).build()?;
+
+        let mut res = doc_params();
+        res.insert("return_code".to_string(), "SUCCESS".to_string());
+        res.insert("result_code".to_string(), "SUCCESS".to_string());
+        res.insert("sign".to_string(), sign(&res, key, SignType::Md5));
+        assert_eq!(
+            client.check_response(&to_xml(&res), SignType::Md5)?["mch_id"],
+            "10000100"
+        );
+
+        res.insert("sign".to_string(), "0".repeat(32));
+        let err = client.check_response(&to_xml(&res), SignType::Md5);
+        assert!(matches!(err, Err(WechatPayError::Verify(_))));
+
+        // 响应未返回 sign_type 时，使用请求的签名类型
+        res.remove("sign");
+        res.insert("sign".to_string(), sign(&res, key, SignType::HmacSha256));
+        let xml = to_xml(&res);
+        assert_eq!(
+            client.check_response(&xml, SignType::HmacSha256)?["mch_id"],
+            "10000100"
+        );
+        let err = client.check_response(&xml, SignType::Md5);
+        assert!(matches!(err, Err(WechatPayError::Verify(_))));
+
+        let xml = "<xml><return_code>SUCCESS</return_code><result_code>FAIL</result_code><err_code>NOTENOUGH</err_code><err_code_des>余额不足</err_code_des></xml>";
+        let err = client.check_response(xml, SignType::Md5).unwrap_err();
+        assert_eq!(err.api_error().unwrap().code(), "NOTENOUGH");
+        Ok(())
+    }
+
+    /// 在后台线程中应答一次 HTTP 请求，返回 base_url 及收到的请求。
+    fn serve_once(body: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut req = vec![];
+            let mut buf = [0; 4096];
+            while !String::from_utf8_lossy(&req).ends_with("</xml>") {
+                let n = stream.read(&mut buf).unwrap();
+                req.extend_from_slice(&buf[..n]);
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            String::from_utf8(req).unwrap()
+        });
+        (base_url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_sandbox() -> anyhow::Result<()> {
+        let key = "192006250b4c09247ec02edce69f6a2d";
+        let (base_url, handle) = serve_once(
+            "<xml><return_code>SUCCESS</return_code><return_msg>ok</return_msg><sandbox_signkey>sandbox_key</sandbox_signkey></xml>",
+        );
+        let client = V2Client::builder()
+            .api_key(key.to_string())
+            .base_url(base_url.clone())
+            .build()?;
+        let sandbox = client.sandbox("10000100").await?;
+        let req = handle.join().unwrap();
+        assert!(req.starts_with("POST /sandboxnew/pay/getsignkey "));
+        let (_, body) = req.split_once("\r\n\r\n").unwrap();
+        assert!(verify(&from_xml(body)?, key, SignType::Md5).is_ok());
+
+        assert_eq!(sandbox.api_key, "sandbox_key");
+        assert_eq!(sandbox.base_url, format!("{}/sandboxnew", base_url));
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! 商户违规通知。
+//! 商户因违规被微信支付处置(如限制收款、拦截交易)时，微信支付会向商户配置的回调地址发送通知。
+//! 回调地址通过本模块的接口管理，通知解密后为 `ViolationNotification`。
+
+use crate::client::WechatPayClient;
+use crate::error::Result;
+use crate::util::datetime_fmt;
+use chrono::{DateTime, Local};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+impl WechatPayClient {
+    /// 创建商户违规通知回调地址。
+    pub async fn create_violation_notification(
+        &self,
+        notify_url: &str,
+    ) -> Result<ViolationNotificationConfig> {
+        self.violation_notification_request(Method::POST, Some(notify_url))
+            .await
+    }
+
+    /// 查询商户违规通知回调地址。
+    pub async fn query_violation_notification(&self) -> Result<ViolationNotificationConfig> {
+        self.violation_notification_request(Method::GET, None).await
+    }
+
+    /// 修改商户违规通知回调地址。
+    pub async fn update_violation_notification(
+        &self,
+        notify_url: &str,
+    ) -> Result<ViolationNotificationConfig> {
+        self.violation_notification_request(Method::PUT, Some(notify_url))
+            .await
+    }
+
+    /// 删除商户违规通知回调地址。
+    pub async fn delete_violation_notification(&self) -> Result<()> {
+        let url = violation_notification_url(&self.base_url);
+        let req = self.client.delete(url).build()?;
+        self.execute(req).await?;
+        Ok(())
+    }
+
+    async fn violation_notification_request(
+        &self,
+        method: Method,
+        notify_url: Option<&str>,
+    ) -> Result<ViolationNotificationConfig> {
+        #[derive(Debug, Serialize)]
+        struct Params<'a> {
+            notify_url: &'a str,
+        }
+
+        let url = violation_notification_url(&self.base_url);
+        let mut req = self.client.request(method, url);
+        if let Some(notify_url) = notify_url {
+            req = req.json(&Params { notify_url });
+        }
+        self.execute_json(req.build()?).await
+    }
+}
+
+fn violation_notification_url(base_url: &str) -> String {
+    format!("{}/merchant-risk-manage/violation-notifications", base_url)
+}
+
+/// 商户违规通知回调地址的配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ViolationNotificationConfig {
+    /// 商户号
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mchid: Option<String>,
+    /// 回调地址
+    pub notify_url: String,
+    /// 更新时间
+    #[serde(with = "datetime_fmt")]
+    pub update_time: DateTime<Local>,
+}
+
+/// 商户违规通知解密后的资源数据。
+/// 对应 event_type: VIOLATION.PUNISH、VIOLATION.INTERCEPT、VIOLATION.APPEAL。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ViolationNotification {
+    /// 违规的(子)商户号
+    pub sub_mchid: String,
+    /// 商户名称
+    pub company_name: String,
+    /// 通知记录 ID
+    pub record_id: String,
+    /// 处罚方案，如“关闭支付权限”
+    pub punish_plan: String,
+    /// 处罚时间
+    #[serde(with = "datetime_fmt")]
+    pub punish_time: DateTime<Local>,
+    /// 处罚方案的说明
+    pub punish_description: String,
+    /// 风险类型
+    pub risk_type: String,
+    /// 风险描述
+    pub risk_description: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock_client;
+    use reqwest::StatusCode;
+
+    #[tokio::test]
+    async fn test_violation_notification() -> anyhow::Result<()> {
+        let (client, mock) = mock_client().await?;
+        let path = "/v3/merchant-risk-manage/violation-notifications";
+        let body = r#"{"mchid":"1900000001","notify_url":"https://www.weixin.qq.com/wxpay/pay.php","update_time":"2015-05-20T13:29:35+08:00"}"#;
+        mock.on(Method::PUT, path, StatusCode::OK, body);
+        mock.on(Method::DELETE, path, StatusCode::NO_CONTENT, "");
+
+        let config = client
+            .update_violation_notification("https://www.weixin.qq.com/wxpay/pay.php")
+            .await?;
+        assert_eq!(config.notify_url, "https://www.weixin.qq.com/wxpay/pay.php");
+        let req: serde_json::Value = serde_json::from_slice(&mock.requests()[0].body)?;
+        assert_eq!(req["notify_url"], "https://www.weixin.qq.com/wxpay/pay.php");
+
+        client.delete_violation_notification().await?;
+        assert_eq!(mock.requests()[1].method, Method::DELETE);
+        Ok(())
+    }
+}